@@ -0,0 +1,191 @@
+use crate::error::Error;
+use crate::utils;
+use serde::{Serialize, Deserialize};
+
+/// Outcome of checking a user-supplied address.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AddressStatus {
+    /// Not `0x` followed by 40 hex digits.
+    Malformed,
+    /// Mixed case that does not match the checksum; almost always a typo.
+    BadChecksum,
+    /// All lowercase (or all uppercase), so there is no checksum to verify.
+    ValidUnchecksummed,
+    ValidChecksummed,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AddressValidation {
+    pub status: AddressStatus,
+    pub valid: bool,
+    /// The correctly checksummed form, when the input was well-formed.
+    pub checksummed: Option<String>,
+}
+
+/// Renders 20 address bytes with an EIP-55 checksum, or the EIP-1191 variant
+/// when `chain_id` is given (RSK and other chains that adopted it).
+pub fn to_checksum_address(address: &[u8; 20], chain_id: Option<u64>) -> String {
+    let lower = hex::encode(address);
+    let hash_input = match chain_id {
+        Some(chain_id) => format!("{}0x{}", chain_id, lower),
+        None => lower.clone(),
+    };
+    let hash = utils::keccak256(hash_input.as_bytes());
+
+    let checksummed: String = lower
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            let nibble = (hash[i / 2] >> if i % 2 == 0 { 4 } else { 0 }) & 0x0f;
+            if nibble >= 8 { c.to_ascii_uppercase() } else { c }
+        })
+        .collect();
+    format!("0x{}", checksummed)
+}
+
+pub fn parse_address(address: &str) -> Result<[u8; 20], Error> {
+    let invalid = |reason: String| Error::InvalidAddress { address: address.to_string(), reason };
+    let digits = address
+        .trim()
+        .strip_prefix("0x")
+        .ok_or_else(|| invalid("must start with 0x".to_string()))?;
+    if digits.len() != 40 {
+        return Err(invalid(format!("must have 40 hex digits, got {}", digits.len())));
+    }
+    let mut bytes = [0u8; 20];
+    hex::decode_to_slice(digits, &mut bytes)
+        .map_err(|e| invalid(format!("not hex: {}", e)))?;
+    Ok(bytes)
+}
+
+pub fn validate_address(address: &str, chain_id: Option<u64>) -> AddressValidation {
+    let bytes = match parse_address(address) {
+        Ok(bytes) => bytes,
+        Err(_) => {
+            return AddressValidation { status: AddressStatus::Malformed, valid: false, checksummed: None };
+        }
+    };
+
+    let digits = &address.trim()[2..];
+    let checksummed = to_checksum_address(&bytes, chain_id);
+    let status = if digits == digits.to_ascii_lowercase() || digits == digits.to_ascii_uppercase() {
+        AddressStatus::ValidUnchecksummed
+    } else if digits == &checksummed[2..] {
+        AddressStatus::ValidChecksummed
+    } else {
+        AddressStatus::BadChecksum
+    };
+
+    AddressValidation {
+        status,
+        valid: status != AddressStatus::BadChecksum,
+        checksummed: Some(checksummed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checksum(address: &str, chain_id: Option<u64>) -> String {
+        to_checksum_address(&parse_address(address).unwrap(), chain_id)
+    }
+
+    fn check_vectors(vectors: &[&str], chain_id: Option<u64>) {
+        for expected in vectors {
+            assert_eq!(checksum(&expected.to_lowercase(), chain_id), *expected, "chain {:?}", chain_id);
+        }
+    }
+
+    #[test]
+    fn eip55_vectors() {
+        check_vectors(
+            &[
+                "0x52908400098527886E0F7030069857D2E4169EE7",
+                "0x8617E340B3D01FA5F11F306F4090FD50E238070D",
+                "0xde709f2102306220921060314715629080e2fb77",
+                "0x27b1fdb04752bbc536007a920d24acb045561c26",
+                "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+                "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+                "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+                "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+            ],
+            None,
+        );
+    }
+
+    #[test]
+    fn eip1191_rsk_mainnet_vectors() {
+        check_vectors(
+            &[
+                "0x27b1FdB04752BBc536007A920D24ACB045561c26",
+                "0x3599689E6292B81B2D85451025146515070129Bb",
+                "0x42712D45473476B98452f434E72461577d686318",
+                "0x52908400098527886E0F7030069857D2E4169ee7",
+                "0x5aaEB6053f3e94c9b9a09f33669435E7ef1bEAeD",
+                "0x6549F4939460DE12611948B3F82B88C3C8975323",
+                "0x8617E340b3D01Fa5f11f306f4090fd50E238070D",
+                "0x88021160c5C792225E4E5452585947470010289d",
+                "0xD1220A0Cf47c7B9BE7a2e6ba89F429762E7B9adB",
+                "0xDBF03B407c01E7CD3cBea99509D93F8Dddc8C6FB",
+                "0xDe709F2102306220921060314715629080e2FB77",
+            ],
+            Some(30),
+        );
+    }
+
+    #[test]
+    fn eip1191_rsk_testnet_vectors() {
+        check_vectors(
+            &[
+                "0x3599689e6292b81b2D85451025146515070129Bb",
+                "0x42712D45473476B98452F434E72461577D686318",
+                "0x52908400098527886E0F7030069857D2e4169EE7",
+                "0x5aAeb6053F3e94c9b9A09F33669435E7EF1BEaEd",
+                "0x8617e340b3D01fa5F11f306F4090Fd50e238070d",
+                "0x88021160c5C792225E4E5452585947470010289d",
+                "0xd1220a0CF47c7B9Be7A2E6Ba89f429762E7b9adB",
+                "0xdbF03B407C01E7cd3cbEa99509D93f8dDDc8C6fB",
+                "0xDE709F2102306220921060314715629080e2Fb77",
+                "0xFb6916095CA1dF60bb79CE92ce3Ea74C37c5D359",
+            ],
+            Some(31),
+        );
+    }
+
+    #[test]
+    fn validate_address_statuses() {
+        let checksummed = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+
+        let validation = validate_address(checksummed, None);
+        assert_eq!(validation.status, AddressStatus::ValidChecksummed);
+        assert!(validation.valid);
+        assert_eq!(validation.checksummed.as_deref(), Some(checksummed));
+
+        let validation = validate_address(&checksummed.to_lowercase(), None);
+        assert_eq!(validation.status, AddressStatus::ValidUnchecksummed);
+        assert!(validation.valid);
+        assert_eq!(validation.checksummed.as_deref(), Some(checksummed));
+
+        let typo = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD";
+        let validation = validate_address(typo, None);
+        assert_eq!(validation.status, AddressStatus::BadChecksum);
+        assert!(!validation.valid);
+        assert_eq!(validation.checksummed.as_deref(), Some(checksummed));
+
+        // An EIP-55 checksum is wrong under EIP-1191, and the other way round.
+        assert_eq!(validate_address(checksummed, Some(30)).status, AddressStatus::BadChecksum);
+        assert_eq!(
+            validate_address("0x5aaEB6053f3e94c9b9a09f33669435E7ef1bEAeD", Some(30)).status,
+            AddressStatus::ValidChecksummed
+        );
+
+        for malformed in ["", "5aaeb6053f3e94c9b9a09f33669435e7ef1beaed", "0x5aaeb6053f", "0xzzaeb6053f3e94c9b9a09f33669435e7ef1beaed"] {
+            let validation = validate_address(malformed, None);
+            assert_eq!(validation.status, AddressStatus::Malformed, "{:?}", malformed);
+            assert!(!validation.valid);
+            assert_eq!(validation.checksummed, None);
+        }
+    }
+}
@@ -0,0 +1,525 @@
+//! `dwss`: mnemonic and split tooling for offline (air-gapped) machines.
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use dwss_rust_core::derivation_path::{self, DerivationPath, PathTemplate, PathValues};
+use dwss_rust_core::distribution::{self, DistributionParams, FeePolicy};
+use dwss_rust_core::hd_wallet::HdWallet;
+use dwss_rust_core::split_plan::{AmountPlan, AmountStrategy};
+use dwss_rust_core::sweep::{self, ChildBalance, SweepParams};
+use dwss_rust_core::transaction::{quantity, TRANSFER_GAS_LIMIT};
+use dwss_rust_core::{bip39, bip85::Bip85, compat, Error, WalletInfo, DEFAULT_SPLIT_CHILD_COUNT, FIRST_SPLIT_CHILD};
+use serde::Serialize;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use zeroize::Zeroizing;
+
+#[derive(Parser)]
+#[command(name = "dwss", version, about = "Deterministic wallet splitter for offline use")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generate a new mnemonic
+    Generate {
+        #[arg(long, default_value_t = bip39::DEFAULT_WORD_COUNT)]
+        words: usize,
+        /// Wordlist code, e.g. en, ja, es
+        #[arg(long, default_value = "en")]
+        language: String,
+    },
+    /// Check a mnemonic and explain what is wrong with it
+    Validate {
+        #[command(flatten)]
+        mnemonic: MnemonicArgs,
+    },
+    /// Derive the parent wallet
+    Parent {
+        #[command(flatten)]
+        mnemonic: MnemonicArgs,
+        #[arg(long, default_value = derivation_path::DEFAULT_PARENT_PATH)]
+        path: String,
+        #[arg(long)]
+        include_private_keys: bool,
+    },
+    /// Derive child wallets
+    Children {
+        #[command(flatten)]
+        mnemonic: MnemonicArgs,
+        #[command(flatten)]
+        children: ChildArgs,
+        #[arg(long)]
+        include_private_keys: bool,
+    },
+    /// Print the parent and children of a split
+    Split {
+        #[command(flatten)]
+        mnemonic: MnemonicArgs,
+        #[arg(long, default_value = derivation_path::DEFAULT_PARENT_PATH)]
+        parent_path: String,
+        #[command(flatten)]
+        children: ChildArgs,
+        #[arg(long, value_enum, default_value_t = Format::Json)]
+        format: Format,
+        #[arg(long)]
+        include_private_keys: bool,
+    },
+    /// List children under both the default and the ethers-legacy layout
+    Report {
+        #[command(flatten)]
+        mnemonic: MnemonicArgs,
+        /// First child to list. Defaults to 1 for the default layout, whose
+        /// child 0 is the parent, and to 0 for ethers-legacy
+        #[arg(long)]
+        start: Option<u32>,
+        #[arg(long, default_value_t = DEFAULT_SPLIT_CHILD_COUNT)]
+        count: u32,
+    },
+    /// Split a mnemonic into SeedXOR parts, one per line
+    SeedXorSplit {
+        #[command(flatten)]
+        mnemonic: MnemonicArgs,
+        #[arg(long, default_value_t = 3)]
+        parts: usize,
+    },
+    /// Combine SeedXOR parts read from stdin, one per line
+    SeedXorCombine,
+    /// Derive independent child mnemonics (BIP-85)
+    Bip85 {
+        #[command(flatten)]
+        mnemonic: MnemonicArgs,
+        #[arg(long, default_value_t = bip39::DEFAULT_WORD_COUNT)]
+        words: usize,
+        /// Wordlist code of the child mnemonics
+        #[arg(long, default_value = "en")]
+        language: String,
+        #[arg(long, default_value_t = 0)]
+        start: u32,
+        #[arg(long, default_value_t = 1)]
+        count: u32,
+    },
+    /// Sign one funding transfer from the parent to each child, with
+    /// sequential nonces, for broadcasting later
+    Distribute {
+        #[command(flatten)]
+        mnemonic: MnemonicArgs,
+        /// First child to fund; child 0 is the parent itself
+        #[arg(long, default_value_t = FIRST_SPLIT_CHILD)]
+        start: u32,
+        #[arg(long, default_value_t = DEFAULT_SPLIT_CHILD_COUNT)]
+        count: u32,
+        #[arg(long)]
+        chain_id: u64,
+        /// The parent's next nonce
+        #[arg(long)]
+        nonce: u64,
+        #[command(flatten)]
+        fees: FeeArgs,
+        #[command(flatten)]
+        amounts: AmountArgs,
+        #[arg(long, default_value_t = TRANSFER_GAS_LIMIT)]
+        gas_limit: u64,
+    },
+    /// Sign "send max minus fee" transfers from every funded child back to one address
+    Sweep {
+        #[command(flatten)]
+        mnemonic: MnemonicArgs,
+        /// First child to sweep; child 0 is the parent itself
+        #[arg(long, default_value_t = FIRST_SPLIT_CHILD)]
+        start: u32,
+        #[arg(long, default_value_t = DEFAULT_SPLIT_CHILD_COUNT)]
+        count: u32,
+        #[arg(long)]
+        chain_id: u64,
+        /// Address that receives the funds
+        #[arg(long)]
+        to: String,
+        /// JSON file of `[{ "address", "balance", "nonce" }]` for the children
+        #[arg(long)]
+        balances: PathBuf,
+        #[command(flatten)]
+        fees: FeeArgs,
+        #[arg(long, default_value_t = TRANSFER_GAS_LIMIT)]
+        gas_limit: u64,
+    },
+}
+
+/// Fees in wei, as decimal or `0x` hex.
+#[derive(Args)]
+struct FeeArgs {
+    /// Sign legacy (type 0) transactions at this gas price
+    #[arg(long, conflicts_with_all = ["max_fee", "priority_fee"])]
+    gas_price: Option<String>,
+    /// EIP-1559 max fee per gas
+    #[arg(long, requires = "priority_fee")]
+    max_fee: Option<String>,
+    /// EIP-1559 max priority fee per gas
+    #[arg(long, requires = "max_fee")]
+    priority_fee: Option<String>,
+}
+
+/// Amounts in wei, as decimal or `0x` hex.
+#[derive(Args)]
+struct AmountArgs {
+    /// Wei sent to each child
+    #[arg(long, conflicts_with = "total", required_unless_present = "total")]
+    amount: Option<String>,
+    /// Wei to split across the children, gas for every transfer included
+    #[arg(long)]
+    total: Option<String>,
+    /// Comma-separated weights, one per child, for splitting --total
+    #[arg(long, value_delimiter = ',', requires = "total", conflicts_with = "random")]
+    weights: Vec<u64>,
+    /// Split --total into random amounts
+    #[arg(long, requires = "total")]
+    random: bool,
+    /// Seed for --random, to reproduce a plan
+    #[arg(long, requires = "random")]
+    seed: Option<u64>,
+    #[arg(long)]
+    min_amount: Option<String>,
+    #[arg(long)]
+    max_amount: Option<String>,
+}
+
+#[derive(Args)]
+struct MnemonicArgs {
+    /// Mnemonic phrase. Read from stdin when omitted, which keeps it out of
+    /// shell history.
+    #[arg(long)]
+    mnemonic: Option<String>,
+    /// BIP39 passphrase
+    #[arg(long)]
+    passphrase: Option<String>,
+}
+
+#[derive(Args)]
+struct ChildArgs {
+    /// First child to list; child 0 is the parent itself
+    #[arg(long, default_value_t = FIRST_SPLIT_CHILD)]
+    start: u32,
+    #[arg(long, default_value_t = DEFAULT_SPLIT_CHILD_COUNT)]
+    count: u32,
+    /// Child path template or preset name (default, ledger-live, legacy-mew, ethers-legacy)
+    #[arg(long, default_value = "default")]
+    template: String,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Json,
+    Csv,
+}
+
+/// One derived wallet, as printed. Private keys are left out unless asked for.
+#[derive(Serialize)]
+struct WalletRow {
+    role: &'static str,
+    index: Option<u32>,
+    path: String,
+    address: String,
+    public_key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    private_key: Option<Zeroizing<String>>,
+}
+
+impl WalletRow {
+    fn new(role: &'static str, index: Option<u32>, path: String, wallet: WalletInfo, include_private_key: bool) -> Self {
+        WalletRow {
+            role,
+            index,
+            path,
+            address: wallet.address,
+            public_key: wallet.public_key,
+            private_key: include_private_key.then(|| wallet.private_key.to_hex()),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct SplitPlan {
+    child_template: String,
+    child_start_index: u32,
+    parent: WalletRow,
+    children: Vec<WalletRow>,
+}
+
+impl SplitPlan {
+    fn new(wallet: &HdWallet, parent_path: &DerivationPath, children: &ChildArgs, include_private_keys: bool) -> Result<Self, Error> {
+        Ok(SplitPlan {
+            child_template: children.template()?.to_string(),
+            child_start_index: children.start,
+            parent: WalletRow::new(
+                "parent",
+                None,
+                parent_path.to_string(),
+                wallet.derive_path(parent_path)?,
+                include_private_keys,
+            ),
+            children: children.rows(wallet, include_private_keys)?,
+        })
+    }
+
+    /// The parent followed by the children.
+    fn rows(&self) -> Vec<&WalletRow> {
+        std::iter::once(&self.parent).chain(&self.children).collect()
+    }
+}
+
+impl MnemonicArgs {
+    fn phrase(&self) -> Result<Zeroizing<String>, Box<dyn std::error::Error>> {
+        match &self.mnemonic {
+            Some(mnemonic) => Ok(Zeroizing::new(mnemonic.clone())),
+            None => {
+                let mut line = Zeroizing::new(String::new());
+                io::stdin().lock().read_line(&mut line)?;
+                Ok(Zeroizing::new(line.trim().to_string()))
+            }
+        }
+    }
+
+    fn wallet(&self) -> Result<HdWallet, Box<dyn std::error::Error>> {
+        Ok(HdWallet::from_mnemonic(&self.phrase()?, self.passphrase.as_deref())?)
+    }
+}
+
+impl FeeArgs {
+    fn policy(&self) -> Result<FeePolicy, Error> {
+        match (&self.gas_price, &self.max_fee, &self.priority_fee) {
+            (Some(gas_price), None, None) => Ok(FeePolicy::Legacy { gas_price: quantity::parse(gas_price)? }),
+            (None, Some(max_fee), Some(priority_fee)) => Ok(FeePolicy::Eip1559 {
+                max_fee_per_gas: quantity::parse(max_fee)?,
+                max_priority_fee_per_gas: quantity::parse(priority_fee)?,
+            }),
+            _ => Err(Error::invalid_argument("fees", "pass --gas-price, or --max-fee with --priority-fee")),
+        }
+    }
+}
+
+impl AmountArgs {
+    fn plan(&self) -> Result<AmountPlan, Error> {
+        let strategy = match (&self.amount, &self.total) {
+            (Some(amount), _) => AmountStrategy::Fixed { amount: quantity::parse(amount)? },
+            (None, Some(total)) => {
+                let total = quantity::parse(total)?;
+                if self.random {
+                    AmountStrategy::Random { total, seed: self.seed }
+                } else if !self.weights.is_empty() {
+                    AmountStrategy::Weighted { total, weights: self.weights.clone() }
+                } else {
+                    AmountStrategy::Equal { total }
+                }
+            }
+            (None, None) => return Err(Error::invalid_argument("amounts", "pass --amount or --total")),
+        };
+        Ok(AmountPlan {
+            strategy,
+            min_amount: self.min_amount.as_deref().map(quantity::parse).transpose()?,
+            max_amount: self.max_amount.as_deref().map(quantity::parse).transpose()?,
+            gas_reserve: None,
+        })
+    }
+}
+
+impl ChildArgs {
+    fn template(&self) -> Result<PathTemplate, Error> {
+        derivation_path::template_preset(&self.template)
+            .unwrap_or(&self.template)
+            .parse()
+    }
+
+    fn rows(&self, wallet: &HdWallet, include_private_keys: bool) -> Result<Vec<WalletRow>, Error> {
+        let template = self.template()?;
+        let wallets = wallet.children_with_template(&template, PathValues::default(), self.start, self.count)?;
+        (self.start..)
+            .zip(wallets)
+            .map(|(index, child)| {
+                let path = template.resolve(PathValues { index, ..PathValues::default() })?;
+                Ok(WalletRow::new("child", Some(index), path.to_string(), child, include_private_keys))
+            })
+            .collect()
+    }
+}
+
+fn print_json<T: Serialize>(value: &T) -> Result<(), Box<dyn std::error::Error>> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+fn write_csv(out: &mut impl Write, rows: &[&WalletRow]) -> io::Result<()> {
+    let include_private_keys = rows.iter().any(|row| row.private_key.is_some());
+    let mut header = vec!["role", "index", "path", "address", "public_key"];
+    if include_private_keys {
+        header.push("private_key");
+    }
+    writeln!(out, "{}", header.join(","))?;
+
+    for row in rows {
+        let mut fields = Zeroizing::new(vec![
+            row.role.to_string(),
+            row.index.map(|i| i.to_string()).unwrap_or_default(),
+            row.path.clone(),
+            row.address.clone(),
+            row.public_key.clone(),
+        ]);
+        if let Some(private_key) = &row.private_key {
+            fields.push(private_key.to_string());
+        }
+        writeln!(out, "{}", fields.join(","))?;
+    }
+    Ok(())
+}
+
+fn run(command: Command) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    match command {
+        Command::Generate { words, language } => {
+            let language = bip39::language_from_code(&language)?;
+            println!("{}", bip39::generate_mnemonic(language, words)?.expose());
+        }
+        Command::Validate { mnemonic } => {
+            let validation = bip39::validate_mnemonic_detailed(&mnemonic.phrase()?);
+            print_json(&validation)?;
+            if !validation.valid {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Command::Parent { mnemonic, path, include_private_keys } => {
+            let path: DerivationPath = path.parse()?;
+            let wallet = mnemonic.wallet()?.derive_path(&path)?;
+            print_json(&WalletRow::new("parent", None, path.to_string(), wallet, include_private_keys))?;
+        }
+        Command::Children { mnemonic, children, include_private_keys } => {
+            print_json(&children.rows(&mnemonic.wallet()?, include_private_keys)?)?;
+        }
+        Command::Split { mnemonic, parent_path, children, format, include_private_keys } => {
+            let plan = SplitPlan::new(&mnemonic.wallet()?, &parent_path.parse()?, &children, include_private_keys)?;
+            match format {
+                Format::Json => print_json(&plan)?,
+                Format::Csv => write_csv(&mut io::stdout().lock(), &plan.rows())?,
+            }
+        }
+        Command::Report { mnemonic, start, count } => {
+            print_json(&compat::recovery_report(&mnemonic.wallet()?, start, count)?)?;
+        }
+        Command::SeedXorSplit { mnemonic, parts } => {
+            for part in bip39::seed_xor_split(&mnemonic.phrase()?, parts)? {
+                println!("{}", part.expose());
+            }
+        }
+        Command::SeedXorCombine => {
+            let parts = io::stdin()
+                .lock()
+                .lines()
+                .map(|line| line.map(|line| Zeroizing::new(line.trim().to_string())))
+                .filter(|line| !matches!(line, Ok(line) if line.is_empty()))
+                .collect::<Result<Vec<_>, _>>()?;
+            let parts: Vec<&str> = parts.iter().map(|part| part.as_str()).collect();
+            println!("{}", bip39::seed_xor_combine(&parts)?.expose());
+        }
+        Command::Bip85 { mnemonic, words, language, start, count } => {
+            let language = bip39::language_from_code(&language)?;
+            let bip85 = Bip85::from_mnemonic(&mnemonic.phrase()?, mnemonic.passphrase.as_deref())?;
+            for index in start..start.saturating_add(count) {
+                println!("{}\t{}", index, bip85.mnemonic(language, words, index)?.expose());
+            }
+        }
+        Command::Distribute { mnemonic, start, count, chain_id, nonce, fees, amounts, gas_limit } => {
+            let params = DistributionParams {
+                chain_id,
+                start_nonce: nonce,
+                fees: fees.policy()?,
+                amounts: amounts.plan()?,
+                gas_limit,
+            };
+            print_json(&distribution::sign_split_distribution(&mnemonic.wallet()?, start, count, &params)?)?;
+        }
+        Command::Sweep { mnemonic, start, count, chain_id, to, balances, fees, gas_limit } => {
+            let balances: Vec<ChildBalance> = serde_json::from_str(&fs::read_to_string(balances)?)?;
+            let params = SweepParams { chain_id, destination: to, fees: fees.policy()?, gas_limit };
+            print_json(&sweep::sign_split_sweep(&mnemonic.wallet()?, start, count, &balances, &params)?)?;
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse().command) {
+        Ok(code) => code,
+        Err(e) => {
+            match e.downcast_ref::<Error>().and_then(|e| serde_json::to_value(e).ok()) {
+                Some(error) => eprintln!("error [{}]: {}", error["code"].as_str().unwrap_or("UNKNOWN"), e),
+                None => eprintln!("error: {}", e),
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    const HARDHAT: &str = "test test test test test test test test test test test junk";
+
+    fn split_plan(extra: &[&str]) -> SplitPlan {
+        let args = ["dwss", "split", "--mnemonic", HARDHAT, "--count", "2"].iter().chain(extra);
+        match Cli::try_parse_from(args).unwrap().command {
+            Command::Split { mnemonic, parent_path, children, include_private_keys, .. } => {
+                SplitPlan::new(&mnemonic.wallet().unwrap(), &parent_path.parse().unwrap(), &children, include_private_keys)
+                    .unwrap()
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn csv(plan: &SplitPlan) -> Vec<Vec<String>> {
+        let mut out = Vec::new();
+        write_csv(&mut out, &plan.rows()).unwrap();
+        String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| line.split(',').map(str::to_string).collect())
+            .collect()
+    }
+
+    #[test]
+    fn cli_definition_is_consistent() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn split_csv_lists_the_parent_then_the_children() {
+        let lines = csv(&split_plan(&["--format", "csv"]));
+        assert_eq!(lines[0], ["role", "index", "path", "address", "public_key"]);
+        let rows: Vec<_> = lines[1..].iter().map(|line| line[..4].join(",")).collect();
+        assert_eq!(
+            rows,
+            [
+                "parent,,m/44'/60'/0'/0/0,0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266",
+                "child,1,m/44'/60'/0'/0/1,0x70997970C51812dc3A010C7d01b50e0d17dc79C8",
+                "child,2,m/44'/60'/0'/0/2,0x3C44CdDdB6a900fa2b585dd299e03d12FA4293BC",
+            ]
+        );
+        assert!(lines.iter().all(|line| line.len() == 5));
+    }
+
+    #[test]
+    fn private_keys_are_omitted_unless_requested() {
+        let plan = split_plan(&[]);
+        assert!(plan.rows().iter().all(|row| row.private_key.is_none()));
+        assert!(!serde_json::to_string(&plan).unwrap().contains("private_key"));
+
+        let plan = split_plan(&["--include-private-keys"]);
+        let json = serde_json::to_value(&plan).unwrap();
+        assert_eq!(json["parent"]["private_key"], "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80");
+        assert_eq!(json["children"][0]["private_key"], "59c6995e998f97a5a0044966f0945389dc9e86dae88c7a8412f4603b6b78690d");
+        let lines = csv(&plan);
+        assert_eq!(lines[0].last().unwrap(), "private_key");
+        assert_eq!(lines[2][5], "59c6995e998f97a5a0044966f0945389dc9e86dae88c7a8412f4603b6b78690d");
+    }
+}
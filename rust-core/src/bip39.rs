@@ -1,18 +1,455 @@
-use bip39::{Mnemonic, MnemonicType, Language};
-use rand::Rng;
+use crate::error::Error;
+use crate::secret::{SecretPhrase, Seed};
+use bip39::{Mnemonic, Language};
+use rand::RngCore;
+use serde::{Serialize, Deserialize};
+use zeroize::{Zeroize, Zeroizing};
 
-pub fn generate_mnemonic() -> Result<String, Box<dyn std::error::Error>> {
-    let mut rng = rand::thread_rng();
-    let mnemonic = Mnemonic::new(MnemonicType::Words24, Language::English);
-    Ok(mnemonic.phrase().to_string())
+/// Word counts accepted for generation, matching 128..=256 bits of entropy.
+pub const SUPPORTED_WORD_COUNTS: [usize; 5] = [12, 15, 18, 21, 24];
+/// Word count used when the caller does not choose one (256 bits of entropy).
+pub const DEFAULT_WORD_COUNT: usize = 24;
+
+/// Language codes as exposed over WASM, paired with their wordlist.
+const LANGUAGE_CODES: [(&str, Language); 10] = [
+    ("en", Language::English),
+    ("ja", Language::Japanese),
+    ("es", Language::Spanish),
+    ("zh-hans", Language::SimplifiedChinese),
+    ("zh-hant", Language::TraditionalChinese),
+    ("ko", Language::Korean),
+    ("fr", Language::French),
+    ("it", Language::Italian),
+    ("cs", Language::Czech),
+    ("pt", Language::Portuguese),
+];
+
+pub fn language_from_code(code: &str) -> Result<Language, Error> {
+    let code = code.trim().to_lowercase();
+    LANGUAGE_CODES
+        .iter()
+        .find(|(c, language)| *c == code || language.to_string().to_lowercase() == code)
+        .map(|(_, language)| *language)
+        .ok_or(Error::UnsupportedLanguage { language: code })
+}
+
+pub fn language_code(language: Language) -> &'static str {
+    LANGUAGE_CODES
+        .iter()
+        .find(|(_, l)| *l == language)
+        .map(|(c, _)| *c)
+        .unwrap_or("en")
+}
+
+pub fn supported_language_codes() -> Vec<&'static str> {
+    LANGUAGE_CODES.iter().map(|(c, _)| *c).collect()
+}
+
+pub fn generate_mnemonic(language: Language, word_count: usize) -> Result<SecretPhrase, Error> {
+    if !SUPPORTED_WORD_COUNTS.contains(&word_count) {
+        return Err(Error::InvalidWordCount { word_count });
+    }
+    let mnemonic = Mnemonic::generate_in(language, word_count)?;
+    Ok(format_phrase(&mnemonic))
+}
+
+/// Renders a mnemonic for display. Japanese phrases use the ideographic
+/// space as separator, as recommended by BIP39.
+pub fn format_phrase(mnemonic: &Mnemonic) -> SecretPhrase {
+    let separator = if mnemonic.language() == Language::Japanese { "\u{3000}" } else { " " };
+    SecretPhrase::new(mnemonic.words().collect::<Vec<_>>().join(separator))
+}
+
+/// Parses a mnemonic in any supported language.
+///
+/// Some wordlists share words (notably the two Chinese lists), so when the
+/// words alone are ambiguous the first candidate whose checksum verifies wins.
+/// Input is lowercased first, since mobile keyboards like to capitalise.
+pub fn parse_mnemonic(mnemonic: &str) -> Result<Mnemonic, Error> {
+    let mnemonic = Zeroizing::new(mnemonic.to_lowercase());
+    match Mnemonic::parse(mnemonic.as_str()) {
+        Err(bip39::Error::AmbiguousLanguages(candidates)) => candidates
+            .iter()
+            .find_map(|language| Mnemonic::parse_in(language, mnemonic.as_str()).ok())
+            .ok_or(Error::InvalidChecksum),
+        result => Ok(result?),
+    }
+}
+
+pub fn detect_language(mnemonic: &str) -> Result<Language, Error> {
+    Ok(parse_mnemonic(mnemonic)?.language())
 }
 
 pub fn validate_mnemonic(mnemonic: &str) -> bool {
-    Mnemonic::from_phrase(mnemonic, Language::English).is_ok()
+    parse_mnemonic(mnemonic).is_ok()
+}
+
+/// Derives the BIP39 seed, optionally protected by a passphrase (the "25th word").
+/// `None` and `Some("")` yield the same seed.
+pub fn mnemonic_to_seed(mnemonic: &str, passphrase: Option<&str>) -> Result<Seed, Error> {
+    let mnemonic = parse_mnemonic(mnemonic)?;
+    Ok(Seed::new(mnemonic.to_seed(passphrase.unwrap_or(""))))
 }
 
-pub fn mnemonic_to_seed(mnemonic: &str) -> Result<[u8; 64], Box<dyn std::error::Error>> {
-    let mnemonic = Mnemonic::from_phrase(mnemonic, Language::English)?;
-    let seed = mnemonic.to_seed("");
-    Ok(seed)
-} 
\ No newline at end of file
+/// SeedXOR (as on Coldcard): splits a mnemonic into `parts` mnemonics of the
+/// same length and language whose entropies XOR to the original's. Every part
+/// has a valid checksum and works as an ordinary decoy wallet; all of them are
+/// needed to recover. `parts - 1` of them are random.
+pub fn seed_xor_split(mnemonic: &str, parts: usize) -> Result<Vec<SecretPhrase>, Error> {
+    if parts < 2 {
+        return Err(Error::invalid_argument("parts", "SeedXOR needs at least 2 parts"));
+    }
+    let mnemonic = parse_mnemonic(mnemonic)?;
+    let (entropy, length) = mnemonic.to_entropy_array();
+    let mut last = Zeroizing::new(entropy);
+
+    let mut phrases = Vec::with_capacity(parts);
+    for _ in 1..parts {
+        let mut part = Zeroizing::new([0u8; 33]);
+        rand::thread_rng().fill_bytes(&mut part[..length]);
+        for (byte, mask) in last.iter_mut().zip(part.iter()) {
+            *byte ^= mask;
+        }
+        phrases.push(format_phrase(&Mnemonic::from_entropy_in(mnemonic.language(), &part[..length])?));
+    }
+    phrases.push(format_phrase(&Mnemonic::from_entropy_in(mnemonic.language(), &last[..length])?));
+    Ok(phrases)
+}
+
+/// Recombines SeedXOR parts, in any order, into the original mnemonic. The
+/// result uses the wordlist of the first part.
+pub fn seed_xor_combine<S: AsRef<str>>(parts: &[S]) -> Result<SecretPhrase, Error> {
+    if parts.len() < 2 {
+        return Err(Error::invalid_argument("parts", "SeedXOR needs at least 2 parts"));
+    }
+    let first = parse_mnemonic(parts[0].as_ref())?;
+    let (entropy, length) = first.to_entropy_array();
+    let mut combined = Zeroizing::new(entropy);
+
+    for part in &parts[1..] {
+        let (entropy, part_length) = parse_mnemonic(part.as_ref())?.to_entropy_array();
+        let entropy = Zeroizing::new(entropy);
+        if part_length != length {
+            return Err(Error::invalid_argument("parts", "all parts must have the same number of words"));
+        }
+        for (byte, other) in combined.iter_mut().zip(entropy.iter()) {
+            *byte ^= other;
+        }
+    }
+    Ok(format_phrase(&Mnemonic::from_entropy_in(first.language(), &combined[..length])?))
+}
+
+/// Suggestions further than this many edits away are not worth showing.
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+const MAX_SUGGESTIONS: usize = 5;
+/// BIP39 wordlists guarantee that the first four letters identify a word.
+const PREFIX_LENGTH: usize = 4;
+
+/// A word that is not in the detected wordlist.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UnknownWord {
+    /// Zero-based position in the phrase.
+    pub position: usize,
+    pub word: String,
+    /// Closest wordlist words, nearest first.
+    pub suggestions: Vec<String>,
+    pub edit_distance: Option<usize>,
+    /// The single word starting with the first four letters, if they identify one.
+    pub prefix_match: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MnemonicValidation {
+    pub valid: bool,
+    pub language: String,
+    pub word_count: usize,
+    pub word_count_valid: bool,
+    pub unknown_words: Vec<UnknownWord>,
+    /// `None` when the checksum could not be checked because of a bad word
+    /// count or unknown words.
+    pub checksum_valid: Option<bool>,
+}
+
+/// Optimal string alignment distance: Levenshtein plus adjacent
+/// transpositions, so "tset" is one edit from "test".
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut before: Vec<usize> = Vec::new();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+            if i > 0 && j > 0 && *ca == b[j - 1] && a[i - 1] == *cb {
+                current[j + 1] = current[j + 1].min(before[j - 1] + 1);
+            }
+        }
+        before = std::mem::replace(&mut previous, current);
+    }
+    previous[b.len()]
+}
+
+fn diagnose_word(language: Language, position: usize, word: &str) -> UnknownWord {
+    let mut ranked: Vec<(usize, &str)> = language
+        .word_list()
+        .iter()
+        .map(|candidate| (edit_distance(word, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= MAX_SUGGESTION_DISTANCE)
+        .collect();
+    ranked.sort_by_key(|(distance, _)| *distance);
+    ranked.truncate(MAX_SUGGESTIONS);
+
+    let prefix: String = word.chars().take(PREFIX_LENGTH).collect();
+    let mut prefixed = language.word_list().iter().filter(|candidate| candidate.starts_with(&prefix));
+    let prefix_match = match (prefixed.next(), prefixed.next()) {
+        (Some(only), None) if prefix.chars().count() == PREFIX_LENGTH => Some(only.to_string()),
+        _ => None,
+    };
+
+    UnknownWord {
+        position,
+        word: word.to_string(),
+        edit_distance: ranked.first().map(|(distance, _)| *distance),
+        suggestions: ranked.into_iter().map(|(_, w)| w.to_string()).collect(),
+        prefix_match,
+    }
+}
+
+/// NFKD-normalizes and lowercases a phrase typed by a user.
+pub fn normalize_phrase(mnemonic: &str) -> Zeroizing<String> {
+    let mut normalized = std::borrow::Cow::Borrowed(mnemonic);
+    Mnemonic::normalize_utf8_cow(&mut normalized);
+    let lowercase = Zeroizing::new(normalized.to_lowercase());
+    if let std::borrow::Cow::Owned(mut owned) = normalized {
+        owned.zeroize();
+    }
+    lowercase
+}
+
+/// The wordlist that recognises the most of `words`, English first on ties.
+/// Unlike [`detect_language`] this tolerates unknown words.
+pub fn best_matching_language(words: &[&str]) -> Language {
+    LANGUAGE_CODES
+        .iter()
+        .enumerate()
+        .max_by_key(|(i, (_, language))| {
+            let known = words.iter().filter(|w| language.find_word(w).is_some()).count();
+            (known, std::cmp::Reverse(*i))
+        })
+        .map(|(_, (_, language))| *language)
+        .unwrap_or(Language::English)
+}
+
+/// Checks a mnemonic and explains what is wrong with it.
+///
+/// The language is the wordlist that recognises the most words, so a single
+/// typo does not prevent detection. Unknown words and checksum failures are
+/// reported separately; the checksum is only checked once every word is known.
+pub fn validate_mnemonic_detailed(mnemonic: &str) -> MnemonicValidation {
+    let normalized = normalize_phrase(mnemonic);
+    let words: Vec<&str> = normalized.split_whitespace().collect();
+    let language = best_matching_language(&words);
+
+    let unknown_words: Vec<UnknownWord> = words
+        .iter()
+        .enumerate()
+        .filter(|(_, word)| language.find_word(word).is_none())
+        .map(|(position, word)| diagnose_word(language, position, word))
+        .collect();
+
+    let word_count_valid = SUPPORTED_WORD_COUNTS.contains(&words.len());
+    let checksum_valid = if word_count_valid && unknown_words.is_empty() {
+        Some(Mnemonic::parse_in_normalized(language, &Zeroizing::new(words.join(" "))).is_ok())
+    } else {
+        None
+    };
+
+    MnemonicValidation {
+        valid: checksum_valid == Some(true),
+        language: language_code(language).to_string(),
+        word_count: words.len(),
+        word_count_valid,
+        unknown_words,
+        checksum_valid,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HARDHAT: &str = "test test test test test test test test test test test junk";
+
+    #[test]
+    fn edit_distance_counts_transpositions_once() {
+        assert_eq!(edit_distance("tset", "test"), 1);
+        assert_eq!(edit_distance("test", "test"), 0);
+        assert_eq!(edit_distance("tes", "test"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("ca", "abc"), 3);
+    }
+
+    #[test]
+    fn valid_mnemonic() {
+        let validation = validate_mnemonic_detailed(HARDHAT);
+        assert!(validation.valid);
+        assert_eq!(validation.language, "en");
+        assert_eq!(validation.word_count, 12);
+        assert!(validation.unknown_words.is_empty());
+        assert_eq!(validation.checksum_valid, Some(true));
+    }
+
+    #[test]
+    fn unknown_word_is_diagnosed() {
+        let validation = validate_mnemonic_detailed("test test test tset test test test test test test test junk");
+        assert!(!validation.valid);
+        assert!(validation.word_count_valid);
+        assert_eq!(validation.checksum_valid, None);
+        assert_eq!(validation.unknown_words.len(), 1);
+        let unknown = &validation.unknown_words[0];
+        assert_eq!(unknown.position, 3);
+        assert_eq!(unknown.word, "tset");
+        assert_eq!(unknown.edit_distance, Some(1));
+        assert_eq!(unknown.suggestions[0], "test");
+    }
+
+    #[test]
+    fn unique_prefix_is_matched() {
+        let validation = validate_mnemonic_detailed("test test test test test test test test test test test junkyard");
+        assert_eq!(validation.unknown_words[0].prefix_match.as_deref(), Some("junk"));
+    }
+
+    #[test]
+    fn bad_checksum() {
+        let validation = validate_mnemonic_detailed("test test test test test test test test test test test test");
+        assert!(!validation.valid);
+        assert!(validation.word_count_valid);
+        assert!(validation.unknown_words.is_empty());
+        assert_eq!(validation.checksum_valid, Some(false));
+    }
+
+    #[test]
+    fn bad_word_count() {
+        let validation = validate_mnemonic_detailed("test test test test test test test test test test junk");
+        assert!(!validation.valid);
+        assert_eq!(validation.word_count, 11);
+        assert!(!validation.word_count_valid);
+        assert_eq!(validation.checksum_valid, None);
+    }
+
+    const ABANDON: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    #[test]
+    fn passphrase_matches_the_trezor_vector() {
+        let seed = mnemonic_to_seed(ABANDON, Some("TREZOR")).unwrap();
+        assert_eq!(
+            hex::encode(seed.as_bytes()),
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+        );
+        let without = mnemonic_to_seed(ABANDON, None).unwrap();
+        assert_eq!(without.as_bytes(), mnemonic_to_seed(ABANDON, Some("")).unwrap().as_bytes());
+        assert_ne!(without.as_bytes(), seed.as_bytes());
+    }
+
+    // The first Japanese vector from the BIP39 reference implementation.
+    const JAPANESE: &str = "あいこくしん\u{3000}あいこくしん\u{3000}あいこくしん\u{3000}あいこくしん\u{3000}あいこくしん\u{3000}あいこくしん\u{3000}あいこくしん\u{3000}あいこくしん\u{3000}あいこくしん\u{3000}あいこくしん\u{3000}あいこくしん\u{3000}あおぞら";
+
+    #[test]
+    fn japanese_vector() {
+        let mnemonic = parse_mnemonic(JAPANESE).unwrap();
+        assert_eq!(mnemonic.language(), Language::Japanese);
+        assert_eq!(mnemonic.to_entropy(), vec![0u8; 16]);
+        // The wordlist is stored NFKD-decomposed, so compare normalized forms.
+        let formatted = format_phrase(&mnemonic);
+        assert_eq!(formatted.expose().matches('\u{3000}').count(), 11);
+        assert_eq!(*normalize_phrase(formatted.expose()), *normalize_phrase(JAPANESE));
+        let seed = mnemonic_to_seed(JAPANESE, Some("㍍ガバヴァぱばぐゞちぢ十人十色")).unwrap();
+        assert_eq!(
+            hex::encode(seed.as_bytes()),
+            "a262d6fb6122ecf45be09c50492b31f92e9beb7d9a845987a02cefda57a15f9c467a17872029a9e92299b5cbdf306e3a0ee620245cbd508959b6cb7ca637bd55"
+        );
+    }
+
+    #[test]
+    fn ambiguous_words_resolve_by_checksum() {
+        // Every word here is in both the English and French lists, at
+        // different indexes, so only the checksum tells them apart.
+        let phrase = |last: &str| format!("{}{}", "animal ".repeat(11), last);
+        assert!(matches!(Mnemonic::parse(phrase("abandon")), Err(bip39::Error::AmbiguousLanguages(_))));
+        assert_eq!(detect_language(&phrase("abandon")).unwrap(), Language::French);
+        assert_eq!(detect_language(&phrase("cycle")).unwrap(), Language::English);
+        // Valid in both: the first candidate, English, wins.
+        assert_eq!(detect_language(&phrase("fortune")).unwrap(), Language::English);
+        assert!(matches!(detect_language(&phrase("animal")), Err(Error::InvalidChecksum)));
+    }
+
+    #[test]
+    fn generates_every_language_and_word_count() {
+        for (code, language) in LANGUAGE_CODES {
+            for word_count in SUPPORTED_WORD_COUNTS {
+                let phrase = generate_mnemonic(language, word_count).unwrap();
+                let parsed = parse_mnemonic(phrase.expose()).unwrap();
+                assert_eq!(parsed.word_count(), word_count);
+                // The two Chinese lists share 1275 words at the same indexes.
+                if language != Language::TraditionalChinese {
+                    assert_eq!(language_code(parsed.language()), code);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_unsupported_word_counts() {
+        for word_count in [0, 11, 13, 16, 25] {
+            assert!(matches!(
+                generate_mnemonic(Language::English, word_count),
+                Err(Error::InvalidWordCount { word_count: n }) if n == word_count
+            ));
+        }
+        assert!(parse_mnemonic("test test test test test test test test test junk").is_err());
+    }
+
+    // The example from Coldcard's SeedXOR documentation.
+    const SEED_XOR_PARTS: [&str; 3] = [
+        "romance wink lottery autumn shop bring dawn tongue range crater truth ability miss spice fitness easy legal release recall obey exchange recycle dragon room",
+        "lion misery divide hurry latin fluid camp advance illegal lab pyramid unaware eager fringe sick camera series noodle toy crowd jeans select depth lounge",
+        "vault nominee cradle silk own frown throw leg cactus recall talent worry gadget surface shy planet purpose coffee drip few seven term squeeze educate",
+    ];
+    const SEED_XOR_RESULT: &str =
+        "silent toe meat possible chair blossom wait occur this worth option bag nurse find fish scene bench asthma bike wage world quit primary indoor";
+
+    #[test]
+    fn seed_xor_matches_coldcard() {
+        assert_eq!(seed_xor_combine(&SEED_XOR_PARTS).unwrap().expose(), SEED_XOR_RESULT);
+        let reordered = [SEED_XOR_PARTS[2], SEED_XOR_PARTS[0], SEED_XOR_PARTS[1]];
+        assert_eq!(seed_xor_combine(&reordered).unwrap().expose(), SEED_XOR_RESULT);
+    }
+
+    #[test]
+    fn seed_xor_round_trips() {
+        for (mnemonic, parts) in [(HARDHAT, 2), (SEED_XOR_RESULT, 4)] {
+            let split = seed_xor_split(mnemonic, parts).unwrap();
+            assert_eq!(split.len(), parts);
+            for part in &split {
+                assert!(validate_mnemonic(part.expose()));
+                assert_eq!(part.expose().split(' ').count(), mnemonic.split(' ').count());
+            }
+            let split: Vec<&str> = split.iter().map(SecretPhrase::expose).collect();
+            assert_eq!(seed_xor_combine(&split).unwrap().expose(), mnemonic);
+        }
+    }
+
+    #[test]
+    fn seed_xor_rejects_bad_parts() {
+        assert!(seed_xor_split(HARDHAT, 1).is_err());
+        assert!(seed_xor_combine(&[HARDHAT][..]).is_err());
+        assert!(matches!(
+            seed_xor_combine(&[HARDHAT, SEED_XOR_RESULT]),
+            Err(Error::InvalidArgument { ref argument, .. }) if argument == "parts"
+        ));
+        let invalid = "test test test test test test test test test test test test";
+        assert!(seed_xor_combine(&[HARDHAT, invalid]).is_err());
+    }
+}
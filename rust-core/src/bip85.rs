@@ -0,0 +1,241 @@
+//! BIP-85: deterministic entropy for independent child wallets.
+//!
+//! Each application derives a hardened key under `m/83696968'`, hashes it
+//! with HMAC-SHA512 and uses the result as entropy for a mnemonic, raw hex,
+//! a WIF key or a password. Children look unrelated to each other and to the
+//! parent, yet all of them come back from the parent seed.
+
+use crate::bip39;
+use crate::derivation_path::DerivationPath;
+use crate::error::Error;
+use crate::extended_key::ExtendedKey;
+use crate::hd_wallet;
+use crate::secret::{SecretExtendedKey, SecretPhrase};
+use ::bip39::{Language, Mnemonic};
+use base64::Engine;
+use hdwallet::ExtendedPrivKey;
+use hmac::{Hmac, Mac};
+use sha2::Sha512;
+use zeroize::Zeroizing;
+
+const PURPOSE: u32 = 83696968;
+const HMAC_KEY: &[u8] = b"bip-entropy-from-k";
+
+const APP_BIP39: u32 = 39;
+const APP_HD_SEED_WIF: u32 = 2;
+const APP_HEX: u32 = 128169;
+const APP_PASSWORD_BASE64: u32 = 707764;
+const APP_PASSWORD_BASE85: u32 = 707785;
+
+/// Python's `base64.b85encode` alphabet, which BIP-85 follows.
+const BASE85_ALPHABET: &[u8; 85] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz!#$%&()*+-;<=>?@^_`{|}~";
+
+/// BIP-85's language numbers, which differ from our wordlist codes.
+fn language_number(language: Language) -> u32 {
+    match language {
+        Language::English => 0,
+        Language::Japanese => 1,
+        Language::Korean => 2,
+        Language::Spanish => 3,
+        Language::SimplifiedChinese => 4,
+        Language::TraditionalChinese => 5,
+        Language::French => 6,
+        Language::Italian => 7,
+        Language::Czech => 8,
+        Language::Portuguese => 9,
+    }
+}
+
+/// Derives BIP-85 children from a master (depth 0) private key.
+pub struct Bip85 {
+    root: SecretExtendedKey,
+}
+
+impl Bip85 {
+    pub(crate) fn new(root: SecretExtendedKey) -> Self {
+        Bip85 { root }
+    }
+
+    pub fn from_seed(seed: &[u8]) -> Result<Self, Error> {
+        let root = ExtendedPrivKey::with_seed(seed).map_err(|e| Error::derivation_failed("m", e))?;
+        Ok(Self::new(root.into()))
+    }
+
+    pub fn from_mnemonic(mnemonic: &str, passphrase: Option<&str>) -> Result<Self, Error> {
+        Self::from_seed(bip39::mnemonic_to_seed(mnemonic, passphrase)?.as_bytes())
+    }
+
+    /// Accepts a master `xprv`; BIP-85 is defined from the root only.
+    pub fn from_extended_key(key: &ExtendedKey) -> Result<Self, Error> {
+        if key.depth != 0 {
+            return Err(Error::invalid_argument("xprv", format!("BIP-85 needs the master key, got depth {}", key.depth)));
+        }
+        let root = key.to_private().ok_or_else(|| Error::PrivateKeyUnavailable {
+            reason: "BIP-85 needs a private master key".to_string(),
+        })?;
+        Ok(Self::new(root))
+    }
+
+    /// The 64 bytes of entropy at `path`, every level of which must be hardened.
+    pub fn entropy(&self, path: &DerivationPath) -> Result<Zeroizing<[u8; 64]>, Error> {
+        if path.children().iter().any(|child| !child.hardened) {
+            return Err(Error::invalid_path(path, "BIP-85 paths are fully hardened"));
+        }
+        let key = hd_wallet::derive_key(&self.root, path)?;
+
+        let mut mac = Hmac::<Sha512>::new_from_slice(HMAC_KEY).expect("HMAC takes any key length");
+        mac.update(&key.private_key[..]);
+        let mut entropy = Zeroizing::new([0u8; 64]);
+        entropy.copy_from_slice(&mac.finalize().into_bytes());
+        Ok(entropy)
+    }
+
+    fn app_entropy(&self, app: u32, levels: &[u32]) -> Result<Zeroizing<[u8; 64]>, Error> {
+        let mut path = format!("m/{}'/{}'", PURPOSE, app);
+        for level in levels {
+            path.push_str(&format!("/{}'", level));
+        }
+        self.entropy(&path.parse()?)
+    }
+
+    /// A child BIP39 mnemonic of 12, 18 or 24 words.
+    pub fn mnemonic(&self, language: Language, word_count: usize, index: u32) -> Result<SecretPhrase, Error> {
+        let length = match word_count {
+            12 => 16,
+            18 => 24,
+            24 => 32,
+            _ => return Err(Error::InvalidWordCount { word_count }),
+        };
+        let entropy = self.app_entropy(APP_BIP39, &[language_number(language), word_count as u32, index])?;
+        let mnemonic = Mnemonic::from_entropy_in(language, &entropy[..length])?;
+        Ok(bip39::format_phrase(&mnemonic))
+    }
+
+    /// `length` bytes (16 to 64) of entropy as lowercase hex.
+    pub fn hex(&self, length: usize, index: u32) -> Result<Zeroizing<String>, Error> {
+        if !(16..=64).contains(&length) {
+            return Err(Error::invalid_argument("length", "must be between 16 and 64 bytes"));
+        }
+        let entropy = self.app_entropy(APP_HEX, &[length as u32, index])?;
+        Ok(Zeroizing::new(hex::encode(&entropy[..length])))
+    }
+
+    /// A compressed mainnet WIF key, for wallets that import a single key.
+    pub fn wif(&self, index: u32) -> Result<Zeroizing<String>, Error> {
+        let entropy = self.app_entropy(APP_HD_SEED_WIF, &[index])?;
+        let mut payload = Zeroizing::new(Vec::with_capacity(34));
+        payload.push(0x80);
+        payload.extend_from_slice(&entropy[..32]);
+        payload.push(0x01);
+        Ok(Zeroizing::new(bs58::encode(&payload[..]).with_check().into_string()))
+    }
+
+    /// A base64 password of 20 to 86 characters.
+    pub fn password_base64(&self, length: usize, index: u32) -> Result<Zeroizing<String>, Error> {
+        if !(20..=86).contains(&length) {
+            return Err(Error::invalid_argument("length", "must be between 20 and 86 characters"));
+        }
+        let entropy = self.app_entropy(APP_PASSWORD_BASE64, &[length as u32, index])?;
+        let mut encoded = Zeroizing::new(base64::engine::general_purpose::STANDARD.encode(&entropy[..]));
+        encoded.truncate(length);
+        Ok(encoded)
+    }
+
+    /// A base85 password of 10 to 80 characters.
+    pub fn password_base85(&self, length: usize, index: u32) -> Result<Zeroizing<String>, Error> {
+        if !(10..=80).contains(&length) {
+            return Err(Error::invalid_argument("length", "must be between 10 and 80 characters"));
+        }
+        let entropy = self.app_entropy(APP_PASSWORD_BASE85, &[length as u32, index])?;
+        let mut encoded = Zeroizing::new(String::with_capacity(80));
+        for chunk in entropy.chunks(4) {
+            let mut value = u32::from_be_bytes(chunk.try_into().expect("64 is a multiple of 4"));
+            let mut digits = [0u8; 5];
+            for digit in digits.iter_mut().rev() {
+                *digit = BASE85_ALPHABET[(value % 85) as usize];
+                value /= 85;
+            }
+            encoded.extend(digits.iter().map(|&d| d as char));
+        }
+        encoded.truncate(length);
+        Ok(encoded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The test vectors from BIP-85.
+    const MASTER: &str =
+        "xprv9s21ZrQH143K2LBWUUQRFXhucrQqBpKdRRxNVq2zBqsx8HVqFk2uYo8kmbaLLHRdqtQpUm98uKfu3vca1LqdGhUtyoFnCNkfmXRyPXLjbKb";
+
+    fn bip85() -> Bip85 {
+        Bip85::from_extended_key(&MASTER.parse().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn entropy_vectors() {
+        let bip85 = bip85();
+        assert_eq!(
+            hex::encode(&bip85.entropy(&"m/83696968'/0'/0'".parse().unwrap()).unwrap()[..]),
+            "efecfbccffea313214232d29e71563d941229afb4338c21f9517c41aaa0d16f00b83d2a09ef747e7a64e8e2bd5a14869e693da66ce94ac2da570ab7ee48618f7"
+        );
+        assert_eq!(
+            hex::encode(&bip85.entropy(&"m/83696968'/0'/1'".parse().unwrap()).unwrap()[..]),
+            "70c6e3e8ebee8dc4c0dbba66076819bb8c09672527c4277ca8729532ad711872218f826919f6b67218adde99018a6df9095ab2b58d803b5b93ec9802085a690e"
+        );
+    }
+
+    #[test]
+    fn bip39_vectors() {
+        let bip85 = bip85();
+        assert_eq!(
+            bip85.mnemonic(Language::English, 12, 0).unwrap().expose(),
+            "girl mad pet galaxy egg matter matrix prison refuse sense ordinary nose"
+        );
+        assert_eq!(
+            bip85.mnemonic(Language::English, 18, 0).unwrap().expose(),
+            "near account window bike charge season chef number sketch tomorrow excuse sniff circle vital hockey outdoor supply token"
+        );
+        assert_eq!(
+            bip85.mnemonic(Language::English, 24, 0).unwrap().expose(),
+            "puppy ocean match cereal symbol another shed magic wrap hammer bulb intact gadget divorce twin tonight reason outdoor destroy simple truth cigar social volcano"
+        );
+    }
+
+    #[test]
+    fn wif_vector() {
+        assert_eq!(bip85().wif(0).unwrap().as_str(), "Kzyv4uF39d4Jrw2W7UryTHwZr1zQVNk4dAFyqE6BuMrMh1Za7uhp");
+    }
+
+    #[test]
+    fn hex_vector() {
+        assert_eq!(
+            bip85().hex(64, 0).unwrap().as_str(),
+            "492db4698cf3b73a5a24998aa3e9d7fa96275d85724a91e71aa2d645442f878555d078fd1f1f67e368976f04137b1f7a0d19232136ca50c44614af72b5582a5c"
+        );
+    }
+
+    #[test]
+    fn password_vectors() {
+        assert_eq!(bip85().password_base64(21, 0).unwrap().as_str(), "dKLoepugzdVJvdL56ogNV");
+        assert_eq!(bip85().password_base85(12, 0).unwrap().as_str(), "_s`{TW89)i4`");
+    }
+
+    #[test]
+    fn rejects_bad_inputs() {
+        let bip85 = bip85();
+        assert!(bip85.entropy(&"m/83696968'/0/0'".parse().unwrap()).is_err());
+        assert!(matches!(bip85.mnemonic(Language::English, 15, 0), Err(Error::InvalidWordCount { word_count: 15 })));
+        assert!(bip85.hex(15, 0).is_err());
+        assert!(bip85.hex(65, 0).is_err());
+        assert!(bip85.password_base64(19, 0).is_err());
+        assert!(bip85.password_base85(81, 0).is_err());
+
+        let account: ExtendedKey = "xprv9zFnWC6h2cLgpmSA46vutJzBcfJ8yaJGg8cX1e5StJh45BBciYTRXSd25UEPVuesF9yog62tGAQtHjXajPPdbRCHuWS6T8XA2ECKADdw4Ef"
+            .parse()
+            .unwrap();
+        assert!(Bip85::from_extended_key(&account).is_err());
+    }
+}
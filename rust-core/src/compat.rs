@@ -0,0 +1,174 @@
+//! Finding children derived with other wallets' layouts.
+//!
+//! Funds split with the mobile client's TS fallback sit under
+//! [`ETHERS_LEGACY_TEMPLATE`], not the default layout. These helpers scan the
+//! named presets so such children can be found and swept.
+//!
+//! Without an explicit start, each layout is scanned from its own first
+//! child: index 1 where index 0 resolves to the parent (the default and
+//! Ledger Live layouts), index 0 otherwise. The TS fallback funded ethers'
+//! `deriveChild(0..count)`, and there child 0 is not the parent.
+
+use crate::address;
+use crate::derivation_path::{self, DerivationPath, PathTemplate, PathValues, ETHERS_LEGACY_TEMPLATE, DEFAULT_CHILD_TEMPLATE, DEFAULT_PARENT_PATH};
+use crate::error::Error;
+use crate::hd_wallet::HdWallet;
+use crate::FIRST_SPLIT_CHILD;
+use serde::{Serialize, Deserialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ChildAddress {
+    pub index: u32,
+    pub path: String,
+    pub address: String,
+}
+
+/// The children of one named layout.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SchemeAddresses {
+    pub scheme: String,
+    pub template: String,
+    /// Index of `children[0]`.
+    pub child_start_index: u32,
+    pub children: Vec<ChildAddress>,
+}
+
+/// Where an address was found.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SchemeMatch {
+    pub scheme: String,
+    pub template: String,
+    pub index: u32,
+    pub path: String,
+}
+
+/// `count` child addresses under both the default and the ethers-legacy
+/// layout, for checking balances on each before sweeping.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RecoveryReport {
+    pub parent_address: String,
+    pub schemes: Vec<SchemeAddresses>,
+}
+
+/// [`FIRST_SPLIT_CHILD`] when index 0 of `template` is the parent path, else 0.
+fn first_child(template: &PathTemplate) -> Result<u32, Error> {
+    let parent: DerivationPath = DEFAULT_PARENT_PATH.parse()?;
+    Ok(if template.resolve(PathValues::default())? == parent { FIRST_SPLIT_CHILD } else { 0 })
+}
+
+fn scheme_addresses(wallet: &HdWallet, scheme: &str, template: &str, start: Option<u32>, count: u32) -> Result<SchemeAddresses, Error> {
+    let parsed: PathTemplate = template.parse()?;
+    let start = match start {
+        Some(start) => start,
+        None => first_child(&parsed)?,
+    };
+    let wallets = wallet.children_with_template(&parsed, PathValues::default(), start, count)?;
+    let children = (start..)
+        .zip(wallets)
+        .map(|(index, child)| {
+            let path = parsed.resolve(PathValues { index, ..PathValues::default() })?;
+            Ok(ChildAddress { index, path: path.to_string(), address: child.address })
+        })
+        .collect::<Result<_, Error>>()?;
+
+    Ok(SchemeAddresses { scheme: scheme.to_string(), template: template.to_string(), child_start_index: start, children })
+}
+
+/// `start` of `None` scans each layout from its own first child.
+pub fn recovery_report(wallet: &HdWallet, start: Option<u32>, count: u32) -> Result<RecoveryReport, Error> {
+    Ok(RecoveryReport {
+        parent_address: wallet.parent()?.address,
+        schemes: vec![
+            scheme_addresses(wallet, "default", DEFAULT_CHILD_TEMPLATE, start, count)?,
+            scheme_addresses(wallet, "ethers-legacy", ETHERS_LEGACY_TEMPLATE, start, count)?,
+        ],
+    })
+}
+
+/// Searches `count` indexes of every named preset for `target`, from `start`
+/// or each preset's own first child. Returns `None` if no preset derives it
+/// in that range.
+pub fn detect_child_scheme(wallet: &HdWallet, target: &str, start: Option<u32>, count: u32) -> Result<Option<SchemeMatch>, Error> {
+    let target = address::parse_address(target)?;
+    for (scheme, template) in derivation_path::TEMPLATE_PRESETS {
+        let found = scheme_addresses(wallet, scheme, template, start, count)?
+            .children
+            .into_iter()
+            .find(|child| address::parse_address(&child.address) == Ok(target));
+        if let Some(child) = found {
+            return Ok(Some(SchemeMatch {
+                scheme: scheme.to_string(),
+                template: template.to_string(),
+                index: child.index,
+                path: child.path,
+            }));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HARDHAT: &str = "test test test test test test test test test test test junk";
+    // ethers v6: `Wallet.fromPhrase(HARDHAT).deriveChild(5).address`.
+    const ETHERS_CHILD_5: &str = "0x4aC9B5dd402C58887cb10a65313a9Edb08646D68";
+
+    fn hardhat() -> HdWallet {
+        HdWallet::from_mnemonic(HARDHAT, None).unwrap()
+    }
+
+    #[test]
+    fn report_lists_both_layouts() {
+        let report = recovery_report(&hardhat(), Some(0), 6).unwrap();
+        assert_eq!(report.parent_address, "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266");
+        let (default, ethers) = (&report.schemes[0], &report.schemes[1]);
+        assert_eq!(default.scheme, "default");
+        assert_eq!(default.children[1].address, "0x70997970C51812dc3A010C7d01b50e0d17dc79C8");
+        assert_eq!(ethers.scheme, "ethers-legacy");
+        assert_eq!(ethers.children[0].address, "0xD51d4b680Cd89E834413c48fa6EE2c59863B738d");
+        assert_eq!(ethers.children[5].path, "m/44'/60'/0'/0/0/5");
+        assert_eq!(ethers.children[5].address, ETHERS_CHILD_5);
+    }
+
+    #[test]
+    fn each_layout_starts_at_its_first_child_by_default() {
+        let report = recovery_report(&hardhat(), None, 100).unwrap();
+        let (default, ethers) = (&report.schemes[0], &report.schemes[1]);
+        // Child 0 of the default layout is the parent; the sweep covers 1..=100.
+        assert_eq!(default.child_start_index, 1);
+        assert_eq!(default.children[0].address, "0x70997970C51812dc3A010C7d01b50e0d17dc79C8");
+        assert_eq!(default.children.last().unwrap().index, 100);
+        assert!(default.children.iter().all(|child| child.address != report.parent_address));
+        // ethers' `deriveChild(0)` is a funded child.
+        assert_eq!(ethers.child_start_index, 0);
+        assert_eq!(ethers.children[0].address, "0xD51d4b680Cd89E834413c48fa6EE2c59863B738d");
+
+        let wallet = hardhat();
+        let found = detect_child_scheme(&wallet, "0xD51d4b680Cd89E834413c48fa6EE2c59863B738d", None, 1).unwrap().unwrap();
+        assert_eq!((found.scheme.as_str(), found.index), ("ethers-legacy", 0));
+        assert!(detect_child_scheme(&wallet, &report.parent_address, None, 100).unwrap().is_none());
+    }
+
+    #[test]
+    fn detects_the_ethers_legacy_layout() {
+        let wallet = hardhat();
+        let found = detect_child_scheme(&wallet, &ETHERS_CHILD_5.to_lowercase(), Some(0), 10).unwrap().unwrap();
+        assert_eq!(found.scheme, "ethers-legacy");
+        assert_eq!(found.index, 5);
+        assert_eq!(found.path, "m/44'/60'/0'/0/0/5");
+
+        let default = detect_child_scheme(&wallet, "0x70997970C51812dc3A010C7d01b50e0d17dc79C8", Some(0), 10).unwrap().unwrap();
+        assert_eq!((default.scheme.as_str(), default.index), ("default", 1));
+    }
+
+    #[test]
+    fn unrelated_and_malformed_targets() {
+        let wallet = hardhat();
+        assert!(detect_child_scheme(&wallet, "0x000000000000000000000000000000000000dEaD", Some(0), 10).unwrap().is_none());
+        // Outside the searched range.
+        assert!(detect_child_scheme(&wallet, ETHERS_CHILD_5, Some(0), 5).unwrap().is_none());
+        assert!(matches!(detect_child_scheme(&wallet, "0x1234", Some(0), 10), Err(Error::InvalidAddress { .. })));
+    }
+}
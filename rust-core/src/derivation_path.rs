@@ -0,0 +1,315 @@
+use crate::error::Error;
+use hdwallet::KeyIndex;
+use serde::{Serialize, Deserialize};
+use std::fmt;
+use std::str::FromStr;
+
+const HARDENED_OFFSET: u32 = 1 << 31;
+/// BIP32 serializes depth in one byte.
+const MAX_DEPTH: usize = 255;
+
+pub const DEFAULT_PARENT_PATH: &str = "m/44'/60'/0'/0/0";
+/// The external chain node the default parent and children hang off.
+pub const ACCOUNT_PATH: &str = "m/44'/60'/0'/0";
+pub const DEFAULT_CHILD_TEMPLATE: &str = "m/44'/60'/0'/0/{index}";
+/// Ledger Live puts each account on its own hardened branch.
+pub const LEDGER_LIVE_TEMPLATE: &str = "m/44'/60'/{index}'/0/0";
+/// MyEtherWallet and early Ledger firmware, without the change level.
+pub const LEGACY_MEW_TEMPLATE: &str = "m/44'/60'/0'/{index}";
+/// What the mobile client's TS fallback derived: ethers'
+/// `Wallet.fromPhrase(m).deriveChild(i)` hangs children below the parent
+/// wallet instead of beside it.
+pub const ETHERS_LEGACY_TEMPLATE: &str = "m/44'/60'/0'/0/0/{index}";
+
+/// Named child layouts offered in the UI, as `(name, template)`.
+pub const TEMPLATE_PRESETS: [(&str, &str); 4] = [
+    ("default", DEFAULT_CHILD_TEMPLATE),
+    ("ledger-live", LEDGER_LIVE_TEMPLATE),
+    ("legacy-mew", LEGACY_MEW_TEMPLATE),
+    ("ethers-legacy", ETHERS_LEGACY_TEMPLATE),
+];
+
+pub fn template_preset(name: &str) -> Option<&'static str> {
+    TEMPLATE_PRESETS
+        .iter()
+        .find(|(preset, _)| *preset == name)
+        .map(|(_, template)| *template)
+}
+
+/// One level of a BIP32 path.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChildNumber {
+    pub index: u32,
+    pub hardened: bool,
+}
+
+impl ChildNumber {
+    pub fn new(index: u32, hardened: bool) -> Result<Self, Error> {
+        if index >= HARDENED_OFFSET {
+            return Err(Error::invalid_argument("child index", format!("{} must be below 2^31", index)));
+        }
+        Ok(ChildNumber { index, hardened })
+    }
+
+    pub fn key_index(self) -> KeyIndex {
+        if self.hardened {
+            KeyIndex::Hardened(self.index + HARDENED_OFFSET)
+        } else {
+            KeyIndex::Normal(self.index)
+        }
+    }
+}
+
+impl fmt::Display for ChildNumber {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.index, if self.hardened { "'" } else { "" })
+    }
+}
+
+/// Splits the trailing hardened marker (`'`, `h` or `H`) off a path segment.
+fn split_hardened(segment: &str) -> (&str, bool) {
+    match segment.strip_suffix(['\'', 'h', 'H']) {
+        Some(rest) => (rest, true),
+        None => (segment, false),
+    }
+}
+
+/// Validates the `m/` prefix and returns the segments after it.
+fn path_segments(path: &str) -> Result<Vec<&str>, Error> {
+    let path = path.trim();
+    let mut segments = path.split('/');
+    if segments.next() != Some("m") {
+        return Err(Error::invalid_path(path, "must start with 'm'"));
+    }
+    let segments: Vec<&str> = segments.collect();
+    if segments.len() > MAX_DEPTH {
+        return Err(Error::invalid_path(path, format!("deeper than {} levels", MAX_DEPTH)));
+    }
+    if segments.iter().any(|s| s.is_empty()) {
+        return Err(Error::invalid_path(path, "empty segment"));
+    }
+    Ok(segments)
+}
+
+/// Parses a numeric segment such as `44` or `0'`. Only ASCII digits are
+/// accepted, so `+44'` is rejected even though `u32::from_str` takes it.
+fn child_number(path: &str, segment: &str) -> Result<ChildNumber, Error> {
+    let (digits, hardened) = split_hardened(segment);
+    let invalid = || Error::invalid_path(path, format!("invalid segment '{}'", segment));
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let index = digits.parse::<u32>().map_err(|_| invalid())?;
+    ChildNumber::new(index, hardened).map_err(|e| Error::invalid_path(path, e))
+}
+
+/// A parsed BIP32 path such as `m/44'/60'/0'/0/0`.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct DerivationPath(Vec<ChildNumber>);
+
+impl DerivationPath {
+    pub fn children(&self) -> &[ChildNumber] {
+        &self.0
+    }
+}
+
+impl FromStr for DerivationPath {
+    type Err = Error;
+
+    fn from_str(path: &str) -> Result<Self, Self::Err> {
+        let children = path_segments(path)?
+            .into_iter()
+            .map(|segment| child_number(path.trim(), segment))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(DerivationPath(children))
+    }
+}
+
+impl fmt::Display for DerivationPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "m")?;
+        for child in &self.0 {
+            write!(f, "/{}", child)?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for DerivationPath {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for DerivationPath {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let path = String::deserialize(deserializer)?;
+        path.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Values substituted into a [`PathTemplate`].
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PathValues {
+    pub account: u32,
+    pub change: u32,
+    pub index: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Placeholder {
+    Account,
+    Change,
+    Index,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TemplateSegment {
+    Fixed(ChildNumber),
+    Placeholder(Placeholder, bool),
+}
+
+/// A derivation path with `{account}`, `{change}` and `{index}` placeholders
+/// (`{i}` is accepted for `{index}`), e.g. `m/44'/60'/{account}'/0/{index}`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathTemplate {
+    source: String,
+    segments: Vec<TemplateSegment>,
+}
+
+impl PathTemplate {
+    pub fn resolve(&self, values: PathValues) -> Result<DerivationPath, Error> {
+        let children = self
+            .segments
+            .iter()
+            .map(|segment| match *segment {
+                TemplateSegment::Fixed(child) => Ok(child),
+                TemplateSegment::Placeholder(placeholder, hardened) => {
+                    let value = match placeholder {
+                        Placeholder::Account => values.account,
+                        Placeholder::Change => values.change,
+                        Placeholder::Index => values.index,
+                    };
+                    ChildNumber::new(value, hardened).map_err(|e| Error::invalid_path(self, e))
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(DerivationPath(children))
+    }
+
+    /// Leading segments without placeholders, shared by every resolved path.
+    pub fn fixed_prefix(&self) -> DerivationPath {
+        let children = self
+            .segments
+            .iter()
+            .map_while(|segment| match *segment {
+                TemplateSegment::Fixed(child) => Some(child),
+                TemplateSegment::Placeholder(..) => None,
+            })
+            .collect();
+        DerivationPath(children)
+    }
+
+    pub fn has_index(&self) -> bool {
+        self.segments
+            .iter()
+            .any(|s| matches!(s, TemplateSegment::Placeholder(Placeholder::Index, _)))
+    }
+}
+
+impl FromStr for PathTemplate {
+    type Err = Error;
+
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        let segments = path_segments(template)?
+            .into_iter()
+            .map(|segment| {
+                let (body, hardened) = split_hardened(segment);
+                if let Some(name) = body.strip_prefix('{').and_then(|b| b.strip_suffix('}')) {
+                    let placeholder = match name {
+                        "account" => Placeholder::Account,
+                        "change" => Placeholder::Change,
+                        "index" | "i" => Placeholder::Index,
+                        other => {
+                            return Err(Error::invalid_path(template.trim(), format!("unknown placeholder '{{{}}}'", other)));
+                        }
+                    };
+                    Ok(TemplateSegment::Placeholder(placeholder, hardened))
+                } else {
+                    Ok(TemplateSegment::Fixed(child_number(template.trim(), segment)?))
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(PathTemplate { source: template.trim().to_string(), segments })
+    }
+}
+
+impl fmt::Display for PathTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(path: &str) -> Result<DerivationPath, Error> {
+        path.parse()
+    }
+
+    #[test]
+    fn parses_hardened_markers() {
+        let parsed = path("m/44'/60h/0H/0/1").unwrap();
+        assert_eq!(parsed.to_string(), "m/44'/60'/0'/0/1");
+        assert_eq!(parsed.children()[1], ChildNumber { index: 60, hardened: true });
+        assert_eq!(parsed.children()[4], ChildNumber { index: 1, hardened: false });
+        assert_eq!(path(" m ").unwrap().children(), &[]);
+        assert_eq!(path("m/2147483647'").unwrap().children()[0].index, (1 << 31) - 1);
+    }
+
+    #[test]
+    fn rejects_bad_segments() {
+        for bad in [
+            "44'/60'", "M/44'", "m/", "m//0", "m/44''", "m/abc", "m/-1", "m/+44'/60'", "m/4 4", "m/0x10",
+            "m/１", "m/2147483648", "m/2147483648'", "m/4294967296",
+        ] {
+            assert!(matches!(path(bad), Err(Error::InvalidPath { .. })), "{}", bad);
+        }
+        assert!(path(&format!("m{}", "/0".repeat(256))).is_err());
+    }
+
+    #[test]
+    fn templates_resolve_placeholders() {
+        let template: PathTemplate = "m/44'/60'/{account}'/{change}/{i}".parse().unwrap();
+        let values = PathValues { account: 2, change: 1, index: 7 };
+        assert_eq!(template.resolve(values).unwrap().to_string(), "m/44'/60'/2'/1/7");
+        assert_eq!(template.fixed_prefix().to_string(), "m/44'/60'");
+        assert!(template.has_index());
+        assert!(!"m/44'/60'/{account}'".parse::<PathTemplate>().unwrap().has_index());
+
+        let too_big = PathValues { index: 1 << 31, ..PathValues::default() };
+        assert!(matches!(template.resolve(too_big), Err(Error::InvalidPath { .. })));
+    }
+
+    #[test]
+    fn templates_reject_unknown_placeholders() {
+        for bad in ["m/44'/{coin}'/0'", "m/44'/{index", "m/{}", "m/+{index}"] {
+            assert!(matches!(bad.parse::<PathTemplate>(), Err(Error::InvalidPath { .. })), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn presets() {
+        let resolve = |name: &str, index: u32| {
+            let template: PathTemplate = template_preset(name).unwrap().parse().unwrap();
+            template.resolve(PathValues { index, ..PathValues::default() }).unwrap().to_string()
+        };
+        assert_eq!(resolve("default", 3), "m/44'/60'/0'/0/3");
+        assert_eq!(resolve("ledger-live", 3), "m/44'/60'/3'/0/0");
+        assert_eq!(resolve("legacy-mew", 3), "m/44'/60'/0'/3");
+        assert_eq!(resolve("ethers-legacy", 3), "m/44'/60'/0'/0/0/3");
+        assert_eq!(resolve("default", 0), DEFAULT_PARENT_PATH);
+        assert!(template_preset("trezor").is_none());
+    }
+}
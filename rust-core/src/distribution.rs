@@ -0,0 +1,327 @@
+//! Pre-signed funding transactions for a split.
+//!
+//! Instead of one `splitEther` call on the WalletSplitter contract, the
+//! parent signs a plain transfer to each child with consecutive nonces. The
+//! batch can be prepared offline and broadcast later from any machine; the
+//! manifest lists what was signed so it can be checked before sending.
+
+use crate::error::Error;
+use crate::hd_wallet::HdWallet;
+use crate::secret::PrivateKey;
+use crate::split_plan::AmountPlan;
+use crate::transaction::{quantity, SignedTransaction, Transaction, TxType, TRANSFER_GAS_LIMIT};
+use crate::utils;
+use primitive_types::U256;
+use serde::{Serialize, Deserialize};
+
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// Gas pricing applied to every transaction of a batch.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum FeePolicy {
+    Legacy {
+        #[serde(with = "quantity")]
+        gas_price: U256,
+    },
+    Eip1559 {
+        #[serde(with = "quantity")]
+        max_fee_per_gas: U256,
+        #[serde(with = "quantity")]
+        max_priority_fee_per_gas: U256,
+    },
+}
+
+impl FeePolicy {
+    /// The most one transfer of `gas_limit` can cost.
+    pub fn max_fee(&self, gas_limit: u64) -> Result<U256, Error> {
+        let price = match self {
+            FeePolicy::Legacy { gas_price } => *gas_price,
+            FeePolicy::Eip1559 { max_fee_per_gas, .. } => *max_fee_per_gas,
+        };
+        price
+            .checked_mul(U256::from(gas_limit))
+            .ok_or_else(|| Error::invalid_argument("gas_limit", "fee overflows 256 bits"))
+    }
+
+    /// A plain ETH transfer priced by this policy.
+    pub fn transfer(&self, chain_id: u64, nonce: u64, to: &str, value: U256, gas_limit: u64) -> Transaction {
+        let (tx_type, gas_price, max_fee_per_gas, max_priority_fee_per_gas) = match *self {
+            FeePolicy::Legacy { gas_price } => (TxType::Legacy, Some(gas_price), None, None),
+            FeePolicy::Eip1559 { max_fee_per_gas, max_priority_fee_per_gas } => {
+                (TxType::Eip1559, None, Some(max_fee_per_gas), Some(max_priority_fee_per_gas))
+            }
+        };
+        Transaction {
+            tx_type,
+            chain_id,
+            nonce,
+            to: Some(to.to_string()),
+            value,
+            data: String::new(),
+            gas_limit,
+            gas_price,
+            max_fee_per_gas,
+            max_priority_fee_per_gas,
+            access_list: Vec::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DistributionParams {
+    pub chain_id: u64,
+    /// The parent's next nonce; transaction `i` uses `start_nonce + i`.
+    pub start_nonce: u64,
+    pub fees: FeePolicy,
+    /// Unless set, `amounts.gas_reserve` is the maximum fee of one transfer,
+    /// so a `total` covers both the amounts and the gas.
+    pub amounts: AmountPlan,
+    #[serde(default = "default_gas_limit")]
+    pub gas_limit: u64,
+}
+
+pub(crate) fn default_gas_limit() -> u64 {
+    TRANSFER_GAS_LIMIT
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Recipient {
+    pub address: String,
+    /// Set when the recipient is a derived child.
+    pub child_index: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ManifestEntry {
+    pub nonce: u64,
+    pub to: String,
+    pub child_index: Option<u32>,
+    #[serde(with = "quantity")]
+    pub value: U256,
+    pub hash: String,
+}
+
+/// Everything needed to review a batch without decoding the raw transactions.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DistributionManifest {
+    pub version: u32,
+    pub chain_id: u64,
+    pub from: String,
+    pub start_nonce: u64,
+    /// Nonce of the last transaction.
+    pub end_nonce: u64,
+    pub fees: FeePolicy,
+    pub gas_limit: u64,
+    #[serde(with = "quantity")]
+    pub total_value: U256,
+    /// Fees if every transaction pays its full gas limit at the maximum price.
+    #[serde(with = "quantity")]
+    pub max_total_fee: U256,
+    /// `total_value + max_total_fee`: the balance `from` needs before broadcasting.
+    #[serde(with = "quantity")]
+    pub required_balance: U256,
+    /// The seed of a random amount plan, to reproduce it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount_seed: Option<u64>,
+    pub created_at_ms: u64,
+    pub entries: Vec<ManifestEntry>,
+}
+
+/// Signed transactions in nonce order, plus their manifest. Broadcast them in
+/// order; a gap stalls every later nonce.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DistributionBatch {
+    pub manifest: DistributionManifest,
+    pub transactions: Vec<SignedTransaction>,
+}
+
+fn overflow() -> Error {
+    Error::invalid_argument("amounts", "total overflows 256 bits")
+}
+
+/// Signs one transfer from `key` to each recipient.
+pub fn sign_distribution(key: &PrivateKey, recipients: &[Recipient], params: &DistributionParams) -> Result<DistributionBatch, Error> {
+    if recipients.is_empty() {
+        return Err(Error::invalid_argument("recipients", "at least one recipient is required"));
+    }
+    let public_key = secp256k1::PublicKey::from_secret_key(&secp256k1::Secp256k1::signing_only(), &key.to_secret_key());
+    let from = utils::public_key_to_address(&public_key);
+    let mut amounts = params.amounts.clone();
+    amounts.gas_reserve.get_or_insert(params.fees.max_fee(params.gas_limit)?);
+    let plan = amounts.plan(recipients.len())?;
+    let end_nonce = u64::try_from(recipients.len() - 1)
+        .ok()
+        .and_then(|n| params.start_nonce.checked_add(n))
+        .ok_or_else(|| Error::invalid_argument("start_nonce", "nonce overflows"))?;
+
+    let mut transactions = Vec::with_capacity(recipients.len());
+    let mut entries = Vec::with_capacity(recipients.len());
+    let mut total_value = U256::zero();
+    let mut max_total_fee = U256::zero();
+    for ((nonce, recipient), value) in (params.start_nonce..=end_nonce).zip(recipients).zip(plan.amounts) {
+        if recipient.address.eq_ignore_ascii_case(&from) {
+            return Err(Error::invalid_argument("recipients", format!("{} is the sender", recipient.address)));
+        }
+        if value.is_zero() {
+            return Err(Error::invalid_argument(
+                "amounts",
+                format!("recipient {} would receive 0 wei", recipient.address),
+            ));
+        }
+        let transaction = params.fees.transfer(params.chain_id, nonce, &recipient.address, value, params.gas_limit);
+        let signed = transaction.sign(key)?;
+
+        total_value = total_value.checked_add(value).ok_or_else(overflow)?;
+        max_total_fee = max_total_fee.checked_add(transaction.max_fee()?).ok_or_else(overflow)?;
+        entries.push(ManifestEntry {
+            nonce,
+            to: recipient.address.clone(),
+            child_index: recipient.child_index,
+            value,
+            hash: signed.hash.clone(),
+        });
+        transactions.push(signed);
+    }
+
+    Ok(DistributionBatch {
+        manifest: DistributionManifest {
+            version: MANIFEST_VERSION,
+            chain_id: params.chain_id,
+            from,
+            start_nonce: params.start_nonce,
+            end_nonce,
+            fees: params.fees,
+            gas_limit: params.gas_limit,
+            total_value,
+            max_total_fee,
+            required_balance: total_value.checked_add(max_total_fee).ok_or_else(overflow)?,
+            amount_seed: plan.seed,
+            created_at_ms: utils::now_millis(),
+            entries,
+        },
+        transactions,
+    })
+}
+
+/// Funds children `start..start + count` from the parent wallet.
+pub fn sign_split_distribution(wallet: &HdWallet, start: u32, count: u32, params: &DistributionParams) -> Result<DistributionBatch, Error> {
+    let recipients: Vec<Recipient> = (start..)
+        .zip(wallet.children_range(start, count)?)
+        .map(|(index, child)| Recipient { address: child.address, child_index: Some(index) })
+        .collect();
+    sign_distribution(&wallet.parent()?.private_key, &recipients, params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::split_plan::AmountStrategy;
+
+    const HARDHAT: &str = "test test test test test test test test test test test junk";
+    const PARENT: &str = "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266";
+
+    fn gwei(amount: u64) -> U256 {
+        U256::from(amount) * U256::exp10(9)
+    }
+
+    fn params(fees: FeePolicy, strategy: AmountStrategy) -> DistributionParams {
+        DistributionParams {
+            chain_id: 1,
+            start_nonce: 7,
+            fees,
+            amounts: strategy.into(),
+            gas_limit: TRANSFER_GAS_LIMIT,
+        }
+    }
+
+    fn recipient(address: &str) -> Recipient {
+        Recipient { address: address.to_string(), child_index: None }
+    }
+
+    #[test]
+    fn manifest_matches_the_signed_transactions() {
+        let wallet = HdWallet::from_mnemonic(HARDHAT, None).unwrap();
+        let fees = FeePolicy::Eip1559 { max_fee_per_gas: gwei(30), max_priority_fee_per_gas: gwei(1) };
+        let params = params(fees, AmountStrategy::Equal { total: U256::exp10(18) });
+        let batch = sign_split_distribution(&wallet, 1, 3, &params).unwrap();
+        let manifest = &batch.manifest;
+
+        assert_eq!(manifest.from, PARENT);
+        assert_eq!((manifest.start_nonce, manifest.end_nonce), (7, 9));
+        assert_eq!(batch.transactions.len(), 3);
+        let children = wallet.children_range(1, 3).unwrap();
+        let mut total_value = U256::zero();
+        for (i, (entry, signed)) in manifest.entries.iter().zip(&batch.transactions).enumerate() {
+            assert_eq!(entry.nonce, 7 + i as u64);
+            assert_eq!(signed.nonce, entry.nonce);
+            assert_eq!(signed.from, PARENT);
+            assert_eq!(entry.child_index, Some(1 + i as u32));
+            assert_eq!(entry.to, children[i].address);
+            assert_eq!(entry.hash, signed.hash);
+            // Signing is deterministic, so the entry rebuilds the exact bytes.
+            let rebuilt = fees
+                .transfer(1, entry.nonce, &entry.to, entry.value, TRANSFER_GAS_LIMIT)
+                .sign(&wallet.parent().unwrap().private_key)
+                .unwrap();
+            assert_eq!(rebuilt.raw, signed.raw);
+            total_value += entry.value;
+        }
+        assert_eq!(manifest.total_value, total_value);
+        assert_eq!(manifest.max_total_fee, gwei(30) * 21_000 * 3);
+        assert_eq!(manifest.required_balance, manifest.total_value + manifest.max_total_fee);
+        // `Equal { total }` reserves each transfer's gas out of the total.
+        assert_eq!(manifest.required_balance, U256::exp10(18));
+    }
+
+    #[test]
+    fn default_split_funds_the_listed_children() {
+        use crate::derivation_path::{DEFAULT_CHILD_TEMPLATE, DEFAULT_PARENT_PATH};
+        use crate::{DEFAULT_SPLIT_CHILD_COUNT, FIRST_SPLIT_CHILD};
+
+        let wallet = HdWallet::from_mnemonic(HARDHAT, None).unwrap();
+        let split = wallet
+            .split(&DEFAULT_PARENT_PATH.parse().unwrap(), &DEFAULT_CHILD_TEMPLATE.parse().unwrap(), FIRST_SPLIT_CHILD, DEFAULT_SPLIT_CHILD_COUNT)
+            .unwrap();
+        let fixed = params(FeePolicy::Legacy { gas_price: gwei(1) }, AmountStrategy::Fixed { amount: U256::exp10(15) });
+        let batch = sign_split_distribution(&wallet, FIRST_SPLIT_CHILD, DEFAULT_SPLIT_CHILD_COUNT, &fixed).unwrap();
+
+        let listed: Vec<&str> = split.child_wallets.iter().map(|child| child.address.as_str()).collect();
+        let funded: Vec<&str> = batch.manifest.entries.iter().map(|entry| entry.to.as_str()).collect();
+        assert_eq!(listed, funded);
+        assert_eq!(split.child_start_index, 1);
+        assert!(!listed.contains(&PARENT));
+    }
+
+    #[test]
+    fn an_explicit_gas_reserve_replaces_the_fee() {
+        let mut params = params(FeePolicy::Legacy { gas_price: gwei(1) }, AmountStrategy::Equal { total: U256::from(3_000) });
+        params.amounts.gas_reserve = Some(U256::zero());
+        let batch = sign_distribution(&PrivateKey::from_hex(&"46".repeat(32)).unwrap(), &[recipient(PARENT)], &params).unwrap();
+        assert_eq!(batch.manifest.total_value, U256::from(3_000));
+        assert_eq!(batch.manifest.max_total_fee, gwei(21_000));
+    }
+
+    #[test]
+    fn rejects_unusable_batches() {
+        let wallet = HdWallet::from_mnemonic(HARDHAT, None).unwrap();
+        let key = wallet.parent().unwrap().private_key;
+        let fixed = params(FeePolicy::Legacy { gas_price: gwei(1) }, AmountStrategy::Fixed { amount: U256::exp10(15) });
+        let child = wallet.child(1).unwrap().address;
+
+        assert!(sign_distribution(&key, &[], &fixed).is_err());
+        assert!(sign_distribution(&key, &[recipient(&child), recipient(&PARENT.to_lowercase())], &fixed).is_err());
+
+        let mut zero = params(FeePolicy::Legacy { gas_price: gwei(1) }, AmountStrategy::Fixed { amount: U256::zero() });
+        zero.amounts.min_amount = Some(U256::zero());
+        assert!(sign_distribution(&key, &[recipient(&child)], &zero).is_err());
+
+        let overflowing = DistributionParams { start_nonce: u64::MAX, ..fixed.clone() };
+        assert!(sign_distribution(&key, &[recipient(&child), recipient(&child)], &overflowing).is_err());
+        assert!(sign_distribution(&key, &[recipient(&child)], &overflowing).is_ok());
+
+        // Not enough to cover one transfer's gas.
+        let short = params(FeePolicy::Legacy { gas_price: gwei(1) }, AmountStrategy::Equal { total: gwei(21_000) });
+        assert!(sign_distribution(&key, &[recipient(&child)], &short).is_err());
+    }
+}
@@ -0,0 +1,248 @@
+use crate::error::Error;
+use crate::secret::SecretPhrase;
+use crate::utils;
+use ::bip39::{Mnemonic, Language};
+use rand::RngCore;
+use serde::{Serialize, Deserialize};
+use zeroize::Zeroizing;
+
+/// Kind of user-supplied entropy.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EntropySource {
+    /// Six-sided dice rolls, digits `1`-`6`.
+    Dice,
+    /// Coin flips, `0`/`1` or `h`/`t`.
+    Coin,
+    /// Raw hex bytes, optionally prefixed with `0x` or `0X`.
+    Hex,
+}
+
+impl EntropySource {
+    pub fn from_name(name: &str) -> Result<Self, Error> {
+        match name.trim().to_lowercase().as_str() {
+            "dice" => Ok(EntropySource::Dice),
+            "coin" => Ok(EntropySource::Coin),
+            "hex" => Ok(EntropySource::Hex),
+            other => Err(Error::invalid_argument("entropy source", format!("unknown source {}", other))),
+        }
+    }
+
+    /// Bits of entropy carried by one input symbol.
+    fn bits_per_symbol(self) -> f64 {
+        match self {
+            EntropySource::Dice => 6f64.log2(),
+            EntropySource::Coin => 1.0,
+            EntropySource::Hex => 4.0,
+        }
+    }
+}
+
+/// Every value needed to rebuild the mnemonic by hand. It contains the
+/// entropy itself, so it is as sensitive as the mnemonic.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EntropyTranscript {
+    pub source: EntropySource,
+    /// Input after normalization (whitespace stripped, coin faces mapped to bits).
+    pub normalized_input: String,
+    pub symbol_count: usize,
+    pub estimated_bits: f64,
+    pub required_bits: usize,
+    /// `"direct"` when the input bits are used as-is, `"sha256"` when the
+    /// normalized input is hashed and truncated.
+    pub method: String,
+    pub user_entropy: String,
+    pub system_entropy: Option<String>,
+    /// `user_entropy` XOR `system_entropy`, or `user_entropy` when not mixed.
+    pub final_entropy: String,
+    pub word_count: usize,
+    pub language: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EntropyMnemonic {
+    #[serde(with = "crate::secret::exposed")]
+    pub mnemonic: SecretPhrase,
+    pub transcript: EntropyTranscript,
+}
+
+fn normalize_input(source: EntropySource, input: &str) -> Result<String, Error> {
+    let mut normalized = String::new();
+    let stripped = input.trim();
+    let stripped = match source {
+        EntropySource::Hex => stripped
+            .strip_prefix("0x")
+            .or_else(|| stripped.strip_prefix("0X"))
+            .unwrap_or(stripped),
+        _ => stripped,
+    };
+
+    for (i, c) in stripped.chars().filter(|c| !c.is_whitespace()).enumerate() {
+        let symbol = match (source, c.to_ascii_lowercase()) {
+            (EntropySource::Dice, c @ '1'..='6') => c,
+            (EntropySource::Coin, '0' | 't') => '0',
+            (EntropySource::Coin, '1' | 'h') => '1',
+            (EntropySource::Hex, c) if c.is_ascii_hexdigit() => c,
+            _ => return Err(Error::InvalidEntropy {
+                reason: format!("invalid {:?} symbol '{}' at position {}", source, c, i),
+            }),
+        };
+        normalized.push(symbol);
+    }
+
+    Ok(normalized)
+}
+
+/// Packs `input` into bytes when it maps exactly onto whole bits.
+fn direct_bytes(source: EntropySource, input: &str) -> Option<Vec<u8>> {
+    match source {
+        EntropySource::Hex if input.len().is_multiple_of(2) => hex::decode(input).ok(),
+        EntropySource::Coin if input.len().is_multiple_of(8) => Some(
+            input
+                .as_bytes()
+                .chunks(8)
+                .map(|chunk| chunk.iter().fold(0u8, |byte, bit| (byte << 1) | (bit - b'0')))
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+/// Builds a mnemonic from dice rolls, coin flips or hex entropy.
+///
+/// Input must carry at least as many bits as the mnemonic's entropy. Coin and
+/// hex input of exactly that length is used verbatim, so the result can be
+/// checked against any BIP39 tool; anything else is hashed with SHA-256 and
+/// truncated. With `mix_system_entropy` the result is XORed with bytes from
+/// the system RNG, which keeps the mnemonic safe even if the rolls were biased.
+pub fn mnemonic_from_user_entropy(
+    source: EntropySource,
+    input: &str,
+    language: Language,
+    word_count: usize,
+    mix_system_entropy: bool,
+) -> Result<EntropyMnemonic, Error> {
+    if !crate::bip39::SUPPORTED_WORD_COUNTS.contains(&word_count) {
+        return Err(Error::InvalidWordCount { word_count });
+    }
+    let required_bits = word_count * 11 * 32 / 33;
+    let required_bytes = required_bits / 8;
+
+    let normalized = normalize_input(source, input)?;
+    let symbol_count = normalized.len();
+    let estimated_bits = symbol_count as f64 * source.bits_per_symbol();
+    if estimated_bits < required_bits as f64 {
+        let needed = (required_bits as f64 / source.bits_per_symbol()).ceil();
+        return Err(Error::InvalidEntropy {
+            reason: format!(
+                "{} {:?} symbols give {:.1} bits, {} words need {} bits ({} symbols)",
+                symbol_count, source, estimated_bits, word_count, required_bits, needed
+            ),
+        });
+    }
+
+    let (method, user_entropy) = match direct_bytes(source, &normalized) {
+        Some(bytes) if bytes.len() == required_bytes => ("direct", Zeroizing::new(bytes)),
+        _ => ("sha256", Zeroizing::new(utils::sha256(normalized.as_bytes())[..required_bytes].to_vec())),
+    };
+
+    let system_entropy = if mix_system_entropy {
+        let mut bytes = Zeroizing::new(vec![0u8; required_bytes]);
+        rand::thread_rng().fill_bytes(&mut bytes);
+        Some(bytes)
+    } else {
+        None
+    };
+
+    let final_entropy: Zeroizing<Vec<u8>> = match &system_entropy {
+        Some(system) => Zeroizing::new(user_entropy.iter().zip(system.iter()).map(|(u, s)| u ^ s).collect()),
+        None => user_entropy.clone(),
+    };
+
+    let mnemonic = Mnemonic::from_entropy_in(language, &final_entropy)?;
+
+    Ok(EntropyMnemonic {
+        mnemonic: crate::bip39::format_phrase(&mnemonic),
+        transcript: EntropyTranscript {
+            source,
+            normalized_input: normalized,
+            symbol_count,
+            estimated_bits,
+            required_bits,
+            method: method.to_string(),
+            user_entropy: hex::encode(&user_entropy[..]),
+            system_entropy: system_entropy.map(|bytes| hex::encode(&bytes[..])),
+            final_entropy: hex::encode(&final_entropy[..]),
+            word_count,
+            language: crate::bip39::language_code(language).to_string(),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn english(source: EntropySource, input: &str, mix: bool) -> Result<EntropyMnemonic, Error> {
+        mnemonic_from_user_entropy(source, input, Language::English, 12, mix)
+    }
+
+    #[test]
+    fn rejects_too_little_entropy() {
+        // 12 words need 128 bits: 50 dice rolls, 128 flips or 32 hex digits.
+        assert!(matches!(english(EntropySource::Dice, &"6".repeat(49), false), Err(Error::InvalidEntropy { .. })));
+        assert!(matches!(english(EntropySource::Coin, &"1".repeat(127), false), Err(Error::InvalidEntropy { .. })));
+        assert!(matches!(english(EntropySource::Hex, &"0".repeat(31), false), Err(Error::InvalidEntropy { .. })));
+        assert!(matches!(english(EntropySource::Dice, &"7".repeat(50), false), Err(Error::InvalidEntropy { .. })));
+    }
+
+    #[test]
+    fn exact_hex_and_coin_input_is_used_directly() {
+        for input in ["00".repeat(16), format!("0x{}", "00".repeat(16)), format!("0X{}", "00".repeat(16))] {
+            let result = english(EntropySource::Hex, &input, false).unwrap();
+            assert_eq!(result.transcript.method, "direct");
+            assert_eq!(
+                result.mnemonic.expose(),
+                "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about"
+            );
+        }
+
+        let flips = "thhh hhhh ".repeat(16);
+        let result = english(EntropySource::Coin, &flips, false).unwrap();
+        assert_eq!(result.transcript.method, "direct");
+        assert_eq!(result.transcript.user_entropy, "7f".repeat(16));
+        assert_eq!(
+            result.mnemonic.expose(),
+            "legal winner thank year wave sausage worth useful legal winner thank yellow"
+        );
+    }
+
+    #[test]
+    fn dice_and_longer_input_are_hashed() {
+        let rolls = "12345612345612345612345612345612345612345612345612";
+        let result = english(EntropySource::Dice, rolls, false).unwrap();
+        assert_eq!(result.transcript.method, "sha256");
+        assert_eq!(result.transcript.symbol_count, 50);
+        assert_eq!(result.transcript.user_entropy, "ee72ae915a4e6ea7ccbeb8e5e5eecef2");
+        assert_eq!(
+            result.mnemonic.expose(),
+            "unveil nice picture region tragic fault cream strike tourist control recipe tourist"
+        );
+
+        let long_hex = english(EntropySource::Hex, &"00".repeat(17), false).unwrap();
+        assert_eq!(long_hex.transcript.method, "sha256");
+    }
+
+    #[test]
+    fn transcript_reproduces_the_mnemonic() {
+        let result = english(EntropySource::Dice, &"3".repeat(60), true).unwrap();
+        let transcript = &result.transcript;
+        let user = hex::decode(&transcript.user_entropy).unwrap();
+        let system = hex::decode(transcript.system_entropy.as_ref().unwrap()).unwrap();
+        let mixed: Vec<u8> = user.iter().zip(&system).map(|(u, s)| u ^ s).collect();
+        assert_eq!(hex::encode(&mixed), transcript.final_entropy);
+
+        let rebuilt = Mnemonic::from_entropy_in(Language::English, &mixed).unwrap();
+        assert_eq!(crate::bip39::format_phrase(&rebuilt).expose(), result.mnemonic.expose());
+    }
+}
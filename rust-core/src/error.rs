@@ -0,0 +1,121 @@
+use crate::bip39::SUPPORTED_WORD_COUNTS;
+use serde::Serialize;
+use std::fmt;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::JsValue;
+
+/// Every failure the core can report.
+///
+/// The variant name, in `SCREAMING_SNAKE_CASE`, is the stable `code` seen by
+/// JS; the fields are its `context`. Rename neither without a major version.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "code", content = "context", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Error {
+    UnsupportedLanguage { language: String },
+    InvalidWordCount { word_count: usize },
+    /// A word outside the wordlist, at a zero-based position.
+    InvalidWord { position: usize },
+    InvalidChecksum,
+    InvalidEntropy { reason: String },
+    InvalidPath { path: String, reason: String },
+    InvalidExtendedKey { reason: String },
+    InvalidAddress { address: String, reason: String },
+    /// The operation needs a private or master key the caller does not hold.
+    PrivateKeyUnavailable { reason: String },
+    /// The keyring was locked explicitly or by its idle timeout.
+    KeyringLocked,
+    InvalidKeystore { reason: String },
+    InvalidVault { reason: String },
+    /// A SLIP-39 share is malformed or the shares do not combine.
+    InvalidShare { reason: String },
+    /// The keystore or vault MAC did not verify.
+    InvalidPassword,
+    DerivationFailed { path: String, reason: String },
+    InvalidArgument { argument: String, reason: String },
+    SerializationFailed { reason: String },
+}
+
+impl Error {
+    pub fn invalid_path(path: impl fmt::Display, reason: impl fmt::Display) -> Self {
+        Error::InvalidPath { path: path.to_string(), reason: reason.to_string() }
+    }
+
+    pub fn derivation_failed(path: impl fmt::Display, reason: impl fmt::Debug) -> Self {
+        Error::DerivationFailed { path: path.to_string(), reason: format!("{:?}", reason) }
+    }
+
+    pub fn invalid_argument(argument: &str, reason: impl fmt::Display) -> Self {
+        Error::InvalidArgument { argument: argument.to_string(), reason: reason.to_string() }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::UnsupportedLanguage { language } => write!(f, "unsupported mnemonic language: {}", language),
+            Error::InvalidWordCount { word_count } => {
+                write!(f, "unsupported word count {}, expected one of {:?}", word_count, SUPPORTED_WORD_COUNTS)
+            }
+            Error::InvalidWord { position } => write!(f, "word {} is not in the wordlist", position + 1),
+            Error::InvalidChecksum => write!(f, "mnemonic checksum is invalid"),
+            Error::InvalidEntropy { reason } => write!(f, "invalid entropy: {}", reason),
+            Error::InvalidPath { path, reason } => write!(f, "invalid derivation path {}: {}", path, reason),
+            Error::InvalidExtendedKey { reason } => write!(f, "invalid extended key: {}", reason),
+            Error::InvalidAddress { address, reason } => write!(f, "invalid address {}: {}", address, reason),
+            Error::PrivateKeyUnavailable { reason } => write!(f, "{}", reason),
+            Error::KeyringLocked => write!(f, "keyring is locked"),
+            Error::InvalidKeystore { reason } => write!(f, "invalid keystore: {}", reason),
+            Error::InvalidVault { reason } => write!(f, "invalid vault: {}", reason),
+            Error::InvalidShare { reason } => write!(f, "invalid share: {}", reason),
+            Error::InvalidPassword => write!(f, "wrong password"),
+            Error::DerivationFailed { path, reason } => write!(f, "derivation of {} failed: {}", path, reason),
+            Error::InvalidArgument { argument, reason } => write!(f, "invalid {}: {}", argument, reason),
+            Error::SerializationFailed { reason } => write!(f, "serialization failed: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<::bip39::Error> for Error {
+    fn from(error: ::bip39::Error) -> Self {
+        match error {
+            ::bip39::Error::BadWordCount(word_count) => Error::InvalidWordCount { word_count },
+            ::bip39::Error::UnknownWord(position) => Error::InvalidWord { position },
+            ::bip39::Error::InvalidChecksum => Error::InvalidChecksum,
+            ::bip39::Error::BadEntropyBitCount(bits) => Error::InvalidEntropy {
+                reason: format!("{} bits is not a BIP39 entropy length", bits),
+            },
+            ::bip39::Error::AmbiguousLanguages(_) => Error::UnsupportedLanguage {
+                language: "ambiguous".to_string(),
+            },
+        }
+    }
+}
+
+#[cfg(feature = "wasm")]
+impl From<serde_wasm_bindgen::Error> for Error {
+    fn from(error: serde_wasm_bindgen::Error) -> Self {
+        Error::SerializationFailed { reason: error.to_string() }
+    }
+}
+
+/// Becomes a JS `Error` named `DwssError` with `code` and `context` set, so
+/// callers can switch on `err.code` instead of matching English messages.
+#[cfg(feature = "wasm")]
+impl From<Error> for JsValue {
+    fn from(error: Error) -> Self {
+        let js_error = js_sys::Error::new(&error.to_string());
+        js_error.set_name("DwssError");
+
+        let fields = serde_wasm_bindgen::to_value(&error).unwrap_or(JsValue::UNDEFINED);
+        for key in ["code", "context"] {
+            let value = js_sys::Reflect::get(&fields, &key.into())
+                .ok()
+                .filter(|value| !value.is_undefined())
+                .unwrap_or_else(|| js_sys::Object::new().into());
+            let _ = js_sys::Reflect::set(&js_error, &key.into(), &value);
+        }
+        js_error.into()
+    }
+}
@@ -0,0 +1,364 @@
+use crate::derivation_path::ChildNumber;
+use crate::error::Error;
+use crate::secret::{self, SecretExtendedKey};
+use crate::utils;
+use hdwallet::{ExtendedPrivKey, ExtendedPubKey};
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use serde::{Serialize, Deserialize};
+use std::fmt;
+use std::str::FromStr;
+use zeroize::{Zeroize, Zeroizing};
+
+const XPRV_VERSION: [u8; 4] = [0x04, 0x88, 0xAD, 0xE4];
+const XPUB_VERSION: [u8; 4] = [0x04, 0x88, 0xB2, 0x1E];
+const TPRV_VERSION: [u8; 4] = [0x04, 0x35, 0x83, 0x94];
+const TPUB_VERSION: [u8; 4] = [0x04, 0x35, 0x87, 0xCF];
+const SERIALIZED_LENGTH: usize = 78;
+const HARDENED_OFFSET: u32 = 1 << 31;
+
+/// A BIP32 node together with the metadata that goes into `xprv`/`xpub`.
+/// Public-only keys (from an `xpub`) have no private key and can only derive
+/// non-hardened children.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ExtendedKey {
+    pub depth: u8,
+    pub parent_fingerprint: [u8; 4],
+    /// Raw child number, with the hardened bit set for hardened children.
+    pub child_number: u32,
+    pub chain_code: [u8; 32],
+    pub private_key: Option<SecretKey>,
+    pub public_key: PublicKey,
+}
+
+/// Public description of an extended key, safe to show or log.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ExtendedKeyInfo {
+    pub xpub: String,
+    pub depth: u8,
+    pub fingerprint: String,
+    pub parent_fingerprint: String,
+    pub child_index: u32,
+    pub hardened: bool,
+    pub has_private_key: bool,
+}
+
+impl ExtendedKey {
+    pub fn master(key: &ExtendedPrivKey) -> Result<Self, Error> {
+        Self::from_private(0, [0; 4], 0, key)
+    }
+
+    pub fn from_private(
+        depth: u8,
+        parent_fingerprint: [u8; 4],
+        child_number: u32,
+        key: &ExtendedPrivKey,
+    ) -> Result<Self, Error> {
+        let secp = Secp256k1::signing_only();
+        Ok(ExtendedKey {
+            depth,
+            parent_fingerprint,
+            child_number,
+            chain_code: chain_code(&key.chain_code)?,
+            private_key: Some(key.private_key),
+            public_key: PublicKey::from_secret_key(&secp, &key.private_key),
+        })
+    }
+
+    pub fn is_private(&self) -> bool {
+        self.private_key.is_some()
+    }
+
+    /// First four bytes of HASH160 of the compressed public key.
+    pub fn fingerprint(&self) -> [u8; 4] {
+        let hash = utils::hash160(&self.public_key.serialize());
+        [hash[0], hash[1], hash[2], hash[3]]
+    }
+
+    pub fn to_private(&self) -> Option<SecretExtendedKey> {
+        self.private_key.map(|private_key| {
+            ExtendedPrivKey {
+                private_key,
+                chain_code: self.chain_code.to_vec(),
+            }
+            .into()
+        })
+    }
+
+    pub fn to_public(&self) -> ExtendedPubKey {
+        ExtendedPubKey {
+            public_key: self.public_key,
+            chain_code: self.chain_code.to_vec(),
+        }
+    }
+
+    /// Derives one level down, privately when the private key is known.
+    pub fn derive_child(&self, child: ChildNumber) -> Result<ExtendedKey, Error> {
+        if self.depth == u8::MAX {
+            return Err(Error::derivation_failed(child, "cannot derive below depth 255"));
+        }
+        let depth = self.depth + 1;
+        let child_number = child.key_index().raw_index();
+
+        match self.to_private() {
+            Some(key) => {
+                let derived: SecretExtendedKey = key.derive_private_key(child.key_index())
+                    .map_err(|e| Error::derivation_failed(child, e))?
+                    .into();
+                Self::from_private(depth, self.fingerprint(), child_number, &derived)
+            }
+            None if child.hardened => {
+                Err(Error::PrivateKeyUnavailable {
+                    reason: format!("hardened child {} cannot be derived from a public key", child),
+                })
+            }
+            None => {
+                let derived = self.to_public().derive_public_key(child.key_index())
+                    .map_err(|e| Error::derivation_failed(child, e))?;
+                Ok(ExtendedKey {
+                    depth,
+                    parent_fingerprint: self.fingerprint(),
+                    child_number,
+                    chain_code: chain_code(&derived.chain_code)?,
+                    private_key: None,
+                    public_key: derived.public_key,
+                })
+            }
+        }
+    }
+
+    pub fn derive(&self, children: &[ChildNumber]) -> Result<ExtendedKey, Error> {
+        children.iter().try_fold(self.clone(), |key, child| key.derive_child(*child))
+    }
+
+    fn serialize(&self, version: [u8; 4], key_data: &[u8; 33]) -> String {
+        let mut buf = Zeroizing::new(Vec::with_capacity(SERIALIZED_LENGTH));
+        buf.extend_from_slice(&version);
+        buf.push(self.depth);
+        buf.extend_from_slice(&self.parent_fingerprint);
+        buf.extend_from_slice(&self.child_number.to_be_bytes());
+        buf.extend_from_slice(&self.chain_code);
+        buf.extend_from_slice(key_data);
+        bs58::encode(&buf[..]).with_check().into_string()
+    }
+
+    pub fn to_xpub(&self) -> String {
+        self.serialize(XPUB_VERSION, &self.public_key.serialize())
+    }
+
+    pub fn to_xprv(&self) -> Result<String, Error> {
+        let private_key = self.private_key.ok_or_else(|| Error::PrivateKeyUnavailable {
+            reason: "extended key has no private key".to_string(),
+        })?;
+        let mut key_data = Zeroizing::new([0u8; 33]);
+        key_data[1..].copy_from_slice(&private_key[..]);
+        Ok(self.serialize(XPRV_VERSION, &key_data))
+    }
+
+    pub fn info(&self) -> ExtendedKeyInfo {
+        ExtendedKeyInfo {
+            xpub: self.to_xpub(),
+            depth: self.depth,
+            fingerprint: hex::encode(self.fingerprint()),
+            parent_fingerprint: hex::encode(self.parent_fingerprint),
+            child_index: self.child_number % HARDENED_OFFSET,
+            hardened: self.child_number >= HARDENED_OFFSET,
+            has_private_key: self.is_private(),
+        }
+    }
+}
+
+fn chain_code(bytes: &[u8]) -> Result<[u8; 32], Error> {
+    bytes.try_into().map_err(|_| invalid_key(format!("chain code must be 32 bytes, got {}", bytes.len())))
+}
+
+fn invalid_key(reason: impl std::fmt::Display) -> Error {
+    Error::InvalidExtendedKey { reason: reason.to_string() }
+}
+
+/// Parses `xprv`/`xpub` strings. Testnet `tprv`/`tpub` are rejected, since
+/// they would come back out with mainnet versions.
+impl FromStr for ExtendedKey {
+    type Err = Error;
+
+    fn from_str(encoded: &str) -> Result<Self, Self::Err> {
+        let data = Zeroizing::new(bs58::decode(encoded.trim())
+            .with_check(None)
+            .into_vec()
+            .map_err(|e| invalid_key(format!("invalid base58check: {}", e)))?);
+        if data.len() != SERIALIZED_LENGTH {
+            return Err(invalid_key(format!("must be {} bytes, got {}", SERIALIZED_LENGTH, data.len())));
+        }
+
+        let version = [data[0], data[1], data[2], data[3]];
+        let private = match version {
+            XPRV_VERSION => true,
+            XPUB_VERSION => false,
+            TPRV_VERSION | TPUB_VERSION => {
+                return Err(Error::invalid_argument("extended key", "testnet tprv/tpub keys are not supported"));
+            }
+            _ => return Err(invalid_key(format!("unsupported version {}", hex::encode(version)))),
+        };
+        let depth = data[4];
+        let parent_fingerprint = [data[5], data[6], data[7], data[8]];
+        let child_number = u32::from_be_bytes([data[9], data[10], data[11], data[12]]);
+        let chain_code = chain_code(&data[13..45])?;
+        if depth == 0 && (parent_fingerprint != [0; 4] || child_number != 0) {
+            return Err(invalid_key("master key has a parent fingerprint or child number"));
+        }
+
+        let (private_key, public_key) = if private {
+            if data[45] != 0 {
+                return Err(invalid_key("private key data must start with 0x00"));
+            }
+            let private_key = SecretKey::from_slice(&data[46..]).map_err(invalid_key)?;
+            let public_key = PublicKey::from_secret_key(&Secp256k1::signing_only(), &private_key);
+            (Some(private_key), public_key)
+        } else {
+            (None, PublicKey::from_slice(&data[45..]).map_err(invalid_key)?)
+        };
+
+        Ok(ExtendedKey {
+            depth,
+            parent_fingerprint,
+            child_number,
+            chain_code,
+            private_key,
+            public_key,
+        })
+    }
+}
+
+impl Drop for ExtendedKey {
+    fn drop(&mut self) {
+        if let Some(private_key) = self.private_key.as_mut() {
+            secret::erase_secret_key(private_key);
+        }
+        self.chain_code.zeroize();
+    }
+}
+
+impl fmt::Debug for ExtendedKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ExtendedKey")
+            .field("xpub", &self.to_xpub())
+            .field("has_private_key", &self.is_private())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::derivation_path::DerivationPath;
+
+    /// `(path, xpub, xprv)` from BIP32's test vectors.
+    fn check_vector(seed: &str, chain: &[(&str, &str, &str)]) {
+        let root = ExtendedPrivKey::with_seed(&hex::decode(seed).unwrap()).unwrap();
+        let master = ExtendedKey::master(&root).unwrap();
+        for (path, xpub, xprv) in chain {
+            let path: DerivationPath = path.parse().unwrap();
+            let key = master.derive(path.children()).unwrap();
+            assert_eq!(key.to_xpub(), *xpub, "xpub at {}", path);
+            assert_eq!(*key.to_xprv().unwrap(), **xprv, "xprv at {}", path);
+
+            let parsed: ExtendedKey = xprv.parse().unwrap();
+            assert!(parsed == key, "parsed xprv at {}", path);
+            let public: ExtendedKey = xpub.parse().unwrap();
+            assert_eq!(public.to_xpub(), *xpub);
+            assert!(!public.is_private());
+        }
+    }
+
+    #[test]
+    fn bip32_test_vector_1() {
+        check_vector("000102030405060708090a0b0c0d0e0f", &[
+            (
+                "m",
+                "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8",
+                "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi",
+            ),
+            (
+                "m/0'",
+                "xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw",
+                "xprv9uHRZZhk6KAJC1avXpDAp4MDc3sQKNxDiPvvkX8Br5ngLNv1TxvUxt4cV1rGL5hj6KCesnDYUhd7oWgT11eZG7XnxHrnYeSvkzY7d2bhkJ7",
+            ),
+            (
+                "m/0'/1",
+                "xpub6ASuArnXKPbfEwhqN6e3mwBcDTgzisQN1wXN9BJcM47sSikHjJf3UFHKkNAWbWMiGj7Wf5uMash7SyYq527Hqck2AxYysAA7xmALppuCkwQ",
+                "xprv9wTYmMFdV23N2TdNG573QoEsfRrWKQgWeibmLntzniatZvR9BmLnvSxqu53Kw1UmYPxLgboyZQaXwTCg8MSY3H2EU4pWcQDnRnrVA1xe8fs",
+            ),
+            (
+                "m/0'/1/2'",
+                "xpub6D4BDPcP2GT577Vvch3R8wDkScZWzQzMMUm3PWbmWvVJrZwQY4VUNgqFJPMM3No2dFDFGTsxxpG5uJh7n7epu4trkrX7x7DogT5Uv6fcLW5",
+                "xprv9z4pot5VBttmtdRTWfWQmoH1taj2axGVzFqSb8C9xaxKymcFzXBDptWmT7FwuEzG3ryjH4ktypQSAewRiNMjANTtpgP4mLTj34bhnZX7UiM",
+            ),
+            (
+                "m/0'/1/2'/2",
+                "xpub6FHa3pjLCk84BayeJxFW2SP4XRrFd1JYnxeLeU8EqN3vDfZmbqBqaGJAyiLjTAwm6ZLRQUMv1ZACTj37sR62cfN7fe5JnJ7dh8zL4fiyLHV",
+                "xprvA2JDeKCSNNZky6uBCviVfJSKyQ1mDYahRjijr5idH2WwLsEd4Hsb2Tyh8RfQMuPh7f7RtyzTtdrbdqqsunu5Mm3wDvUAKRHSC34sJ7in334",
+            ),
+            (
+                "m/0'/1/2'/2/1000000000",
+                "xpub6H1LXWLaKsWFhvm6RVpEL9P4KfRZSW7abD2ttkWP3SSQvnyA8FSVqNTEcYFgJS2UaFcxupHiYkro49S8yGasTvXEYBVPamhGW6cFJodrTHy",
+                "xprvA41z7zogVVwxVSgdKUHDy1SKmdb533PjDz7J6N6mV6uS3ze1ai8FHa8kmHScGpWmj4WggLyQjgPie1rFSruoUihUZREPSL39UNdE3BBDu76",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn bip32_test_vector_2() {
+        check_vector(
+            "fffcf9f6f3f0edeae7e4e1dedbd8d5d2cfccc9c6c3c0bdbab7b4b1aeaba8a5a29f9c999693908d8a8784817e7b7875726f6c696663605d5a5754514e4b484542",
+            &[
+                (
+                    "m",
+                    "xpub661MyMwAqRbcFW31YEwpkMuc5THy2PSt5bDMsktWQcFF8syAmRUapSCGu8ED9W6oDMSgv6Zz8idoc4a6mr8BDzTJY47LJhkJ8UB7WEGuduB",
+                    "xprv9s21ZrQH143K31xYSDQpPDxsXRTUcvj2iNHm5NUtrGiGG5e2DtALGdso3pGz6ssrdK4PFmM8NSpSBHNqPqm55Qn3LqFtT2emdEXVYsCzC2U",
+                ),
+                (
+                    "m/0",
+                    "xpub69H7F5d8KSRgmmdJg2KhpAK8SR3DjMwAdkxj3ZuxV27CprR9LgpeyGmXUbC6wb7ERfvrnKZjXoUmmDznezpbZb7ap6r1D3tgFxHmwMkQTPH",
+                    "xprv9vHkqa6EV4sPZHYqZznhT2NPtPCjKuDKGY38FBWLvgaDx45zo9WQRUT3dKYnjwih2yJD9mkrocEZXo1ex8G81dwSM1fwqWpWkeS3v86pgKt",
+                ),
+                (
+                    "m/0/2147483647'",
+                    "xpub6ASAVgeehLbnwdqV6UKMHVzgqAG8Gr6riv3Fxxpj8ksbH9ebxaEyBLZ85ySDhKiLDBrQSARLq1uNRts8RuJiHjaDMBU4Zn9h8LZNnBC5y4a",
+                    "xprv9wSp6B7kry3Vj9m1zSnLvN3xH8RdsPP1Mh7fAaR7aRLcQMKTR2vidYEeEg2mUCTAwCd6vnxVrcjfy2kRgVsFawNzmjuHc2YmYRmagcEPdU9",
+                ),
+                (
+                    "m/0/2147483647'/1",
+                    "xpub6DF8uhdarytz3FWdA8TvFSvvAh8dP3283MY7p2V4SeE2wyWmG5mg5EwVvmdMVCQcoNJxGoWaU9DCWh89LojfZ537wTfunKau47EL2dhHKon",
+                    "xprv9zFnWC6h2cLgpmSA46vutJzBcfJ8yaJGg8cX1e5StJh45BBciYTRXSd25UEPVuesF9yog62tGAQtHjXajPPdbRCHuWS6T8XA2ECKADdw4Ef",
+                ),
+                (
+                    "m/0/2147483647'/1/2147483646'",
+                    "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL",
+                    "xprvA1RpRA33e1JQ7ifknakTFpgNXPmW2YvmhqLQYMmrj4xJXXWYpDPS3xz7iAxn8L39njGVyuoseXzU6rcxFLJ8HFsTjSyQbLYnMpCqE2VbFWc",
+                ),
+                (
+                    "m/0/2147483647'/1/2147483646'/2",
+                    "xpub6FnCn6nSzZAw5Tw7cgR9bi15UV96gLZhjDstkXXxvCLsUXBGXPdSnLFbdpq8p9HmGsApME5hQTZ3emM2rnY5agb9rXpVGyy3bdW6EEgAtqt",
+                    "xprvA2nrNbFZABcdryreWet9Ea4LvTJcGsqrMzxHx98MMrotbir7yrKCEXw7nadnHM8Dq38EGfSh6dqA9QWTyefMLEcBYJUuekgW4BYPJcr9E7j",
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn rejects_testnet_versions() {
+        let master: ExtendedKey = "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi"
+            .parse()
+            .unwrap();
+        let mut private_data = [0u8; 33];
+        private_data[1..].copy_from_slice(&master.private_key.unwrap()[..]);
+        let tprv = master.serialize(TPRV_VERSION, &private_data);
+        let tpub = master.serialize(TPUB_VERSION, &master.public_key.serialize());
+        assert!(tprv.starts_with("tprv") && tpub.starts_with("tpub"));
+        for testnet in [tprv, tpub] {
+            assert!(matches!(
+                testnet.parse::<ExtendedKey>(),
+                Err(Error::InvalidArgument { ref argument, .. }) if argument == "extended key"
+            ));
+        }
+    }
+}
@@ -1,58 +1,394 @@
-use crate::{WalletInfo, bip39};
-use hdwallet::{HDWallet, DefaultKeyChain, ExtendedPrivKey, ExtendedPubKey};
-use secp256k1::{Secp256k1, SecretKey, PublicKey};
-use std::str::FromStr;
-
-pub fn derive_parent_wallet(mnemonic: &str) -> Result<WalletInfo, Box<dyn std::error::Error>> {
-    let seed = bip39::mnemonic_to_seed(mnemonic)?;
-    let secp = Secp256k1::new();
-    
-    // Derive master key
-    let master_key = ExtendedPrivKey::new(&secp, &seed)?;
-    
-    // Derive Ethereum path: m/44'/60'/0'/0/0
-    let path = "m/44'/60'/0'/0/0";
-    let child_key = master_key.derive_priv(&secp, &path.parse()?)?;
-    
-    let private_key = child_key.private_key;
-    let public_key = PublicKey::from_secret_key(&secp, &private_key);
-    
+use crate::{SplitResult, WalletInfo, bip39, utils};
+use crate::error::Error;
+use crate::bip85::Bip85;
+use crate::derivation_path::{ChildNumber, DerivationPath, PathTemplate, PathValues, ACCOUNT_PATH};
+use crate::extended_key::ExtendedKey;
+use crate::secret::{PrivateKey, SecretExtendedKey};
+use crate::watch_only::{ACCOUNT_DEPTH, EXTERNAL_CHAIN_DEPTH};
+use hdwallet::{ExtendedPrivKey, KeyIndex};
+use secp256k1::{Secp256k1, PublicKey, All};
+
+/// Walks `children` down from `key`, one level at a time. Errors name
+/// `path`, the full path being derived.
+/// Intermediate keys are wiped as soon as the next level is derived.
+fn derive_children(key: &ExtendedPrivKey, children: &[ChildNumber], path: &DerivationPath) -> Result<SecretExtendedKey, Error> {
+    let mut key = SecretExtendedKey::from(key.clone());
+    for child in children {
+        key = key.derive_private_key(child.key_index())
+            .map_err(|e| Error::derivation_failed(path, format!("{:?} at {}", e, child)))?
+            .into();
+    }
+    Ok(key)
+}
+
+pub fn derive_key(key: &ExtendedPrivKey, path: &DerivationPath) -> Result<SecretExtendedKey, Error> {
+    derive_children(key, path.children(), path)
+}
+
+fn wallet_info(secp: &Secp256k1<All>, key: &ExtendedPrivKey) -> WalletInfo {
+    let public_key = PublicKey::from_secret_key(secp, &key.private_key);
+
     // Generate Ethereum address
     let address = utils::public_key_to_address(&public_key);
-    
-    Ok(WalletInfo {
+
+    WalletInfo {
         address,
-        private_key: hex::encode(private_key.secret_bytes()),
+        private_key: PrivateKey::from_secret_key(&key.private_key),
         public_key: hex::encode(public_key.serialize()),
-    })
-}
-
-pub fn derive_child_wallets(mnemonic: &str, count: u32) -> Result<Vec<WalletInfo>, Box<dyn std::error::Error>> {
-    let seed = bip39::mnemonic_to_seed(mnemonic)?;
-    let secp = Secp256k1::new();
-    
-    // Derive master key
-    let master_key = ExtendedPrivKey::new(&secp, &seed)?;
-    
-    let mut wallets = Vec::new();
-    
-    for i in 0..count {
-        // Derive child path: m/44'/60'/0'/0/{i}
-        let path = format!("m/44'/60'/0'/0/{}", i);
-        let child_key = master_key.derive_priv(&secp, &path.parse()?)?;
-        
-        let private_key = child_key.private_key;
-        let public_key = PublicKey::from_secret_key(&secp, &private_key);
-        
-        // Generate Ethereum address
-        let address = utils::public_key_to_address(&public_key);
-        
-        wallets.push(WalletInfo {
-            address,
-            private_key: hex::encode(private_key.secret_bytes()),
-            public_key: hex::encode(public_key.serialize()),
-        });
-    }
-    
-    Ok(wallets)
-} 
\ No newline at end of file
+    }
+}
+
+/// A wallet with its master key and the `m/44'/60'/0'/0` node kept in memory.
+///
+/// Children of the default layout ([`HdWallet::child`] and the range and
+/// index-list variants) are one non-hardened step below the cached account
+/// node, so each costs a single HMAC and point multiplication instead of a
+/// fresh seed and five-level walk. Template paths start from the master key
+/// instead: the template's fixed prefix is derived once per call, then each
+/// child walks the remaining levels. Both keys are wiped when the wallet is
+/// dropped.
+pub struct HdWallet {
+    secp: Secp256k1<All>,
+    /// `None` when the wallet was imported from an `xprv` below the root.
+    master_key: Option<SecretExtendedKey>,
+    account: ExtendedKey,
+    account_key: SecretExtendedKey,
+}
+
+impl HdWallet {
+    pub fn from_seed(seed: &[u8]) -> Result<Self, Error> {
+        let master_key: SecretExtendedKey = ExtendedPrivKey::with_seed(seed)
+            .map_err(|e| Error::derivation_failed("m", e))?
+            .into();
+        Self::from_master_key(master_key)
+    }
+
+    fn from_master_key(master_key: SecretExtendedKey) -> Result<Self, Error> {
+        let account_path: DerivationPath = ACCOUNT_PATH.parse()?;
+        let account = ExtendedKey::master(&master_key)?.derive(account_path.children())?;
+
+        Self::new(Some(master_key), account)
+    }
+
+    /// Starts from an `xprv` instead of a mnemonic, placed by its depth the
+    /// way [`WatchOnlyWallet::from_xpub`](crate::watch_only::WatchOnlyWallet::from_xpub)
+    /// places an `xpub`:
+    ///
+    /// - depth 0, a BIP32 root key: the full wallet, master key included;
+    /// - depth 3, `m/44'/60'/0'`: the external chain `/0` is derived first;
+    /// - depth 4, `m/44'/60'/0'/0`: children are derived directly below it.
+    ///
+    /// Other depths are rejected. Below the root, paths from the master key
+    /// are unavailable.
+    pub fn from_extended_key(key: ExtendedKey) -> Result<Self, Error> {
+        match key.depth {
+            0 => {
+                let master_key = key.to_private().ok_or_else(|| Error::PrivateKeyUnavailable {
+                    reason: "an xpub cannot derive private keys; use watch-only derivation".to_string(),
+                })?;
+                Self::from_master_key(master_key)
+            }
+            ACCOUNT_DEPTH => Self::new(None, key.derive_child(ChildNumber { index: 0, hardened: false })?),
+            EXTERNAL_CHAIN_DEPTH => Self::new(None, key),
+            depth => Err(Error::InvalidExtendedKey {
+                reason: format!("expected an xprv at m, m/44'/60'/0' or m/44'/60'/0'/0, got depth {}", depth),
+            }),
+        }
+    }
+
+    fn new(master_key: Option<SecretExtendedKey>, account: ExtendedKey) -> Result<Self, Error> {
+        let account_key = account.to_private()
+            .ok_or_else(|| Error::PrivateKeyUnavailable {
+                reason: "an xpub cannot derive private keys; use watch-only derivation".to_string(),
+            })?;
+
+        Ok(HdWallet {
+            secp: Secp256k1::new(),
+            master_key,
+            account,
+            account_key,
+        })
+    }
+
+    fn master_key(&self) -> Result<&SecretExtendedKey, Error> {
+        self.master_key.as_ref().ok_or_else(|| Error::PrivateKeyUnavailable {
+            reason: "wallet was imported below the root and has no master key".to_string(),
+        })
+    }
+
+    /// The `m/44'/60'/0'/0` node.
+    pub fn account_key(&self) -> &ExtendedKey {
+        &self.account
+    }
+
+    pub fn master_fingerprint(&self) -> Result<[u8; 4], Error> {
+        Ok(ExtendedKey::master(self.master_key()?)?.fingerprint())
+    }
+
+    /// The extended key at any path from the master key, with BIP32 metadata.
+    pub fn extended_key(&self, path: &DerivationPath) -> Result<ExtendedKey, Error> {
+        ExtendedKey::master(self.master_key()?)?.derive(path.children())
+    }
+
+    /// BIP-85 children of this wallet's master key.
+    pub fn bip85(&self) -> Result<Bip85, Error> {
+        Ok(Bip85::new(self.master_key()?.clone()))
+    }
+
+    pub fn from_mnemonic(mnemonic: &str, passphrase: Option<&str>) -> Result<Self, Error> {
+        let seed = bip39::mnemonic_to_seed(mnemonic, passphrase)?;
+        Self::from_seed(seed.as_bytes())
+    }
+
+    /// The parent wallet at `m/44'/60'/0'/0/0`.
+    pub fn parent(&self) -> Result<WalletInfo, Error> {
+        self.child(0)
+    }
+
+    /// The child at `m/44'/60'/0'/0/{index}`.
+    pub fn child(&self, index: u32) -> Result<WalletInfo, Error> {
+        let key: SecretExtendedKey = self.account_key.derive_private_key(KeyIndex::Normal(index))
+            .map_err(|e| Error::derivation_failed(format!("child {}", index), e))?
+            .into();
+        Ok(wallet_info(&self.secp, &key))
+    }
+
+    pub fn children(&self, count: u32) -> Result<Vec<WalletInfo>, Error> {
+        self.children_range(0, count)
+    }
+
+    /// Children `start..start + count`, for paging and follow-up split batches.
+    pub fn children_range(&self, start: u32, count: u32) -> Result<Vec<WalletInfo>, Error> {
+        let end = index_range_end(start, count)?;
+        (start..end).map(|i| self.child(i)).collect()
+    }
+
+    /// Children at the given indexes, in the order given.
+    pub fn children_at(&self, indexes: &[u32]) -> Result<Vec<WalletInfo>, Error> {
+        indexes.iter().map(|i| self.child(*i)).collect()
+    }
+
+    pub fn derive_path(&self, path: &DerivationPath) -> Result<WalletInfo, Error> {
+        let key = derive_key(self.master_key()?, path)?;
+        Ok(wallet_info(&self.secp, &key))
+    }
+
+    /// Derives children by substituting `start..start + count` for `{index}`
+    /// in `template`; `values` supplies `{account}` and `{change}`.
+    pub fn children_with_template(
+        &self,
+        template: &PathTemplate,
+        values: PathValues,
+        start: u32,
+        count: u32,
+    ) -> Result<Vec<WalletInfo>, Error> {
+        let end = index_range_end(start, count)?;
+        self.template_children(template, values, start..end)
+    }
+
+    /// Like [`HdWallet::children_with_template`] for arbitrary indexes.
+    pub fn children_with_template_at(
+        &self,
+        template: &PathTemplate,
+        values: PathValues,
+        indexes: &[u32],
+    ) -> Result<Vec<WalletInfo>, Error> {
+        self.template_children(template, values, indexes.iter().copied())
+    }
+
+    /// The fixed prefix of the template is derived once and shared by every child.
+    fn template_children(
+        &self,
+        template: &PathTemplate,
+        values: PathValues,
+        indexes: impl Iterator<Item = u32>,
+    ) -> Result<Vec<WalletInfo>, Error> {
+        if !template.has_index() {
+            return Err(Error::invalid_path(template, "no {index} placeholder"));
+        }
+        let prefix = template.fixed_prefix();
+        let prefix_key = derive_key(self.master_key()?, &prefix)?;
+
+        let mut wallets = Vec::new();
+        for i in indexes {
+            let path = template.resolve(PathValues { index: i, ..values })?;
+            let key = derive_children(&prefix_key, &path.children()[prefix.children().len()..], &path)?;
+            wallets.push(wallet_info(&self.secp, &key));
+        }
+
+        Ok(wallets)
+    }
+
+    /// The parent at `parent_path` and `count` children from `child_template`.
+    pub fn split(
+        &self,
+        parent_path: &DerivationPath,
+        child_template: &PathTemplate,
+        start: u32,
+        count: u32,
+    ) -> Result<SplitResult, Error> {
+        Ok(SplitResult {
+            parent_wallet: self.derive_path(parent_path)?,
+            child_wallets: self.children_with_template(child_template, PathValues::default(), start, count)?,
+            child_start_index: start,
+        })
+    }
+}
+
+/// End of `start..start + count`, checked against the non-hardened index space.
+pub(crate) fn index_range_end(start: u32, count: u32) -> Result<u32, Error> {
+    start
+        .checked_add(count)
+        .filter(|end| *end <= 1 << 31)
+        .ok_or_else(|| Error::invalid_argument("child range", format!("{}+{} exceeds the non-hardened index space", start, count)))
+}
+
+pub fn derive_wallet_at_path(mnemonic: &str, passphrase: Option<&str>, path: &DerivationPath) -> Result<WalletInfo, Error> {
+    HdWallet::from_mnemonic(mnemonic, passphrase)?.derive_path(path)
+}
+
+pub fn derive_parent_wallet(mnemonic: &str, passphrase: Option<&str>) -> Result<WalletInfo, Error> {
+    // Derive Ethereum path: m/44'/60'/0'/0/0
+    HdWallet::from_mnemonic(mnemonic, passphrase)?.parent()
+}
+
+pub fn derive_child_wallets_with_template(
+    mnemonic: &str,
+    passphrase: Option<&str>,
+    template: &PathTemplate,
+    values: PathValues,
+    start: u32,
+    count: u32,
+) -> Result<Vec<WalletInfo>, Error> {
+    HdWallet::from_mnemonic(mnemonic, passphrase)?.children_with_template(template, values, start, count)
+}
+
+pub fn derive_child_wallets_with_template_at(
+    mnemonic: &str,
+    passphrase: Option<&str>,
+    template: &PathTemplate,
+    values: PathValues,
+    indexes: &[u32],
+) -> Result<Vec<WalletInfo>, Error> {
+    HdWallet::from_mnemonic(mnemonic, passphrase)?.children_with_template_at(template, values, indexes)
+}
+
+pub fn derive_child_wallets(mnemonic: &str, passphrase: Option<&str>, count: u32) -> Result<Vec<WalletInfo>, Error> {
+    // Derive child path: m/44'/60'/0'/0/{i}
+    HdWallet::from_mnemonic(mnemonic, passphrase)?.children(count)
+}
+
+pub fn derive_child_wallets_range(mnemonic: &str, passphrase: Option<&str>, start: u32, count: u32) -> Result<Vec<WalletInfo>, Error> {
+    HdWallet::from_mnemonic(mnemonic, passphrase)?.children_range(start, count)
+}
+
+pub fn derive_child_wallets_at(mnemonic: &str, passphrase: Option<&str>, indexes: &[u32]) -> Result<Vec<WalletInfo>, Error> {
+    HdWallet::from_mnemonic(mnemonic, passphrase)?.children_at(indexes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HARDHAT: &str = "test test test test test test test test test test test junk";
+    const HARDHAT_CHILD_1: &str = "0x70997970C51812dc3A010C7d01b50e0d17dc79C8";
+    const BIP32_TV1_ROOT: &str =
+        "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi";
+
+    fn xprv_at(wallet: &HdWallet, path: &str) -> ExtendedKey {
+        wallet.extended_key(&path.parse().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn parent_and_children_follow_the_default_layout() {
+        let wallet = HdWallet::from_mnemonic(HARDHAT, None).unwrap();
+        assert_eq!(wallet.parent().unwrap().address, "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266");
+        assert_eq!(wallet.child(1).unwrap().address, HARDHAT_CHILD_1);
+        assert_eq!(wallet.children_range(1, 2).unwrap()[1].address, "0x3C44CdDdB6a900fa2b585dd299e03d12FA4293BC");
+    }
+
+    #[test]
+    fn passphrase_changes_the_wallet() {
+        let plain = HdWallet::from_mnemonic(HARDHAT, None).unwrap();
+        let empty = HdWallet::from_mnemonic(HARDHAT, Some("")).unwrap();
+        let protected = HdWallet::from_mnemonic(HARDHAT, Some("TREZOR")).unwrap();
+        assert_eq!(empty.child(1).unwrap().address, plain.child(1).unwrap().address);
+        assert_eq!(protected.parent().unwrap().address, "0x9313778B3753108128B9c476EBDd42FbD566F4Ed");
+        assert_ne!(protected.child(1).unwrap().address, plain.child(1).unwrap().address);
+    }
+
+    #[test]
+    fn children_at_follows_the_given_order() {
+        let wallet = HdWallet::from_mnemonic(HARDHAT, None).unwrap();
+        let children = wallet.children_at(&[5, 2]).unwrap();
+        assert_eq!(children.len(), 2);
+        assert_eq!(children[0].address, wallet.child(5).unwrap().address);
+        assert_eq!(children[1].address, wallet.child(2).unwrap().address);
+        assert_eq!(children[1].private_key.as_bytes(), wallet.child(2).unwrap().private_key.as_bytes());
+        assert!(wallet.children_at(&[]).unwrap().is_empty());
+        assert!(wallet.children_at(&[1, 1 << 31]).is_err());
+    }
+
+    #[test]
+    fn templates_follow_their_own_layout() {
+        let wallet = HdWallet::from_mnemonic(HARDHAT, None).unwrap();
+        let preset = |name: &str| -> PathTemplate { crate::derivation_path::template_preset(name).unwrap().parse().unwrap() };
+
+        let default = wallet.children_with_template(&preset("default"), PathValues::default(), 0, 3).unwrap();
+        let cached = wallet.children_range(0, 3).unwrap();
+        assert!(default.iter().zip(&cached).all(|(a, b)| a.address == b.address));
+
+        let ethers = wallet.children_with_template(&preset("ethers-legacy"), PathValues::default(), 0, 3).unwrap();
+        assert_eq!(ethers[1].address, "0xb687FE7E47774B22F10Ca5E747496d81827167E3");
+        assert!(ethers.iter().zip(&cached).all(|(a, b)| a.address != b.address));
+        let ethers_at = wallet.children_with_template_at(&preset("ethers-legacy"), PathValues::default(), &[1]).unwrap();
+        assert_eq!(ethers_at[0].address, ethers[1].address);
+
+        let no_index: PathTemplate = "m/44'/60'/0'/0/0".parse().unwrap();
+        assert!(wallet.children_with_template(&no_index, PathValues::default(), 0, 1).is_err());
+    }
+
+    #[test]
+    fn root_xprv_imports_the_full_wallet() {
+        let wallet = HdWallet::from_mnemonic(HARDHAT, None).unwrap();
+        let imported = HdWallet::from_extended_key(xprv_at(&wallet, "m")).unwrap();
+        assert_eq!(imported.child(1).unwrap().address, HARDHAT_CHILD_1);
+        assert_eq!(imported.master_fingerprint().unwrap(), wallet.master_fingerprint().unwrap());
+
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        let from_seed = HdWallet::from_seed(&seed).unwrap();
+        let from_root = HdWallet::from_extended_key(BIP32_TV1_ROOT.parse().unwrap()).unwrap();
+        assert_eq!(from_root.child(0).unwrap().address, from_seed.child(0).unwrap().address);
+        assert_eq!(from_root.account_key().to_xpub(), from_seed.account_key().to_xpub());
+    }
+
+    #[test]
+    fn account_xprv_derives_the_external_chain() {
+        let wallet = HdWallet::from_mnemonic(HARDHAT, None).unwrap();
+        let imported = HdWallet::from_extended_key(xprv_at(&wallet, "m/44'/60'/0'")).unwrap();
+        assert_eq!(imported.child(1).unwrap().address, HARDHAT_CHILD_1);
+        assert_eq!(imported.account_key().to_xpub(), wallet.account_key().to_xpub());
+        assert!(matches!(imported.master_fingerprint(), Err(Error::PrivateKeyUnavailable { .. })));
+    }
+
+    #[test]
+    fn external_chain_xprv_is_used_as_is() {
+        let wallet = HdWallet::from_mnemonic(HARDHAT, None).unwrap();
+        let imported = HdWallet::from_extended_key(xprv_at(&wallet, "m/44'/60'/0'/0")).unwrap();
+        assert_eq!(imported.child(1).unwrap().address, HARDHAT_CHILD_1);
+    }
+
+    #[test]
+    fn other_depths_are_rejected() {
+        let wallet = HdWallet::from_mnemonic(HARDHAT, None).unwrap();
+        for path in ["m/44'", "m/44'/60'", "m/44'/60'/0'/0/1"] {
+            assert!(
+                matches!(HdWallet::from_extended_key(xprv_at(&wallet, path)), Err(Error::InvalidExtendedKey { .. })),
+                "{}",
+                path
+            );
+        }
+        let root_xpub: ExtendedKey = xprv_at(&wallet, "m").to_xpub().parse().unwrap();
+        assert!(matches!(HdWallet::from_extended_key(root_xpub), Err(Error::PrivateKeyUnavailable { .. })));
+    }
+}
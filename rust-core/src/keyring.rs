@@ -0,0 +1,244 @@
+use crate::distribution::{self, DistributionBatch, DistributionParams};
+use crate::error::Error;
+use crate::hd_wallet::{self, HdWallet};
+use crate::keystore::{self, Kdf, Keystore};
+use crate::signing::{self, Signature};
+use crate::sweep::{self, ChildBalance, SweepBatch, SweepParams};
+use crate::transaction::{SignedTransaction, Transaction};
+use crate::utils;
+use crate::vault;
+use crate::watch_only::PublicWalletInfo;
+use std::time::Duration;
+
+/// Matches `autoLockTimeout` in the mobile `SecurityService` defaults.
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Holds an unlocked wallet and exposes only public data and signatures.
+///
+/// Children are addressed by index; private keys never leave the keyring.
+/// Once `idle_timeout` passes without a call, the wallet is dropped (which
+/// wipes its keys) and every further call fails with `KeyringLocked`. The
+/// timeout is checked on each call and by [`Keyring::check_idle`]; the WASM
+/// `KeyringSession` also schedules a timer that locks it without a call.
+pub struct Keyring {
+    wallet: Option<HdWallet>,
+    /// `None` disables auto-lock.
+    idle_timeout: Option<Duration>,
+    last_used_ms: u64,
+}
+
+impl Keyring {
+    pub fn unlock(mnemonic: &str, passphrase: Option<&str>, idle_timeout: Option<Duration>) -> Result<Self, Error> {
+        Ok(Self::with_wallet(HdWallet::from_mnemonic(mnemonic, passphrase)?, idle_timeout))
+    }
+
+    /// Unlocks from a raw BIP32 seed, e.g. a SLIP-39 master secret.
+    pub fn unlock_seed(seed: &[u8], idle_timeout: Option<Duration>) -> Result<Self, Error> {
+        Ok(Self::with_wallet(HdWallet::from_seed(seed)?, idle_timeout))
+    }
+
+    fn with_wallet(wallet: HdWallet, idle_timeout: Option<Duration>) -> Self {
+        Keyring {
+            wallet: Some(wallet),
+            idle_timeout,
+            last_used_ms: utils::now_millis(),
+        }
+    }
+
+    /// Unlocks straight from an encrypted vault, so the mnemonic never
+    /// reaches the caller.
+    pub fn unlock_vault(vault: &[u8], password: &str, idle_timeout: Option<Duration>) -> Result<Self, Error> {
+        let contents = vault::open(vault, password)?;
+        Self::unlock(contents.mnemonic.expose(), contents.passphrase.as_deref().map(String::as_str), idle_timeout)
+    }
+
+    /// Wipes the keys. A locked keyring cannot be unlocked again; create a new one.
+    pub fn lock(&mut self) {
+        self.wallet = None;
+    }
+
+    /// Locks the keyring if it has been idle too long and reports whether it
+    /// is locked. Does not count as activity.
+    pub fn check_idle(&mut self) -> bool {
+        if let Some(timeout) = self.idle_timeout {
+            let idle_ms = utils::now_millis().saturating_sub(self.last_used_ms);
+            if u128::from(idle_ms) >= timeout.as_millis() {
+                self.lock();
+            }
+        }
+        self.wallet.is_none()
+    }
+
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        self.idle_timeout
+    }
+
+    pub fn set_idle_timeout(&mut self, idle_timeout: Option<Duration>) {
+        self.idle_timeout = idle_timeout;
+    }
+
+    /// The wallet, if still unlocked; counts as activity.
+    fn wallet(&mut self) -> Result<&HdWallet, Error> {
+        if self.check_idle() {
+            return Err(Error::KeyringLocked);
+        }
+        self.last_used_ms = utils::now_millis();
+        self.wallet.as_ref().ok_or(Error::KeyringLocked)
+    }
+
+    pub fn account_xpub(&mut self) -> Result<String, Error> {
+        Ok(self.wallet()?.account_key().to_xpub())
+    }
+
+    pub fn child(&mut self, index: u32) -> Result<PublicWalletInfo, Error> {
+        let wallet = self.wallet()?.child(index)?;
+        Ok(PublicWalletInfo {
+            index,
+            address: wallet.address.clone(),
+            public_key: wallet.public_key.clone(),
+        })
+    }
+
+    pub fn children_range(&mut self, start: u32, count: u32) -> Result<Vec<PublicWalletInfo>, Error> {
+        let end = hd_wallet::index_range_end(start, count)?;
+        (start..end).map(|i| self.child(i)).collect()
+    }
+
+    /// Signs a 32-byte digest with the key of child `index`.
+    pub fn sign_hash(&mut self, index: u32, hash: &[u8; 32]) -> Result<Signature, Error> {
+        let wallet = self.wallet()?.child(index)?;
+        Ok(signing::sign_hash(&wallet.private_key, hash))
+    }
+
+    /// Signs `transaction` with the key of child `index`.
+    pub fn sign_transaction(&mut self, index: u32, transaction: &Transaction) -> Result<SignedTransaction, Error> {
+        let wallet = self.wallet()?.child(index)?;
+        transaction.sign(&wallet.private_key)
+    }
+
+    /// Signs the parent's funding transfers to children `start..start + count`;
+    /// see [`distribution::sign_split_distribution`].
+    pub fn sign_split_distribution(&mut self, start: u32, count: u32, params: &DistributionParams) -> Result<DistributionBatch, Error> {
+        distribution::sign_split_distribution(self.wallet()?, start, count, params)
+    }
+
+    /// Sweeps children `start..start + count` back to `params.destination`;
+    /// see [`sweep::sign_sweep`].
+    pub fn sign_split_sweep(
+        &mut self,
+        start: u32,
+        count: u32,
+        balances: &[ChildBalance],
+        params: &SweepParams,
+    ) -> Result<SweepBatch, Error> {
+        sweep::sign_split_sweep(self.wallet()?, start, count, balances, params)
+    }
+
+    /// Encrypts the key of child `index` into a V3 keystore for other wallets.
+    pub fn export_keystore(&mut self, index: u32, password: &str, kdf: Kdf) -> Result<Keystore, Error> {
+        let wallet = self.wallet()?.child(index)?;
+        keystore::encrypt_key(&wallet.private_key, password, kdf)
+    }
+
+    /// Signs `message` as `personal_sign` (EIP-191) does.
+    pub fn sign_message(&mut self, index: u32, message: &[u8]) -> Result<Signature, Error> {
+        self.sign_hash(index, &signing::personal_message_hash(message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::sleep;
+
+    const HARDHAT: &str = "test test test test test test test test test test test junk";
+    const HARDHAT_CHILD_1: &str = "0x70997970C51812dc3A010C7d01b50e0d17dc79C8";
+
+    fn assert_locked<T: std::fmt::Debug>(result: Result<T, Error>) {
+        assert!(matches!(result, Err(Error::KeyringLocked)), "{:?}", result);
+    }
+
+    #[test]
+    fn locks_after_the_idle_timeout() {
+        let mut keyring = Keyring::unlock(HARDHAT, None, Some(Duration::from_millis(50))).unwrap();
+        assert_eq!(keyring.child(1).unwrap().address, HARDHAT_CHILD_1);
+        assert!(!keyring.check_idle());
+
+        sleep(Duration::from_millis(120));
+        assert!(keyring.check_idle());
+        assert_locked(keyring.child(1));
+    }
+
+    #[test]
+    fn a_call_after_the_timeout_fails_without_check_idle() {
+        let mut keyring = Keyring::unlock(HARDHAT, None, Some(Duration::from_millis(50))).unwrap();
+        sleep(Duration::from_millis(120));
+        assert_locked(keyring.sign_hash(1, &[0; 32]));
+        assert!(keyring.check_idle());
+    }
+
+    #[test]
+    fn calls_reset_the_idle_timer() {
+        let mut keyring = Keyring::unlock(HARDHAT, None, Some(Duration::from_millis(400))).unwrap();
+        for _ in 0..4 {
+            sleep(Duration::from_millis(150));
+            keyring.child(1).unwrap();
+        }
+        assert!(!keyring.check_idle());
+    }
+
+    #[test]
+    fn every_call_fails_after_lock() {
+        let mut keyring = Keyring::unlock(HARDHAT, None, None).unwrap();
+        keyring.lock();
+        assert!(keyring.check_idle());
+        assert_locked(keyring.account_xpub());
+        assert_locked(keyring.child(1));
+        assert_locked(keyring.children_range(1, 2));
+        assert_locked(keyring.sign_hash(1, &[0; 32]));
+        assert_locked(keyring.sign_message(1, b"hello"));
+        assert_locked(keyring.export_keystore(1, "password", Kdf::Pbkdf2 { iterations: 1 }));
+    }
+
+    #[test]
+    fn no_timeout_never_locks() {
+        let mut keyring = Keyring::unlock(HARDHAT, None, Some(Duration::from_millis(50))).unwrap();
+        keyring.set_idle_timeout(None);
+        sleep(Duration::from_millis(120));
+        assert!(!keyring.check_idle());
+        assert_eq!(keyring.child(1).unwrap().address, HARDHAT_CHILD_1);
+    }
+
+    #[test]
+    fn children_range_stays_non_hardened() {
+        let mut keyring = Keyring::unlock(HARDHAT, None, None).unwrap();
+        let children = keyring.children_range(1, 2).unwrap();
+        assert_eq!(children.iter().map(|child| child.index).collect::<Vec<_>>(), [1, 2]);
+        assert!(keyring.children_range((1 << 31) - 1, 2).is_err());
+        assert!(keyring.children_range(u32::MAX, 1).is_err());
+    }
+
+    #[test]
+    fn message_signatures_recover_to_the_child_address() {
+        use secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
+        use secp256k1::{Message, Secp256k1};
+
+        let mut keyring = Keyring::unlock(HARDHAT, None, None).unwrap();
+        let message = b"dwss keyring test";
+        for index in [1, 7] {
+            let signature = keyring.sign_message(index, message).unwrap();
+            let mut compact = [0u8; 64];
+            compact[..32].copy_from_slice(&signature.r);
+            compact[32..].copy_from_slice(&signature.s);
+            let recoverable = RecoverableSignature::from_compact(
+                &compact,
+                RecoveryId::from_i32(i32::from(signature.recovery_id)).unwrap(),
+            )
+            .unwrap();
+            let digest = Message::from_slice(&signing::personal_message_hash(message)).unwrap();
+            let signer = Secp256k1::verification_only().recover_ecdsa(&digest, &recoverable).unwrap();
+            assert_eq!(utils::public_key_to_address(&signer), keyring.child(index).unwrap().address);
+        }
+        assert_eq!(keyring.child(1).unwrap().address, HARDHAT_CHILD_1);
+    }
+}
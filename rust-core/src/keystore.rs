@@ -0,0 +1,426 @@
+use crate::error::Error;
+use crate::secret::PrivateKey;
+use crate::utils;
+use aes::cipher::{KeyIvInit, StreamCipher};
+use rand::RngCore;
+use salsa20::cipher::typenum::U4;
+use salsa20::cipher::StreamCipherCore;
+use salsa20::SalsaCore;
+use secp256k1::{PublicKey, Secp256k1};
+use serde::{Serialize, Deserialize};
+use sha2::Sha256;
+use std::str::FromStr;
+use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
+
+type Aes128Ctr = ctr::Ctr128BE<aes::Aes128>;
+
+const VERSION: u32 = 3;
+const CIPHER: &str = "aes-128-ctr";
+const PRF: &str = "hmac-sha256";
+const DERIVED_KEY_LENGTH: usize = 32;
+// Keystore parameters come from untrusted JSON, so scrypt's memory and work
+// and the PBKDF2 iteration count are capped before anything is allocated.
+// The memory cap fits geth's standard scrypt (n = 2^18, r = 8, p = 1, which
+// needs 256 MiB) with 1 MiB to spare, as that is all a wasm32 or mobile host
+// can be asked for. The work cap is twice the standard, and PBKDF2 allows
+// about 38 times the count in the spec's test vector.
+const MAX_SCRYPT_MEMORY: u64 = 257 * 1024 * 1024;
+const MAX_SCRYPT_WORK: u64 = 1 << 22;
+const MAX_PBKDF2_ITERATIONS: u32 = 10_000_000;
+
+/// Key derivation settings for new keystores.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kdf {
+    Scrypt { log_n: u8, r: u32, p: u32 },
+    Pbkdf2 { iterations: u32 },
+}
+
+impl Kdf {
+    /// Geth's default: n = 2^18, r = 8, p = 1. Needs 256 MiB of memory.
+    pub const SCRYPT_STANDARD: Kdf = Kdf::Scrypt { log_n: 18, r: 8, p: 1 };
+    /// Geth's `--lightkdf`: n = 2^12, r = 8, p = 6. Fits comfortably on a phone.
+    pub const SCRYPT_LIGHT: Kdf = Kdf::Scrypt { log_n: 12, r: 8, p: 6 };
+    pub const PBKDF2: Kdf = Kdf::Pbkdf2 { iterations: 262_144 };
+
+    /// `"scrypt"`, `"scrypt-light"` or `"pbkdf2"`.
+    pub fn from_name(name: &str) -> Result<Self, Error> {
+        match name.trim().to_lowercase().as_str() {
+            "scrypt" => Ok(Kdf::SCRYPT_STANDARD),
+            "scrypt-light" => Ok(Kdf::SCRYPT_LIGHT),
+            "pbkdf2" => Ok(Kdf::PBKDF2),
+            other => Err(Error::invalid_argument("kdf", format!("unknown kdf {}", other))),
+        }
+    }
+}
+
+/// A Web3 Secret Storage (V3) keystore, as written by Geth and MetaMask.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Keystore {
+    pub version: u32,
+    pub id: String,
+    /// Lowercase hex without `0x`; optional in the spec.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    /// Some older wallets write `Crypto`.
+    #[serde(alias = "Crypto")]
+    pub crypto: KeystoreCrypto,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct KeystoreCrypto {
+    pub cipher: String,
+    pub cipherparams: CipherParams,
+    pub ciphertext: String,
+    pub kdf: String,
+    pub kdfparams: KdfParams,
+    pub mac: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CipherParams {
+    pub iv: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum KdfParams {
+    Scrypt { dklen: usize, n: u64, r: u32, p: u32, salt: String },
+    Pbkdf2 { c: u32, dklen: usize, prf: String, salt: String },
+}
+
+impl Keystore {
+    pub fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string(self).map_err(|e| Error::SerializationFailed { reason: e.to_string() })
+    }
+}
+
+impl FromStr for Keystore {
+    type Err = Error;
+
+    fn from_str(json: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(json).map_err(invalid)
+    }
+}
+
+fn invalid(reason: impl std::fmt::Display) -> Error {
+    Error::InvalidKeystore { reason: reason.to_string() }
+}
+
+fn decode_hex(field: &str, value: &str) -> Result<Vec<u8>, Error> {
+    hex::decode(value.trim_start_matches("0x")).map_err(|e| invalid(format!("{} is not hex: {}", field, e)))
+}
+
+fn derive_key(params: &KdfParams, password: &str) -> Result<Zeroizing<[u8; DERIVED_KEY_LENGTH]>, Error> {
+    let mut derived = Zeroizing::new([0u8; DERIVED_KEY_LENGTH]);
+    match params {
+        KdfParams::Scrypt { dklen, n, r, p, salt } => {
+            if *dklen != DERIVED_KEY_LENGTH {
+                return Err(invalid(format!("unsupported dklen {}", dklen)));
+            }
+            scrypt(password.as_bytes(), &decode_hex("salt", salt)?, *n, *r, *p, &mut derived[..])?;
+        }
+        KdfParams::Pbkdf2 { c, dklen, prf, salt } => {
+            if *dklen != DERIVED_KEY_LENGTH {
+                return Err(invalid(format!("unsupported dklen {}", dklen)));
+            }
+            if prf != PRF {
+                return Err(invalid(format!("unsupported prf {}", prf)));
+            }
+            if *c == 0 || *c > MAX_PBKDF2_ITERATIONS {
+                return Err(invalid(format!("unsupported pbkdf2 iteration count {}", c)));
+            }
+            pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), &decode_hex("salt", salt)?, *c, &mut derived[..]);
+        }
+    }
+    Ok(derived)
+}
+
+/// scrypt with the limits geth applies. The `scrypt` crate also enforces
+/// RFC 7914's n < 2^(16r), which rejects keystores geth reads, such as the
+/// Web3 Secret Storage test vector (n = 2^18, r = 1, p = 8). On top of that,
+/// memory (128·r·n + 128·r·p bytes) and work (n·r·p) are capped.
+fn scrypt(password: &[u8], salt: &[u8], n: u64, r: u32, p: u32, output: &mut [u8]) -> Result<(), Error> {
+    if !n.is_power_of_two() || n < 2 {
+        return Err(invalid(format!("scrypt n {} is not a power of two", n)));
+    }
+    let unsupported = || invalid(format!("unsupported scrypt parameters n={} r={} p={}", n, r, p));
+    if r == 0 || p == 0 || u64::from(r) * u64::from(p) >= 1 << 30 {
+        return Err(unsupported());
+    }
+    let block_len = 128 * u64::from(r);
+    let v_len = block_len.checked_mul(n).ok_or_else(unsupported)?;
+    let b_len = block_len * u64::from(p);
+    let memory = v_len.checked_add(b_len).ok_or_else(unsupported)?;
+    let work = n.checked_mul(u64::from(r) * u64::from(p)).ok_or_else(unsupported)?;
+    if memory > MAX_SCRYPT_MEMORY || work > MAX_SCRYPT_WORK {
+        return Err(unsupported());
+    }
+    // All three fit in usize on wasm32 once under the memory cap.
+    let to_usize = |value: u64| usize::try_from(value).map_err(|_| unsupported());
+    let (block_len, v_len, b_len, n) = (to_usize(block_len)?, to_usize(v_len)?, to_usize(b_len)?, to_usize(n)?);
+
+    let mut b = Zeroizing::new(vec![0u8; b_len]);
+    pbkdf2::pbkdf2_hmac::<Sha256>(password, salt, 1, &mut b);
+    let mut v = Zeroizing::new(vec![0u8; v_len]);
+    let mut t = Zeroizing::new(vec![0u8; block_len]);
+    for block in b.chunks_mut(block_len) {
+        ro_mix(block, &mut v, &mut t, n);
+    }
+    pbkdf2::pbkdf2_hmac::<Sha256>(password, &b, 1, output);
+    Ok(())
+}
+
+/// scryptROMix from RFC 7914, in place on `b`.
+fn ro_mix(b: &mut [u8], v: &mut [u8], t: &mut [u8], n: usize) {
+    let len = b.len();
+    for chunk in v.chunks_mut(len) {
+        chunk.copy_from_slice(b);
+        block_mix(chunk, b);
+    }
+    for _ in 0..n {
+        // Integerify: the last 64-byte block as a little-endian integer, mod n.
+        let j = u64::from_le_bytes(b[len - 64..len - 56].try_into().expect("8 bytes")) as usize & (n - 1);
+        for ((t, b), v) in t.iter_mut().zip(b.iter()).zip(&v[j * len..(j + 1) * len]) {
+            *t = b ^ v;
+        }
+        block_mix(t, b);
+    }
+}
+
+/// scryptBlockMix with Salsa20/8, from `input` into `output`.
+fn block_mix(input: &[u8], output: &mut [u8]) {
+    let half = input.len() / 2;
+    let mut x = [0u8; 64];
+    x.copy_from_slice(&input[input.len() - 64..]);
+    for (i, chunk) in input.chunks(64).enumerate() {
+        let mut state = [0u32; 16];
+        for ((word, x), chunk) in state.iter_mut().zip(x.chunks_exact(4)).zip(chunk.chunks_exact(4)) {
+            *word = u32::from_le_bytes([x[0] ^ chunk[0], x[1] ^ chunk[1], x[2] ^ chunk[2], x[3] ^ chunk[3]]);
+        }
+        SalsaCore::<U4>::from_raw_state(state).write_keystream_block((&mut x).into());
+        // Even blocks fill the first half of the output, odd blocks the second.
+        let offset = (i / 2) * 64 + if i % 2 == 0 { 0 } else { half };
+        output[offset..offset + 64].copy_from_slice(&x);
+    }
+}
+
+/// keccak256(derived[16..32] || ciphertext)
+fn mac(derived: &[u8; DERIVED_KEY_LENGTH], ciphertext: &[u8]) -> [u8; 32] {
+    let mut data = Vec::with_capacity(16 + ciphertext.len());
+    data.extend_from_slice(&derived[16..]);
+    data.extend_from_slice(ciphertext);
+    utils::keccak256(&data)
+}
+
+/// Random version 4 UUID.
+fn uuid_v4() -> String {
+    let mut bytes = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = hex::encode(bytes);
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+fn address_of(key: &PrivateKey) -> String {
+    let public_key = PublicKey::from_secret_key(&Secp256k1::signing_only(), &key.to_secret_key());
+    utils::public_key_to_address(&public_key)
+}
+
+pub fn encrypt_key(key: &PrivateKey, password: &str, kdf: Kdf) -> Result<Keystore, Error> {
+    let mut rng = rand::thread_rng();
+    let mut salt = [0u8; 32];
+    let mut iv = [0u8; 16];
+    rng.fill_bytes(&mut salt);
+    rng.fill_bytes(&mut iv);
+
+    let (kdf_name, kdfparams) = match kdf {
+        Kdf::Scrypt { log_n, r, p } => ("scrypt", KdfParams::Scrypt {
+            dklen: DERIVED_KEY_LENGTH,
+            n: 1u64.checked_shl(u32::from(log_n)).ok_or_else(|| Error::invalid_argument("kdf", "scrypt log_n is too large"))?,
+            r,
+            p,
+            salt: hex::encode(salt),
+        }),
+        Kdf::Pbkdf2 { iterations } => ("pbkdf2", KdfParams::Pbkdf2 {
+            c: iterations,
+            dklen: DERIVED_KEY_LENGTH,
+            prf: PRF.to_string(),
+            salt: hex::encode(salt),
+        }),
+    };
+    let derived = derive_key(&kdfparams, password)?;
+
+    let mut ciphertext = key.as_bytes().to_vec();
+    Aes128Ctr::new(derived[..16].into(), &iv.into()).apply_keystream(&mut ciphertext);
+
+    Ok(Keystore {
+        version: VERSION,
+        id: uuid_v4(),
+        address: Some(address_of(key)[2..].to_lowercase()),
+        crypto: KeystoreCrypto {
+            cipher: CIPHER.to_string(),
+            cipherparams: CipherParams { iv: hex::encode(iv) },
+            ciphertext: hex::encode(&ciphertext),
+            kdf: kdf_name.to_string(),
+            kdfparams,
+            mac: hex::encode(mac(&derived, &ciphertext)),
+        },
+    })
+}
+
+/// Decrypts a keystore. A wrong password shows up as a MAC mismatch and is
+/// reported as `InvalidPassword`.
+pub fn decrypt_key(keystore: &Keystore, password: &str) -> Result<PrivateKey, Error> {
+    if keystore.version != VERSION {
+        return Err(invalid(format!("unsupported version {}", keystore.version)));
+    }
+    let crypto = &keystore.crypto;
+    if crypto.cipher != CIPHER {
+        return Err(invalid(format!("unsupported cipher {}", crypto.cipher)));
+    }
+    let kdf_matches = matches!(
+        (crypto.kdf.as_str(), &crypto.kdfparams),
+        ("scrypt", KdfParams::Scrypt { .. }) | ("pbkdf2", KdfParams::Pbkdf2 { .. })
+    );
+    if !kdf_matches {
+        return Err(invalid(format!("kdf {} does not match its parameters", crypto.kdf)));
+    }
+
+    let iv: [u8; 16] = decode_hex("iv", &crypto.cipherparams.iv)?
+        .try_into()
+        .map_err(|_| invalid("iv must be 16 bytes"))?;
+    let ciphertext = decode_hex("ciphertext", &crypto.ciphertext)?;
+    let expected_mac = decode_hex("mac", &crypto.mac)?;
+
+    let derived = derive_key(&crypto.kdfparams, password)?;
+    if !bool::from(mac(&derived, &ciphertext)[..].ct_eq(&expected_mac[..])) {
+        return Err(Error::InvalidPassword);
+    }
+
+    let mut plaintext = Zeroizing::new(ciphertext);
+    Aes128Ctr::new(derived[..16].into(), &iv.into()).apply_keystream(&mut plaintext);
+    let key = PrivateKey::from_slice(&plaintext)
+        .map_err(|_| invalid("decrypted data is not a valid private key"))?;
+
+    if let Some(address) = &keystore.address {
+        if !address_of(&key)[2..].eq_ignore_ascii_case(address.trim_start_matches("0x")) {
+            return Err(invalid("decrypted key does not match the keystore address"));
+        }
+    }
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PASSWORD: &str = "testpassword";
+    const PRIVATE_KEY: &str = "7a28b5ba57c53603b0b07b56bba752f7784bf506fa95edc395f5cf6c7514fe9d";
+
+    // The test vectors from the Web3 Secret Storage Definition.
+    const PBKDF2_VECTOR: &str = r#"{
+        "crypto": {
+            "cipher": "aes-128-ctr",
+            "cipherparams": { "iv": "6087dab2f9fdbbfaddc31a909735c1e6" },
+            "ciphertext": "5318b4d5bcd28de64ee5559e671353e16f075ecae9f99c7a79a38af5f869aa46",
+            "kdf": "pbkdf2",
+            "kdfparams": {
+                "c": 262144,
+                "dklen": 32,
+                "prf": "hmac-sha256",
+                "salt": "ae3cd4e7013836a3df6bd7241b12db061dbe2c6785853cce422d148a624ce0bd"
+            },
+            "mac": "517ead924a9d0dc3124507e3393d175ce3ff7c1e96529c6c555ce9e51205e9b2"
+        },
+        "id": "3198bc9c-6672-5ab3-d995-4942343ae5b6",
+        "version": 3
+    }"#;
+
+    const SCRYPT_VECTOR: &str = r#"{
+        "crypto": {
+            "cipher": "aes-128-ctr",
+            "cipherparams": { "iv": "83dbcc02d8ccb40e466191a123791e0e" },
+            "ciphertext": "d172bf743a674da9cdad04534d56926ef8358534d458fffccd4e6ad2fbde479c",
+            "kdf": "scrypt",
+            "kdfparams": {
+                "dklen": 32,
+                "n": 262144,
+                "p": 8,
+                "r": 1,
+                "salt": "ab0c7876052600dd703518d6fc3fe8984592145b591fc8fb5c6d43190334ba19"
+            },
+            "mac": "2103ac29920d71da29f15d75b4a16dbe95cfd7ff8faea1056c33131d846e3097"
+        },
+        "id": "3198bc9c-6672-5ab3-d995-4942343ae5b6",
+        "version": 3
+    }"#;
+
+    fn decrypt(json: &str, password: &str) -> Result<PrivateKey, Error> {
+        decrypt_key(&json.parse().unwrap(), password)
+    }
+
+    #[test]
+    fn decrypts_the_pbkdf2_vector() {
+        assert_eq!(hex::encode(decrypt(PBKDF2_VECTOR, PASSWORD).unwrap().as_bytes()), PRIVATE_KEY);
+    }
+
+    #[test]
+    fn decrypts_the_scrypt_vector() {
+        // n = 2^18 with r = 1 is outside RFC 7914's n < 2^(16r).
+        assert_eq!(hex::encode(decrypt(SCRYPT_VECTOR, PASSWORD).unwrap().as_bytes()), PRIVATE_KEY);
+    }
+
+    #[test]
+    fn scrypt_matches_rfc_7914() {
+        let mut output = [0u8; 64];
+        scrypt(b"password", b"NaCl", 1024, 8, 16, &mut output).unwrap();
+        assert_eq!(
+            hex::encode(output),
+            "fdbabe1c9d3472007856e7190d01e9fe7c6ad7cbc8237830e77376634b3731622eaf30d92e22a3886ff109279d9830dac727afb94a83ee6d8360cbdfa2cc0640"
+        );
+    }
+
+    #[test]
+    fn wrong_password_is_reported_as_such() {
+        assert!(matches!(decrypt(PBKDF2_VECTOR, "wrongpassword"), Err(Error::InvalidPassword)));
+    }
+
+    #[test]
+    fn round_trips_with_each_kdf() {
+        let key = PrivateKey::from_hex(PRIVATE_KEY).unwrap();
+        for kdf in [Kdf::Scrypt { log_n: 10, r: 8, p: 1 }, Kdf::Pbkdf2 { iterations: 1024 }] {
+            let keystore = encrypt_key(&key, PASSWORD, kdf).unwrap();
+            assert_eq!(keystore.address.as_deref(), Some("008aeeda4d805471df9b2a5b0f38a0c3bcba786b"));
+            let json = keystore.to_json().unwrap();
+            assert_eq!(decrypt(&json, PASSWORD).unwrap().as_bytes(), key.as_bytes());
+        }
+    }
+
+    #[test]
+    fn rejects_bad_scrypt_parameters() {
+        let mut output = [0u8; 32];
+        assert!(scrypt(b"", b"", 1000, 8, 1, &mut output).is_err());
+        assert!(scrypt(b"", b"", 1, 8, 1, &mut output).is_err());
+        assert!(scrypt(b"", b"", 1024, 0, 1, &mut output).is_err());
+        assert!(scrypt(b"", b"", 1024, 1 << 15, 1 << 15, &mut output).is_err());
+    }
+
+    #[test]
+    fn rejects_oversized_kdf_parameters() {
+        // 2^40 · 128 · 8 bytes would abort on allocation if it got that far.
+        let oversized = SCRYPT_VECTOR.replace(r#""n": 262144"#, r#""n": 1099511627776"#).replace(r#""r": 1"#, r#""r": 8"#);
+        assert!(matches!(decrypt(&oversized, PASSWORD), Err(Error::InvalidKeystore { .. })));
+        // Small enough to allocate, but p = 2^20 blocks of n = 2^10 would take hours.
+        let slow = SCRYPT_VECTOR.replace(r#""n": 262144"#, r#""n": 1024"#).replace(r#""p": 8"#, r#""p": 1048576"#);
+        assert!(matches!(decrypt(&slow, PASSWORD), Err(Error::InvalidKeystore { .. })));
+        let pbkdf2 = PBKDF2_VECTOR.replace(r#""c": 262144"#, r#""c": 4000000000"#);
+        assert!(matches!(decrypt(&pbkdf2, PASSWORD), Err(Error::InvalidKeystore { .. })));
+
+        let mut output = [0u8; 32];
+        // One step past geth's standard parameters doubles the memory.
+        assert!(scrypt(b"", b"", 1 << 19, 8, 1, &mut output).is_err());
+        assert!(scrypt(b"", b"", 1 << 62, 1 << 20, 1, &mut output).is_err());
+    }
+}
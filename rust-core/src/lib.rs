@@ -1,65 +1,66 @@
-use wasm_bindgen::prelude::*;
 use serde::{Serialize, Deserialize};
 
-mod bip39;
-mod hd_wallet;
-mod utils;
+pub mod address;
+pub mod bip39;
+pub mod bip85;
+pub mod compat;
+pub mod derivation_path;
+pub mod distribution;
+pub mod entropy;
+pub mod error;
+pub mod extended_key;
+pub mod hd_wallet;
+pub mod keyring;
+pub mod keystore;
+pub mod recovery;
+pub mod secret;
+pub mod signing;
+pub mod slip39;
+pub mod split_plan;
+pub mod sweep;
+pub mod transaction;
+pub mod utils;
+pub mod vault;
+#[cfg(feature = "wasm")]
+mod wasm;
+pub mod watch_only;
+
+pub use error::Error;
+/// Wordlist languages, as taken by the mnemonic and BIP-85 functions.
+pub use ::bip39::Language;
 
 #[derive(Serialize, Deserialize)]
 pub struct WalletInfo {
     pub address: String,
-    pub private_key: String,
+    #[serde(with = "crate::secret::exposed")]
+    pub private_key: secret::PrivateKey,
     pub public_key: String,
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct SplitResult {
-    pub parent_wallet: WalletInfo,
-    pub child_wallets: Vec<WalletInfo>,
-}
-
-#[wasm_bindgen]
-pub fn generate_mnemonic() -> Result<String, JsValue> {
-    bip39::generate_mnemonic()
-        .map_err(|e| JsValue::from_str(&e.to_string()))
+impl WalletInfo {
+    /// Address and public key for a standalone key, e.g. one read from a keystore.
+    pub fn from_private_key(private_key: secret::PrivateKey) -> Self {
+        let secp = secp256k1::Secp256k1::signing_only();
+        let public_key = secp256k1::PublicKey::from_secret_key(&secp, &private_key.to_secret_key());
+        WalletInfo {
+            address: utils::public_key_to_address(&public_key),
+            private_key,
+            public_key: hex::encode(public_key.serialize()),
+        }
+    }
 }
 
-#[wasm_bindgen]
-pub fn derive_parent_wallet(mnemonic: &str) -> Result<JsValue, JsValue> {
-    let wallet = hd_wallet::derive_parent_wallet(mnemonic)
-        .map_err(|e| JsValue::from_str(&e.to_string()))?;
-    
-    serde_wasm_bindgen::to_value(&wallet)
-        .map_err(|e| JsValue::from_str(&e.to_string()))
-}
+/// Number of children in a split when the caller does not choose one.
+pub const DEFAULT_SPLIT_CHILD_COUNT: u32 = 100;
 
-#[wasm_bindgen]
-pub fn derive_child_wallets(mnemonic: &str, count: u32) -> Result<JsValue, JsValue> {
-    let wallets = hd_wallet::derive_child_wallets(mnemonic, count)
-        .map_err(|e| JsValue::from_str(&e.to_string()))?;
-    
-    serde_wasm_bindgen::to_value(&wallets)
-        .map_err(|e| JsValue::from_str(&e.to_string()))
-}
+/// First child a distribution funds or a sweep empties when the caller does
+/// not choose one; child 0 is the parent itself.
+pub const FIRST_SPLIT_CHILD: u32 = 1;
 
-#[wasm_bindgen]
-pub fn create_split_operation(mnemonic: &str) -> Result<JsValue, JsValue> {
-    let parent = hd_wallet::derive_parent_wallet(mnemonic)
-        .map_err(|e| JsValue::from_str(&e.to_string()))?;
-    
-    let children = hd_wallet::derive_child_wallets(mnemonic, 100)
-        .map_err(|e| JsValue::from_str(&e.to_string()))?;
-    
-    let result = SplitResult {
-        parent_wallet: parent,
-        child_wallets: children,
-    };
-    
-    serde_wasm_bindgen::to_value(&result)
-        .map_err(|e| JsValue::from_str(&e.to_string()))
+#[derive(Serialize, Deserialize)]
+pub struct SplitResult {
+    pub parent_wallet: WalletInfo,
+    pub child_wallets: Vec<WalletInfo>,
+    /// Index of `child_wallets[0]`; children are consecutive from there.
+    pub child_start_index: u32,
 }
-
-#[wasm_bindgen]
-pub fn validate_mnemonic(mnemonic: &str) -> bool {
-    bip39::validate_mnemonic(mnemonic)
-} 
\ No newline at end of file
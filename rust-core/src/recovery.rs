@@ -0,0 +1,307 @@
+use crate::{address, bip39, hd_wallet, utils};
+use crate::error::Error;
+use ::bip39::Language;
+use serde::{Serialize, Deserialize};
+use zeroize::{Zeroize, Zeroizing};
+
+/// More than two missing words means 2048^3 combinations, which is out of
+/// reach on a phone.
+pub const MAX_UNKNOWN_POSITIONS: usize = 2;
+pub const DEFAULT_MAX_CANDIDATES: usize = 50_000;
+const WORDLIST_SIZE: u64 = 2048;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RecoveryProgress {
+    /// Combinations tried so far.
+    pub checked: u64,
+    pub total: u64,
+    pub candidates_found: usize,
+    pub matches_found: usize,
+    /// True once the candidate cap was hit; later candidates are dropped.
+    pub truncated: bool,
+    pub done: bool,
+    pub cancelled: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RecoveryResult {
+    pub progress: RecoveryProgress,
+    pub unknown_positions: Vec<usize>,
+    /// Checksum-valid phrases, in search order.
+    pub candidates: Vec<String>,
+    /// Candidates whose parent address equals the target address.
+    pub matches: Vec<String>,
+}
+
+/// Incremental search over the missing positions of a damaged mnemonic.
+///
+/// The search runs in slices through [`RecoverySearch::step`], so a WASM
+/// caller can yield to the event loop between slices, show progress and stop
+/// at any time with [`RecoverySearch::cancel`].
+pub struct RecoverySearch {
+    language: Language,
+    indices: Vec<u16>,
+    unknown_positions: Vec<usize>,
+    target_address: Option<[u8; 20]>,
+    passphrase: Option<Zeroizing<String>>,
+    max_candidates: usize,
+    next: u64,
+    total: u64,
+    candidates: Vec<String>,
+    matches: Vec<String>,
+    truncated: bool,
+    cancelled: bool,
+}
+
+impl RecoverySearch {
+    /// Missing words can be written as `?` or any other non-word. Every word
+    /// outside the wordlist is searched, as are `suspect_positions` (zero-based).
+    /// A malformed `target_address` is rejected up front rather than left to
+    /// exhaust the search without a match.
+    pub fn new(
+        mnemonic: &str,
+        suspect_positions: &[usize],
+        target_address: Option<&str>,
+        passphrase: Option<&str>,
+        max_candidates: Option<usize>,
+    ) -> Result<Self, Error> {
+        let normalized = bip39::normalize_phrase(mnemonic);
+        let words: Vec<&str> = normalized.split_whitespace().collect();
+        if !bip39::SUPPORTED_WORD_COUNTS.contains(&words.len()) {
+            return Err(Error::InvalidWordCount { word_count: words.len() });
+        }
+        let language = bip39::best_matching_language(&words);
+
+        let mut indices = Vec::with_capacity(words.len());
+        let mut unknown_positions = Vec::new();
+        for (position, word) in words.iter().enumerate() {
+            if suspect_positions.contains(&position) {
+                unknown_positions.push(position);
+                indices.push(0);
+                continue;
+            }
+            match language.find_word(word) {
+                Some(index) => indices.push(index),
+                None => {
+                    unknown_positions.push(position);
+                    indices.push(0);
+                }
+            }
+        }
+        if let Some(position) = suspect_positions.iter().find(|p| **p >= words.len()) {
+            return Err(Error::invalid_argument(
+                "suspect position",
+                format!("{} is outside a {}-word mnemonic", position, words.len()),
+            ));
+        }
+        let target_address = target_address
+            .map(|target| address::parse_address(target).map_err(|e| Error::invalid_argument("target address", e)))
+            .transpose()?;
+        if unknown_positions.is_empty() {
+            return Err(Error::invalid_argument("suspect positions", "no missing or suspect positions to recover"));
+        }
+        if unknown_positions.len() > MAX_UNKNOWN_POSITIONS {
+            return Err(Error::invalid_argument(
+                "suspect positions",
+                format!("{} unknown positions, at most {} can be recovered", unknown_positions.len(), MAX_UNKNOWN_POSITIONS),
+            ));
+        }
+
+        Ok(RecoverySearch {
+            language,
+            indices,
+            total: WORDLIST_SIZE.pow(unknown_positions.len() as u32),
+            unknown_positions,
+            target_address,
+            passphrase: passphrase.map(|p| Zeroizing::new(p.to_string())),
+            max_candidates: max_candidates.unwrap_or(DEFAULT_MAX_CANDIDATES),
+            next: 0,
+            candidates: Vec::new(),
+            matches: Vec::new(),
+            truncated: false,
+            cancelled: false,
+        })
+    }
+
+    /// Tries up to `budget` combinations and reports where the search stands.
+    ///
+    /// Checksum filtering is cheap; each checksum-valid candidate costs a full
+    /// seed derivation when a target address is set, so keep budgets small then.
+    pub fn step(&mut self, budget: u64) -> Result<RecoveryProgress, Error> {
+        let end = self.total.min(self.next.saturating_add(budget));
+        while self.next < end && !self.cancelled {
+            let mut rest = self.next;
+            for position in &self.unknown_positions {
+                self.indices[*position] = (rest % WORDLIST_SIZE) as u16;
+                rest /= WORDLIST_SIZE;
+            }
+            self.next += 1;
+
+            if !checksum_matches(&self.indices) {
+                continue;
+            }
+            let phrase = self.phrase();
+            if let Some(target) = &self.target_address {
+                let parent = hd_wallet::derive_parent_wallet(&phrase, self.passphrase.as_deref().map(String::as_str))?;
+                if address::parse_address(&parent.address)? == *target {
+                    self.matches.push(phrase.clone());
+                }
+            }
+            if self.candidates.len() < self.max_candidates {
+                self.candidates.push(phrase);
+            } else {
+                self.truncated = true;
+            }
+        }
+        Ok(self.progress())
+    }
+
+    pub fn cancel(&mut self) {
+        self.cancelled = true;
+    }
+
+    pub fn progress(&self) -> RecoveryProgress {
+        RecoveryProgress {
+            checked: self.next,
+            total: self.total,
+            candidates_found: self.candidates.len(),
+            matches_found: self.matches.len(),
+            truncated: self.truncated,
+            done: self.next >= self.total,
+            cancelled: self.cancelled,
+        }
+    }
+
+    pub fn result(&self) -> RecoveryResult {
+        RecoveryResult {
+            progress: self.progress(),
+            unknown_positions: self.unknown_positions.clone(),
+            candidates: self.candidates.clone(),
+            matches: self.matches.clone(),
+        }
+    }
+
+    fn phrase(&self) -> String {
+        let word_list = self.language.word_list();
+        self.indices
+            .iter()
+            .map(|index| word_list[*index as usize])
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Partial phrases and candidates are as sensitive as the mnemonic itself.
+impl Drop for RecoverySearch {
+    fn drop(&mut self) {
+        self.indices.zeroize();
+        self.candidates.zeroize();
+        self.matches.zeroize();
+    }
+}
+
+/// BIP39 checksum check straight on word indices, avoiding a string round trip.
+fn checksum_matches(indices: &[u16]) -> bool {
+    let entropy_bits = indices.len() * 11 * 32 / 33;
+    let checksum_bits = entropy_bits / 32;
+
+    let mut entropy = [0u8; 33];
+    for bit in 0..entropy_bits + checksum_bits {
+        let index = indices[bit / 11];
+        if index >> (10 - bit % 11) & 1 == 1 {
+            entropy[bit / 8] |= 1 << (7 - bit % 8);
+        }
+    }
+
+    let hash = utils::sha256(&entropy[..entropy_bits / 8]);
+    let expected = hash[0] >> (8 - checksum_bits);
+    let actual = entropy[entropy_bits / 8] >> (8 - checksum_bits);
+    expected == actual
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HARDHAT: &str = "test test test test test test test test test test test junk";
+    const HARDHAT_PARENT: &str = "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266";
+    const MISSING_LAST: &str = "test test test test test test test test test test test ?";
+
+    #[test]
+    fn finds_one_missing_word_by_address() {
+        let mut search = RecoverySearch::new(MISSING_LAST, &[], Some(HARDHAT_PARENT), None, None).unwrap();
+        let progress = search.step(WORDLIST_SIZE).unwrap();
+        assert!(progress.done);
+        assert_eq!(progress.checked, 2048);
+        assert_eq!(progress.total, 2048);
+        // 4 checksum bits leave 2048 / 16 candidates for the last word.
+        assert_eq!(progress.candidates_found, 128);
+        assert_eq!(progress.matches_found, 1);
+        assert_eq!(search.result().matches, vec![HARDHAT.to_string()]);
+        assert_eq!(search.result().unknown_positions, vec![11]);
+    }
+
+    #[test]
+    fn suspect_position_is_searched() {
+        let search = RecoverySearch::new(HARDHAT, &[4], None, None, None).unwrap();
+        assert_eq!(search.result().unknown_positions, vec![4]);
+    }
+
+    #[test]
+    fn stops_when_the_budget_runs_out() {
+        let mut search = RecoverySearch::new(MISSING_LAST, &[], None, None, None).unwrap();
+        let progress = search.step(100).unwrap();
+        assert_eq!(progress.checked, 100);
+        assert!(!progress.done);
+
+        let progress = search.step(u64::MAX).unwrap();
+        assert_eq!(progress.checked, 2048);
+        assert!(progress.done);
+        assert_eq!(progress.candidates_found, 128);
+        assert!(search.result().candidates.contains(&HARDHAT.to_string()));
+    }
+
+    #[test]
+    fn candidate_cap_truncates() {
+        let mut search = RecoverySearch::new(MISSING_LAST, &[], None, None, Some(3)).unwrap();
+        let progress = search.step(u64::MAX).unwrap();
+        assert!(progress.done);
+        assert!(progress.truncated);
+        assert_eq!(progress.candidates_found, 3);
+    }
+
+    #[test]
+    fn cancel_stops_the_search() {
+        let mut search = RecoverySearch::new(MISSING_LAST, &[], None, None, None).unwrap();
+        search.step(10).unwrap();
+        search.cancel();
+        let progress = search.step(u64::MAX).unwrap();
+        assert!(progress.cancelled);
+        assert!(!progress.done);
+        assert_eq!(progress.checked, 10);
+    }
+
+    #[test]
+    fn rejects_more_than_two_unknown_positions() {
+        let error = RecoverySearch::new("test test test test test test test test test ? ? ?", &[], None, None, None)
+            .err()
+            .unwrap();
+        assert!(matches!(error, Error::InvalidArgument { ref argument, .. } if argument == "suspect positions"));
+    }
+
+    #[test]
+    fn rejects_nothing_to_recover_and_bad_positions() {
+        assert!(RecoverySearch::new(HARDHAT, &[], None, None, None).is_err());
+        assert!(RecoverySearch::new(HARDHAT, &[12], None, None, None).is_err());
+        for target in ["0xf39Fd6e51aad88F6F4ce6aB8827279cffFb9226", "f39Fd6e51aad88F6F4ce6aB8827279cffFb92266", "0xnot-an-address"] {
+            assert!(matches!(
+                RecoverySearch::new(MISSING_LAST, &[], Some(target), None, None),
+                Err(Error::InvalidArgument { ref argument, .. }) if argument == "target address"
+            ));
+        }
+        assert!(matches!(
+            RecoverySearch::new("test test ?", &[], None, None, None),
+            Err(Error::InvalidWordCount { word_count: 3 })
+        ));
+    }
+}
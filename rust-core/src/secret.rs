@@ -0,0 +1,212 @@
+use crate::error::Error;
+use hdwallet::ExtendedPrivKey;
+use secp256k1::SecretKey;
+use std::fmt;
+use std::ops::Deref;
+use zeroize::{Zeroize, Zeroizing};
+
+/// Overwrites a secp256k1 key in place. `SecretKey` is `Copy`, so only this
+/// copy is wiped; keep keys behind references where possible.
+pub fn erase_secret_key(key: &mut SecretKey) {
+    // SAFETY: `as_mut_ptr` points at the key's `len()` bytes, which we borrow
+    // exclusively. An all-zero key is never used again before the drop.
+    unsafe { std::slice::from_raw_parts_mut(key.as_mut_ptr(), key.len()) }.zeroize();
+}
+
+/// A mnemonic phrase, wiped on drop.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SecretPhrase(Zeroizing<String>);
+
+impl SecretPhrase {
+    pub fn new(phrase: String) -> Self {
+        SecretPhrase(Zeroizing::new(phrase))
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+/// A 64-byte BIP39 seed, wiped on drop.
+#[derive(Clone)]
+pub struct Seed(Zeroizing<[u8; 64]>);
+
+impl Seed {
+    pub fn new(bytes: [u8; 64]) -> Self {
+        Seed(Zeroizing::new(bytes))
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 64] {
+        &self.0
+    }
+}
+
+/// A raw secp256k1 private key, wiped on drop.
+#[derive(Clone, PartialEq, Eq)]
+pub struct PrivateKey(Zeroizing<[u8; 32]>);
+
+impl PrivateKey {
+    pub fn from_secret_key(key: &SecretKey) -> Self {
+        let mut bytes = Zeroizing::new([0u8; 32]);
+        bytes.copy_from_slice(&key[..]);
+        PrivateKey(bytes)
+    }
+
+    /// Accepts exactly 32 bytes forming a valid secp256k1 scalar.
+    pub fn from_slice(slice: &[u8]) -> Result<Self, Error> {
+        SecretKey::from_slice(slice).map_err(|e| Error::invalid_argument("private key", e))?;
+        let mut bytes = Zeroizing::new([0u8; 32]);
+        bytes.copy_from_slice(slice);
+        Ok(PrivateKey(bytes))
+    }
+
+    pub fn from_hex(encoded: &str) -> Result<Self, Error> {
+        let encoded = encoded.trim();
+        let mut bytes = Zeroizing::new([0u8; 32]);
+        hex::decode_to_slice(encoded.strip_prefix("0x").unwrap_or(encoded), &mut bytes[..])
+            .map_err(|e| Error::invalid_argument("private key", e))?;
+        Self::from_slice(&bytes[..])
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// A secp256k1 copy of the key for signing and public key derivation.
+    /// `SecretKey` is `Copy` and does not wipe itself, so keep the copy in a
+    /// short-lived temporary, or pass it to [`erase_secret_key`] once done.
+    pub fn to_secret_key(&self) -> SecretKey {
+        SecretKey::from_slice(&self.0[..]).expect("validated when constructed")
+    }
+
+    pub fn to_hex(&self) -> Zeroizing<String> {
+        Zeroizing::new(hex::encode(&self.0[..]))
+    }
+}
+
+/// An hdwallet `ExtendedPrivKey` that wipes its key and chain code on drop.
+#[derive(Clone)]
+pub struct SecretExtendedKey(ExtendedPrivKey);
+
+impl From<ExtendedPrivKey> for SecretExtendedKey {
+    fn from(key: ExtendedPrivKey) -> Self {
+        SecretExtendedKey(key)
+    }
+}
+
+impl Deref for SecretExtendedKey {
+    type Target = ExtendedPrivKey;
+
+    fn deref(&self) -> &ExtendedPrivKey {
+        &self.0
+    }
+}
+
+impl Drop for SecretExtendedKey {
+    fn drop(&mut self) {
+        erase_secret_key(&mut self.0.private_key);
+        self.0.chain_code.zeroize();
+    }
+}
+
+macro_rules! redacted_debug {
+    ($($name:ident),*) => {$(
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str(concat!(stringify!($name), "(<redacted>)"))
+            }
+        }
+    )*};
+}
+
+redacted_debug!(SecretPhrase, Seed, PrivateKey, SecretExtendedKey);
+
+/// Secrets deliberately have no `Serialize` impl. A field that must carry one
+/// across an API boundary opts in with `#[serde(with = "crate::secret::exposed")]`.
+pub mod exposed {
+    use super::{PrivateKey, SecretPhrase};
+    use crate::error::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use zeroize::Zeroizing;
+
+    pub trait ExposeSecret: Sized {
+        fn expose_string(&self) -> Zeroizing<String>;
+        fn from_exposed(exposed: &str) -> Result<Self, Error>;
+    }
+
+    impl ExposeSecret for SecretPhrase {
+        fn expose_string(&self) -> Zeroizing<String> {
+            self.0.clone()
+        }
+
+        fn from_exposed(exposed: &str) -> Result<Self, Error> {
+            Ok(SecretPhrase::new(exposed.to_string()))
+        }
+    }
+
+    impl ExposeSecret for PrivateKey {
+        fn expose_string(&self) -> Zeroizing<String> {
+            self.to_hex()
+        }
+
+        fn from_exposed(exposed: &str) -> Result<Self, Error> {
+            PrivateKey::from_hex(exposed)
+        }
+    }
+
+    pub fn serialize<T: ExposeSecret, S: Serializer>(secret: &T, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&secret.expose_string())
+    }
+
+    pub fn deserialize<'de, T: ExposeSecret, D: Deserializer<'de>>(deserializer: D) -> Result<T, D::Error> {
+        let exposed = Zeroizing::new(String::deserialize(deserializer)?);
+        T::from_exposed(&exposed).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::WalletInfo;
+
+    const KEY: &str = "4646464646464646464646464646464646464646464646464646464646464646";
+    const PHRASE: &str = "test test test test test test test test test test test junk";
+
+    #[test]
+    fn debug_output_is_redacted() {
+        let key = PrivateKey::from_hex(KEY).unwrap();
+        let extended: SecretExtendedKey = ExtendedPrivKey::with_seed(&[7u8; 64]).unwrap().into();
+        let chain_code = hex::encode(&extended.chain_code);
+        let outputs = [
+            format!("{:?}", SecretPhrase::new(PHRASE.to_string())),
+            format!("{:?}", Seed::new([0x46; 64])),
+            format!("{:?}", key),
+            format!("{:?}", extended),
+        ];
+        for output in &outputs {
+            assert!(output.contains("<redacted>"), "{}", output);
+            assert!(!output.contains("4646") && !output.contains("70, 70"), "{}", output);
+            assert!(!output.contains("test test") && !output.contains(&chain_code), "{}", output);
+        }
+    }
+
+    #[test]
+    fn wallet_info_exposes_the_key_only_through_the_helper() {
+        let info = WalletInfo::from_private_key(PrivateKey::from_hex(KEY).unwrap());
+        let json = serde_json::to_value(&info).unwrap();
+        assert_eq!(json["private_key"], KEY);
+        assert_eq!(json["address"], "0x9d8A62f656a8d1615C1294fd71e9CFb3E4855A4F");
+
+        let back: WalletInfo = serde_json::from_value(json).unwrap();
+        assert_eq!(back.private_key.as_bytes(), info.private_key.as_bytes());
+        let bad = serde_json::json!({ "address": "", "private_key": "00", "public_key": "" });
+        assert!(serde_json::from_value::<WalletInfo>(bad).is_err());
+    }
+
+    #[test]
+    fn erase_secret_key_wipes_the_copy() {
+        let mut key = PrivateKey::from_hex(KEY).unwrap().to_secret_key();
+        erase_secret_key(&mut key);
+        assert!(key[..].iter().all(|b| *b == 0));
+    }
+}
@@ -0,0 +1,87 @@
+use crate::secret::PrivateKey;
+use crate::utils;
+use secp256k1::{Message, Secp256k1};
+use serde::{Serialize, Deserialize};
+
+/// A recoverable secp256k1 signature. `s` is always in the lower half of the
+/// curve order, as Ethereum requires.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Signature {
+    pub r: [u8; 32],
+    pub s: [u8; 32],
+    /// 0 or 1; how it is encoded into `v` depends on what is being signed.
+    pub recovery_id: u8,
+}
+
+/// A signature as handed to JS, with `v` already encoded.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SignatureInfo {
+    /// `r || s || v`, 65 bytes, `0x`-prefixed.
+    pub signature: String,
+    pub r: String,
+    pub s: String,
+    pub v: u64,
+}
+
+impl Signature {
+    /// Encodes `v` as `27 + recovery_id`, as used by `personal_sign`.
+    pub fn to_info(&self) -> SignatureInfo {
+        let v = 27 + u64::from(self.recovery_id);
+        let mut bytes = Vec::with_capacity(65);
+        bytes.extend_from_slice(&self.r);
+        bytes.extend_from_slice(&self.s);
+        bytes.push(v as u8);
+
+        SignatureInfo {
+            signature: format!("0x{}", hex::encode(bytes)),
+            r: format!("0x{}", hex::encode(self.r)),
+            s: format!("0x{}", hex::encode(self.s)),
+            v,
+        }
+    }
+}
+
+pub fn sign_hash(key: &PrivateKey, hash: &[u8; 32]) -> Signature {
+    let secp = Secp256k1::signing_only();
+    let message = Message::from_slice(hash).expect("hash is 32 bytes");
+    let (recovery_id, compact) = secp
+        .sign_ecdsa_recoverable(&message, &key.to_secret_key())
+        .serialize_compact();
+
+    let mut r = [0u8; 32];
+    let mut s = [0u8; 32];
+    r.copy_from_slice(&compact[..32]);
+    s.copy_from_slice(&compact[32..]);
+    Signature { r, s, recovery_id: recovery_id.to_i32() as u8 }
+}
+
+/// EIP-191 hash of a `personal_sign` message.
+pub fn personal_message_hash(message: &[u8]) -> [u8; 32] {
+    let mut data = format!("\x19Ethereum Signed Message:\n{}", message.len()).into_bytes();
+    data.extend_from_slice(message);
+    utils::keccak256(&data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Hardhat account #0 signing "hello world", as produced by ethers and viem.
+    const HARDHAT_KEY_0: &str = "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+
+    #[test]
+    fn personal_sign_matches_a_known_vector() {
+        let hash = personal_message_hash(b"hello world");
+        assert_eq!(hex::encode(hash), "d9eba16ed0ecae432b71fe008c98cc872bb4cc214d3220a36f365326cf807d68");
+
+        let info = sign_hash(&PrivateKey::from_hex(HARDHAT_KEY_0).unwrap(), &hash).to_info();
+        assert_eq!(info.r, "0xa461f509887bd19e312c0c58467ce8ff8e300d3c1a90b608a760c5b80318eaf1");
+        assert_eq!(info.s, "0x5fe57c96f9175d6cd4daad4663763baa7e78836e067d0163e9a2ccf2ff753f5b");
+        assert_eq!(info.v, 27);
+        assert_eq!(
+            info.signature,
+            "0xa461f509887bd19e312c0c58467ce8ff8e300d3c1a90b608a760c5b80318eaf1\
+             5fe57c96f9175d6cd4daad4663763baa7e78836e067d0163e9a2ccf2ff753f5b1b"
+        );
+    }
+}
@@ -0,0 +1,678 @@
+//! SLIP-39 Shamir backups, compatible with Trezor's `shamir-mnemonic`.
+//!
+//! A master secret is encrypted with the passphrase, split into groups with a
+//! group threshold, and each group's share is split again into member shares.
+//! Any `member_threshold` members of any `group_threshold` groups recover it.
+//!
+//! Trezor uses the master secret directly as the BIP32 seed and only accepts
+//! 128- and 256-bit secrets. [`split_mnemonic`] splits the entropy of an
+//! existing BIP39 wallet instead; those shares restore that wallet only
+//! through [`combine_mnemonic`], and a Trezor restoring them derives a
+//! different wallet from the raw entropy.
+
+use crate::bip39;
+use crate::error::Error;
+use crate::secret::SecretPhrase;
+use crate::Language;
+use hmac::{Hmac, Mac};
+use rand::{Rng, RngCore};
+use serde::{Serialize, Deserialize};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use zeroize::Zeroizing;
+
+const WORDLIST: &str = include_str!("slip39_wordlist.txt");
+const RADIX_BITS: usize = 10;
+/// Identifier and exponent (2), share parameters (2) and checksum (3).
+const METADATA_WORDS: usize = 7;
+const MIN_STRENGTH_BYTES: usize = 16;
+const MAX_SHARE_COUNT: u8 = 16;
+const MAX_ITERATION_EXPONENT: u8 = 15;
+const BASE_ITERATION_COUNT: u32 = 10_000;
+const ROUND_COUNT: u8 = 4;
+const DIGEST_LENGTH: usize = 4;
+const DIGEST_INDEX: u8 = 254;
+const SECRET_INDEX: u8 = 255;
+
+/// Matches `shamir-mnemonic`'s default: 20,000 PBKDF2 iterations in total.
+pub const DEFAULT_ITERATION_EXPONENT: u8 = 1;
+
+/// Members within one group: any `threshold` of `count` shares rebuild it.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GroupSpec {
+    pub threshold: u8,
+    pub count: u8,
+}
+
+/// One decoded share mnemonic. Only the metadata is serialized.
+#[derive(Serialize, Clone)]
+pub struct Share {
+    pub identifier: u16,
+    pub extendable: bool,
+    pub iteration_exponent: u8,
+    pub group_index: u8,
+    pub group_threshold: u8,
+    pub group_count: u8,
+    pub member_index: u8,
+    pub member_threshold: u8,
+    #[serde(skip)]
+    value: Zeroizing<Vec<u8>>,
+}
+
+fn invalid(reason: impl std::fmt::Display) -> Error {
+    Error::InvalidShare { reason: reason.to_string() }
+}
+
+fn words() -> &'static [&'static str] {
+    static WORDS: OnceLock<Vec<&'static str>> = OnceLock::new();
+    WORDS.get_or_init(|| WORDLIST.lines().collect())
+}
+
+fn customization(extendable: bool) -> &'static [u8] {
+    if extendable { b"shamir_extendable" } else { b"shamir" }
+}
+
+fn rs1024_polymod(values: impl IntoIterator<Item = u32>) -> u32 {
+    const GENERATOR: [u32; 10] = [
+        0x00E0_E040, 0x01C1_C080, 0x0383_8100, 0x0707_0200, 0x0E0E_0009,
+        0x1C0C_2412, 0x3808_6C24, 0x3090_FC48, 0x21B1_F890, 0x03F3_F120,
+    ];
+    let mut checksum = 1u32;
+    for value in values {
+        let top = checksum >> 20;
+        checksum = ((checksum & 0xF_FFFF) << 10) ^ value;
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+    }
+    checksum
+}
+
+fn rs1024_values<'a>(extendable: bool, data: &'a [u16]) -> impl Iterator<Item = u32> + 'a {
+    customization(extendable)
+        .iter()
+        .map(|&b| u32::from(b))
+        .chain(data.iter().map(|&w| u32::from(w)))
+}
+
+fn rs1024_checksum(extendable: bool, data: &[u16]) -> [u16; 3] {
+    let polymod = rs1024_polymod(rs1024_values(extendable, data).chain([0, 0, 0])) ^ 1;
+    [2, 1, 0].map(|i| ((polymod >> (10 * i)) & 1023) as u16)
+}
+
+/// Big-endian bits of `bytes` as 10-bit words, zero-padded at the front.
+fn bytes_to_words(bytes: &[u8]) -> Vec<u16> {
+    let word_count = (bytes.len() * 8).div_ceil(RADIX_BITS);
+    let mut words = Vec::with_capacity(word_count);
+    let mut accumulator = 0u32;
+    let mut bits = word_count * RADIX_BITS - bytes.len() * 8;
+    for &byte in bytes {
+        accumulator = (accumulator << 8) | u32::from(byte);
+        bits += 8;
+        while bits >= RADIX_BITS {
+            bits -= RADIX_BITS;
+            words.push(((accumulator >> bits) & 1023) as u16);
+        }
+        accumulator &= (1 << bits) - 1;
+    }
+    words
+}
+
+/// Inverse of [`bytes_to_words`]; the padding must be short and all zero.
+fn words_to_bytes(words: &[u16]) -> Result<Zeroizing<Vec<u8>>, Error> {
+    let padding = words.len() * RADIX_BITS % 16;
+    if padding > 8 {
+        return Err(invalid("invalid mnemonic length"));
+    }
+    let mut bytes = Zeroizing::new(Vec::with_capacity(words.len() * RADIX_BITS / 8));
+    let mut accumulator = 0u32;
+    let mut bits = 0;
+    let mut skip = padding;
+    for &word in words {
+        accumulator = (accumulator << RADIX_BITS) | u32::from(word);
+        bits += RADIX_BITS;
+        if skip > 0 {
+            if accumulator >> (bits - skip) != 0 {
+                return Err(invalid("invalid mnemonic padding"));
+            }
+            bits -= skip;
+            skip = 0;
+        }
+        while bits >= 8 {
+            bits -= 8;
+            bytes.push((accumulator >> bits) as u8);
+        }
+        accumulator &= (1 << bits) - 1;
+    }
+    Ok(bytes)
+}
+
+impl Share {
+    pub fn to_mnemonic(&self) -> SecretPhrase {
+        let id_exp = (u32::from(self.identifier) << 5)
+            | (u32::from(self.extendable) << 4)
+            | u32::from(self.iteration_exponent);
+        let parameters = (u32::from(self.group_index) << 16)
+            | (u32::from(self.group_threshold - 1) << 12)
+            | (u32::from(self.group_count - 1) << 8)
+            | (u32::from(self.member_index) << 4)
+            | u32::from(self.member_threshold - 1);
+
+        let mut data = Zeroizing::new(vec![
+            (id_exp >> 10) as u16,
+            (id_exp & 1023) as u16,
+            (parameters >> 10) as u16,
+            (parameters & 1023) as u16,
+        ]);
+        data.extend(bytes_to_words(&self.value));
+        let checksum = rs1024_checksum(self.extendable, &data);
+        data.extend(checksum);
+
+        let words = words();
+        SecretPhrase::new(data.iter().map(|&i| words[usize::from(i)]).collect::<Vec<_>>().join(" "))
+    }
+}
+
+impl FromStr for Share {
+    type Err = Error;
+
+    fn from_str(mnemonic: &str) -> Result<Self, Self::Err> {
+        let mnemonic = Zeroizing::new(mnemonic.to_lowercase());
+        let wordlist = words();
+        let data = Zeroizing::new(
+            mnemonic
+                .split_whitespace()
+                .enumerate()
+                .map(|(position, word)| {
+                    wordlist
+                        .binary_search(&word)
+                        .map(|i| i as u16)
+                        .map_err(|_| Error::InvalidWord { position })
+                })
+                .collect::<Result<Vec<u16>, Error>>()?,
+        );
+        let min_words = METADATA_WORDS + (MIN_STRENGTH_BYTES * 8).div_ceil(RADIX_BITS);
+        if data.len() < min_words {
+            return Err(invalid(format!("a share has at least {} words, got {}", min_words, data.len())));
+        }
+
+        let extendable = (data[1] >> 4) & 1 == 1;
+        if rs1024_polymod(rs1024_values(extendable, &data)) != 1 {
+            return Err(Error::InvalidChecksum);
+        }
+
+        let id_exp = (u32::from(data[0]) << 10) | u32::from(data[1]);
+        let parameters = (u32::from(data[2]) << 10) | u32::from(data[3]);
+        let nibble = |shift: u32| ((parameters >> shift) & 15) as u8;
+        let share = Share {
+            identifier: (id_exp >> 5) as u16,
+            extendable,
+            iteration_exponent: (id_exp & 15) as u8,
+            group_index: nibble(16),
+            group_threshold: nibble(12) + 1,
+            group_count: nibble(8) + 1,
+            member_index: nibble(4),
+            member_threshold: nibble(0) + 1,
+            value: words_to_bytes(&data[4..data.len() - 3])?,
+        };
+        if share.group_threshold > share.group_count {
+            return Err(invalid(format!(
+                "group threshold {} exceeds group count {}",
+                share.group_threshold, share.group_count
+            )));
+        }
+        if share.group_index >= share.group_count {
+            return Err(invalid(format!(
+                "group index {} is out of range for {} groups",
+                share.group_index + 1, share.group_count
+            )));
+        }
+        Ok(share)
+    }
+}
+
+/// Log and antilog tables for GF(256) with the Rijndael polynomial.
+const fn field_tables() -> ([u8; 255], [u8; 256]) {
+    let mut exp = [0u8; 255];
+    let mut log = [0u8; 256];
+    let mut poly: u16 = 1;
+    let mut i = 0;
+    while i < 255 {
+        exp[i] = poly as u8;
+        log[poly as usize] = i as u8;
+        poly = (poly << 1) ^ poly;
+        if poly & 0x100 != 0 {
+            poly ^= 0x11B;
+        }
+        i += 1;
+    }
+    (exp, log)
+}
+
+const FIELD: ([u8; 255], [u8; 256]) = field_tables();
+
+/// Lagrange interpolation of `shares` at `x`, byte by byte.
+fn interpolate(shares: &[(u8, &[u8])], x: u8) -> Zeroizing<Vec<u8>> {
+    if let Some((_, value)) = shares.iter().find(|(index, _)| *index == x) {
+        return Zeroizing::new(value.to_vec());
+    }
+    let (exp, log) = &FIELD;
+    let log_product: u32 = shares.iter().map(|(index, _)| u32::from(log[usize::from(index ^ x)])).sum();
+
+    let mut result = Zeroizing::new(vec![0u8; shares[0].1.len()]);
+    for (index, value) in shares {
+        let others: u32 = shares
+            .iter()
+            .filter(|(other, _)| other != index)
+            .map(|(other, _)| u32::from(log[usize::from(index ^ other)]))
+            .sum();
+        let basis = (log_product + 255 * 16 - u32::from(log[usize::from(index ^ x)]) - others) % 255;
+        for (out, &byte) in result.iter_mut().zip(value.iter()) {
+            if byte != 0 {
+                *out ^= exp[((u32::from(log[usize::from(byte)]) + basis) % 255) as usize];
+            }
+        }
+    }
+    result
+}
+
+fn digest(random_part: &[u8], secret: &[u8]) -> [u8; DIGEST_LENGTH] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(random_part).expect("HMAC takes any key length");
+    mac.update(secret);
+    let mut digest = [0u8; DIGEST_LENGTH];
+    digest.copy_from_slice(&mac.finalize().into_bytes()[..DIGEST_LENGTH]);
+    digest
+}
+
+fn split_secret(threshold: u8, count: u8, secret: &[u8]) -> Vec<(u8, Zeroizing<Vec<u8>>)> {
+    if threshold == 1 {
+        return (0..count).map(|i| (i, Zeroizing::new(secret.to_vec()))).collect();
+    }
+    let mut rng = rand::thread_rng();
+    let random_count = threshold - 2;
+    let mut shares: Vec<(u8, Zeroizing<Vec<u8>>)> = (0..random_count)
+        .map(|i| {
+            let mut value = Zeroizing::new(vec![0u8; secret.len()]);
+            rng.fill_bytes(&mut value);
+            (i, value)
+        })
+        .collect();
+
+    let mut digest_share = Zeroizing::new(vec![0u8; secret.len()]);
+    rng.fill_bytes(&mut digest_share[DIGEST_LENGTH..]);
+    let check = digest(&digest_share[DIGEST_LENGTH..], secret);
+    digest_share[..DIGEST_LENGTH].copy_from_slice(&check);
+
+    let mut base: Vec<(u8, &[u8])> = shares.iter().map(|(i, v)| (*i, &v[..])).collect();
+    base.push((DIGEST_INDEX, &digest_share));
+    base.push((SECRET_INDEX, secret));
+    let derived: Vec<_> = (random_count..count).map(|i| (i, interpolate(&base, i))).collect();
+    shares.extend(derived);
+    shares
+}
+
+fn recover_secret(threshold: u8, shares: &[(u8, &[u8])]) -> Result<Zeroizing<Vec<u8>>, Error> {
+    if threshold == 1 {
+        return Ok(Zeroizing::new(shares[0].1.to_vec()));
+    }
+    let secret = interpolate(shares, SECRET_INDEX);
+    let digest_share = interpolate(shares, DIGEST_INDEX);
+    if digest(&digest_share[DIGEST_LENGTH..], &secret) != digest_share[..DIGEST_LENGTH] {
+        return Err(invalid("share digest mismatch; the shares do not belong together"));
+    }
+    Ok(secret)
+}
+
+/// The four-round Feistel cipher keyed by the passphrase. Encrypts with rounds
+/// 0..4 and decrypts with the same rounds reversed.
+fn feistel(
+    value: &[u8],
+    passphrase: &str,
+    iteration_exponent: u8,
+    identifier: u16,
+    extendable: bool,
+    rounds: impl Iterator<Item = u8>,
+) -> Zeroizing<Vec<u8>> {
+    let half = value.len() / 2;
+    let mut left = Zeroizing::new(value[..half].to_vec());
+    let mut right = Zeroizing::new(value[half..].to_vec());
+    let mut salt = Vec::new();
+    if !extendable {
+        salt.extend_from_slice(b"shamir");
+        salt.extend_from_slice(&identifier.to_be_bytes());
+    }
+    let iterations = (BASE_ITERATION_COUNT << iteration_exponent) / u32::from(ROUND_COUNT);
+
+    for round in rounds {
+        let mut password = Zeroizing::new(vec![round]);
+        password.extend_from_slice(passphrase.as_bytes());
+        let mut round_salt = salt.clone();
+        round_salt.extend_from_slice(&right);
+
+        let mut f = Zeroizing::new(vec![0u8; right.len()]);
+        pbkdf2::pbkdf2_hmac::<Sha256>(&password, &round_salt, iterations, &mut f);
+        for (byte, mask) in left.iter_mut().zip(f.iter()) {
+            *byte ^= mask;
+        }
+        std::mem::swap(&mut left, &mut right);
+    }
+
+    right.extend_from_slice(&left);
+    right
+}
+
+fn check_passphrase(passphrase: &str) -> Result<(), Error> {
+    if passphrase.bytes().all(|b| (32..=126).contains(&b)) {
+        Ok(())
+    } else {
+        Err(Error::invalid_argument("passphrase", "SLIP-39 passphrases are limited to printable ASCII"))
+    }
+}
+
+/// Splits `master_secret` into share mnemonics, one list per group.
+///
+/// `iteration_exponent` sets the PBKDF2 cost (`10000 << e` iterations).
+/// `extendable` backups can later gain shares with the same identifier; Trezor
+/// creates them by default.
+pub fn split_master_secret(
+    master_secret: &[u8],
+    passphrase: &str,
+    group_threshold: u8,
+    groups: &[GroupSpec],
+    iteration_exponent: u8,
+    extendable: bool,
+) -> Result<Vec<Vec<SecretPhrase>>, Error> {
+    if master_secret.len() < MIN_STRENGTH_BYTES || !master_secret.len().is_multiple_of(2) {
+        return Err(Error::invalid_argument(
+            "master secret",
+            format!("must be an even number of bytes, at least {}", MIN_STRENGTH_BYTES),
+        ));
+    }
+    check_passphrase(passphrase)?;
+    if iteration_exponent > MAX_ITERATION_EXPONENT {
+        return Err(Error::invalid_argument("iteration exponent", format!("must be at most {}", MAX_ITERATION_EXPONENT)));
+    }
+    let group_count = u8::try_from(groups.len())
+        .ok()
+        .filter(|count| (1..=MAX_SHARE_COUNT).contains(count))
+        .ok_or_else(|| Error::invalid_argument("groups", format!("between 1 and {} groups are allowed", MAX_SHARE_COUNT)))?;
+    if group_threshold == 0 || group_threshold > group_count {
+        return Err(Error::invalid_argument(
+            "group threshold",
+            format!("must be between 1 and the group count {}", group_count),
+        ));
+    }
+    for group in groups {
+        if group.threshold == 0 || group.threshold > group.count || group.count > MAX_SHARE_COUNT {
+            return Err(Error::invalid_argument(
+                "groups",
+                format!("{}-of-{} is not a valid group", group.threshold, group.count),
+            ));
+        }
+        if group.threshold == 1 && group.count > 1 {
+            return Err(Error::invalid_argument("groups", "use 1-of-1 instead of several 1-of-n shares"));
+        }
+    }
+
+    let identifier: u16 = rand::thread_rng().gen_range(0..1 << 15);
+    let encrypted = feistel(master_secret, passphrase, iteration_exponent, identifier, extendable, 0..ROUND_COUNT);
+
+    let group_shares = split_secret(group_threshold, group_count, &encrypted);
+    Ok(groups
+        .iter()
+        .zip(group_shares)
+        .map(|(group, (group_index, group_secret))| {
+            split_secret(group.threshold, group.count, &group_secret)
+                .into_iter()
+                .map(|(member_index, value)| {
+                    Share {
+                        identifier,
+                        extendable,
+                        iteration_exponent,
+                        group_index,
+                        group_threshold,
+                        group_count,
+                        member_index,
+                        member_threshold: group.threshold,
+                        value,
+                    }
+                    .to_mnemonic()
+                })
+                .collect()
+        })
+        .collect())
+}
+
+/// Recovers the master secret. Shares may come in any order; duplicates and
+/// shares beyond what the thresholds need are ignored.
+pub fn combine_shares<S: AsRef<str>>(mnemonics: &[S], passphrase: &str) -> Result<Zeroizing<Vec<u8>>, Error> {
+    check_passphrase(passphrase)?;
+    let shares = mnemonics
+        .iter()
+        .map(|mnemonic| mnemonic.as_ref().parse::<Share>())
+        .collect::<Result<Vec<_>, _>>()?;
+    let first = shares.first().ok_or_else(|| invalid("no shares given"))?;
+
+    let mut groups: BTreeMap<u8, BTreeMap<u8, &Share>> = BTreeMap::new();
+    for share in &shares {
+        let same_backup = share.identifier == first.identifier
+            && share.extendable == first.extendable
+            && share.iteration_exponent == first.iteration_exponent
+            && share.group_threshold == first.group_threshold
+            && share.group_count == first.group_count
+            && share.value.len() == first.value.len();
+        if !same_backup {
+            return Err(invalid("shares come from different backups"));
+        }
+        let members = groups.entry(share.group_index).or_default();
+        if members.values().any(|member| member.member_threshold != share.member_threshold) {
+            return Err(invalid(format!("group {} mixes member thresholds", share.group_index + 1)));
+        }
+        if let Some(existing) = members.insert(share.member_index, share) {
+            if existing.value != share.value {
+                return Err(invalid(format!(
+                    "two different shares claim member {} of group {}",
+                    share.member_index + 1,
+                    share.group_index + 1
+                )));
+            }
+        }
+    }
+
+    let mut group_secrets = Vec::new();
+    for (group_index, members) in &groups {
+        let threshold = members.values().next().map_or(0, |member| member.member_threshold);
+        if members.len() >= usize::from(threshold) {
+            let values: Vec<(u8, &[u8])> = members
+                .iter()
+                .take(usize::from(threshold))
+                .map(|(index, member)| (*index, &member.value[..]))
+                .collect();
+            group_secrets.push((*group_index, recover_secret(threshold, &values)?));
+        }
+        if group_secrets.len() == usize::from(first.group_threshold) {
+            break;
+        }
+    }
+    if group_secrets.len() < usize::from(first.group_threshold) {
+        return Err(invalid(format!(
+            "{} of the {} required groups are complete",
+            group_secrets.len(),
+            first.group_threshold
+        )));
+    }
+
+    let values: Vec<(u8, &[u8])> = group_secrets.iter().map(|(index, value)| (*index, &value[..])).collect();
+    let encrypted = recover_secret(first.group_threshold, &values)?;
+    Ok(feistel(
+        &encrypted,
+        passphrase,
+        first.iteration_exponent,
+        first.identifier,
+        first.extendable,
+        (0..ROUND_COUNT).rev(),
+    ))
+}
+
+/// Splits the entropy of a BIP39 mnemonic. Any SLIP-39 implementation
+/// recovers the entropy, but only [`combine_mnemonic`] turns it back into the
+/// phrase; a Trezor would use it as the seed and show different addresses.
+pub fn split_mnemonic(
+    mnemonic: &str,
+    passphrase: &str,
+    group_threshold: u8,
+    groups: &[GroupSpec],
+    iteration_exponent: u8,
+    extendable: bool,
+) -> Result<Vec<Vec<SecretPhrase>>, Error> {
+    let (entropy, length) = bip39::parse_mnemonic(mnemonic)?.to_entropy_array();
+    let entropy = Zeroizing::new(entropy);
+    split_master_secret(&entropy[..length], passphrase, group_threshold, groups, iteration_exponent, extendable)
+}
+
+/// Recovers a mnemonic split by [`split_mnemonic`]. The shares carry only the
+/// entropy, so the wordlist has to be given again.
+pub fn combine_mnemonic<S: AsRef<str>>(mnemonics: &[S], passphrase: &str, language: Language) -> Result<SecretPhrase, Error> {
+    let entropy = combine_shares(mnemonics, passphrase)?;
+    Ok(bip39::format_phrase(&::bip39::Mnemonic::from_entropy_in(language, &entropy)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // From `vectors.json` in Trezor's python-shamir-mnemonic; every vector
+    // uses the passphrase "TREZOR".
+    const PASSPHRASE: &str = "TREZOR";
+    const NO_SHARING: &str = "duckling enlarge academic academic agency result length solution fridge kidney coal piece deal husband erode duke ajar critical decision keyboard";
+    const BAD_CHECKSUM: &str = "duckling enlarge academic academic agency result length solution fridge kidney coal piece deal husband erode duke ajar critical decision kidney";
+    const BAD_PADDING: &str = "duckling enlarge academic academic email result length solution fridge kidney coal piece deal husband erode duke ajar music cargo fitness";
+    const TWO_OF_THREE: [&str; 2] = [
+        "shadow pistol academic always adequate wildlife fancy gross oasis cylinder mustang wrist rescue view short owner flip making coding armed",
+        "shadow pistol academic acid actress prayer class unknown daughter sweater depict flip twice unkind craft early superior advocate guest smoking",
+    ];
+    const GROUPS: [&str; 5] = [
+        "eraser senior beard romp adorn nuclear spill corner cradle style ancient family general leader ambition exchange unusual garlic promise voice",
+        "eraser senior ceramic snake clay various huge numb argue hesitate auction category timber browser greatest hanger petition script leaf pickup",
+        "eraser senior ceramic shaft dynamic become junior wrist silver peasant force math alto coal amazing segment yelp velvet image paces",
+        "eraser senior ceramic round column hawk trust auction smug shame alive greatest sheriff living perfect corner chest sled fumes adequate",
+        "eraser senior decision smug corner ruin rescue cubic angel tackle skin skunk program roster trash rumor slush angel flea amazing",
+    ];
+    const NO_SHARING_256: &str = "theory painting academic academic armed sweater year military elder discuss acne wildlife boring employer fused large satoshi bundle carbon diagnose anatomy hamster leaves tracks paces beyond phantom capital marvel lips brave detect luck";
+    const EXTENDABLE: &str = "testify swimming academic academic column loyalty smear include exotic bedroom exotic wrist lobe cover grief golden smart junior estimate learn";
+
+    const HARDHAT: &str = "test test test test test test test test test test test junk";
+
+    fn combine(mnemonics: &[&str]) -> Result<String, Error> {
+        combine_shares(mnemonics, PASSPHRASE).map(|secret| hex::encode(&secret[..]))
+    }
+
+    fn expose(groups: &[Vec<SecretPhrase>]) -> Vec<Vec<String>> {
+        groups.iter().map(|group| group.iter().map(|share| share.expose().to_string()).collect()).collect()
+    }
+
+    #[test]
+    fn recovers_the_vector_secrets() {
+        assert_eq!(combine(&[NO_SHARING]).unwrap(), "bb54aac4b89dc868ba37d9cc21b2cece");
+        assert_eq!(combine(&TWO_OF_THREE).unwrap(), "b43ceb7e57a0ea8766221624d01b0864");
+        assert_eq!(combine(&[TWO_OF_THREE[1], TWO_OF_THREE[0]]).unwrap(), "b43ceb7e57a0ea8766221624d01b0864");
+        assert_eq!(combine(&GROUPS).unwrap(), "7c3397a292a5941682d7a4ae2d898d11");
+        assert_eq!(
+            combine(&[NO_SHARING_256]).unwrap(),
+            "989baf9dcaad5b10ca33dfd8cc75e42477025dce88ae83e75a230086a0e00e92"
+        );
+        assert_eq!(combine(&[EXTENDABLE]).unwrap(), "1679b4516e0ee5954351d288a838f45e");
+    }
+
+    #[test]
+    fn rejects_a_bad_checksum() {
+        assert!(matches!(combine(&[BAD_CHECKSUM]), Err(Error::InvalidChecksum)));
+    }
+
+    #[test]
+    fn rejects_bad_padding() {
+        assert!(matches!(combine(&[BAD_PADDING]), Err(Error::InvalidShare { .. })));
+    }
+
+    #[test]
+    fn rejects_too_few_shares() {
+        assert!(matches!(combine(&TWO_OF_THREE[..1]), Err(Error::InvalidShare { .. })));
+        // Group 1 is complete; group 3 has one of its two members.
+        assert!(matches!(combine(&[GROUPS[0], GROUPS[4]]), Err(Error::InvalidShare { .. })));
+    }
+
+    #[test]
+    fn rejects_shares_from_different_backups() {
+        assert!(matches!(combine(&[TWO_OF_THREE[0], GROUPS[1]]), Err(Error::InvalidShare { .. })));
+    }
+
+    #[test]
+    fn rejects_a_group_index_beyond_the_group_count() {
+        let mut share: Share = NO_SHARING.parse().unwrap();
+        share.group_index = share.group_count;
+        let out_of_range = share.to_mnemonic();
+        assert!(matches!(out_of_range.expose().parse::<Share>(), Err(Error::InvalidShare { .. })));
+        assert!(matches!(combine(&[out_of_range.expose()]), Err(Error::InvalidShare { .. })));
+    }
+
+    #[test]
+    fn reads_share_metadata() {
+        let share: Share = GROUPS[1].parse().unwrap();
+        assert_eq!(
+            (share.group_index, share.group_threshold, share.group_count, share.member_index, share.member_threshold),
+            (2, 2, 4, 4, 3)
+        );
+        assert_eq!(share.to_mnemonic().expose(), GROUPS[1]);
+    }
+
+    #[test]
+    fn group_split_round_trips() {
+        let secret = hex::decode("0c94e6d4a8dc3e7d6db0a27e5b34a2fb").unwrap();
+        let groups = [GroupSpec { threshold: 1, count: 1 }, GroupSpec { threshold: 2, count: 3 }, GroupSpec { threshold: 3, count: 5 }];
+        for extendable in [false, true] {
+            let shares = expose(&split_master_secret(&secret, PASSPHRASE, 2, &groups, 0, extendable).unwrap());
+            assert_eq!(shares.iter().map(Vec::len).collect::<Vec<_>>(), [1, 3, 5]);
+
+            let enough = [&shares[0][0], &shares[2][4], &shares[2][0], &shares[2][2]];
+            assert_eq!(combine_shares(&enough, PASSPHRASE).unwrap()[..], secret[..]);
+            let enough = [&shares[1][2], &shares[2][1], &shares[1][0], &shares[2][3], &shares[2][4]];
+            assert_eq!(combine_shares(&enough, PASSPHRASE).unwrap()[..], secret[..]);
+
+            // One complete group and one member short in the other.
+            let short = [&shares[0][0], &shares[2][0], &shares[2][1]];
+            assert!(combine_shares(&short, PASSPHRASE).is_err());
+
+            // Another passphrase decrypts to another secret, by design.
+            let other = combine_shares(&[&shares[0][0], &shares[1][0], &shares[1][1]], "").unwrap();
+            assert_ne!(other[..], secret[..]);
+        }
+    }
+
+    #[test]
+    fn mnemonic_split_round_trips_through_the_entropy() {
+        let groups = [GroupSpec { threshold: 2, count: 3 }];
+        let shares = expose(&split_mnemonic(HARDHAT, "", 1, &groups, 0, true).unwrap());
+        let share: Share = shares[0][0].parse().unwrap();
+        assert_eq!(share.value.len(), 16);
+
+        let recovered = combine_mnemonic(&shares[0][1..], "", Language::English).unwrap();
+        assert_eq!(recovered.expose(), HARDHAT);
+    }
+
+    #[test]
+    fn rejects_invalid_split_parameters() {
+        let secret = [7u8; 16];
+        let group = [GroupSpec { threshold: 2, count: 3 }];
+        assert!(split_master_secret(&secret[..15], "", 1, &group, 0, true).is_err());
+        assert!(split_master_secret(&[7u8; 17], "", 1, &group, 0, true).is_err());
+        assert!(split_master_secret(&secret, "é", 1, &group, 0, true).is_err());
+        assert!(split_master_secret(&secret, "", 2, &group, 0, true).is_err());
+        assert!(split_master_secret(&secret, "", 1, &[GroupSpec { threshold: 4, count: 3 }], 0, true).is_err());
+        assert!(split_master_secret(&secret, "", 1, &[GroupSpec { threshold: 1, count: 3 }], 0, true).is_err());
+        assert!(split_master_secret(&secret, "", 1, &group, MAX_ITERATION_EXPONENT + 1, true).is_err());
+    }
+}
@@ -0,0 +1,358 @@
+//! Dividing a wei amount between the children of a split.
+//!
+//! Everything is exact 256-bit integer arithmetic, so a plan always adds up
+//! to the wei it was given. Integer division leaves a few wei over; they go
+//! to the children with the largest fractional share, lower index first on
+//! ties, so the same input always gives the same plan.
+
+use crate::error::Error;
+use crate::transaction::quantity;
+use primitive_types::{U256, U512};
+use rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::{Serialize, Deserialize};
+use std::cmp::Reverse;
+
+/// How much each child receives.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "strategy", rename_all = "snake_case")]
+pub enum AmountStrategy {
+    /// `total` divided equally; the remainder goes one wei each to the first children.
+    Equal {
+        #[serde(with = "quantity")]
+        total: U256,
+    },
+    /// `total` divided in proportion to `weights`, one per child.
+    Weighted {
+        #[serde(with = "quantity")]
+        total: U256,
+        weights: Vec<u64>,
+    },
+    /// The same amount to every child.
+    Fixed {
+        #[serde(with = "quantity")]
+        amount: U256,
+    },
+    /// Explicit amounts, one per child.
+    PerChild {
+        #[serde(with = "quantity::vec")]
+        amounts: Vec<U256>,
+    },
+    /// Random amounts that add up to `total`. The same seed gives the same
+    /// amounts; without one a seed is drawn and reported in [`SplitPlan::seed`].
+    Random {
+        #[serde(with = "quantity")]
+        total: U256,
+        #[serde(default)]
+        seed: Option<u64>,
+    },
+}
+
+/// A strategy plus per-child bounds and a gas reserve.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AmountPlan {
+    #[serde(flatten)]
+    pub strategy: AmountStrategy,
+    /// Defaults to 1 wei, so no child is left unfunded.
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "quantity::option")]
+    pub min_amount: Option<U256>,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "quantity::option")]
+    pub max_amount: Option<U256>,
+    /// Wei held back from `total` for each transfer's gas. Distributions
+    /// default it to the maximum fee of one transfer.
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "quantity::option")]
+    pub gas_reserve: Option<U256>,
+}
+
+impl From<AmountStrategy> for AmountPlan {
+    fn from(strategy: AmountStrategy) -> Self {
+        AmountPlan { strategy, min_amount: None, max_amount: None, gas_reserve: None }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SplitPlan {
+    #[serde(with = "quantity::vec")]
+    pub amounts: Vec<U256>,
+    /// The sum of `amounts`.
+    #[serde(with = "quantity")]
+    pub total_amount: U256,
+    /// `gas_reserve` times the number of children.
+    #[serde(with = "quantity")]
+    pub gas_reserved: U256,
+    /// `total_amount + gas_reserved`; equals `total` for the strategies that take one.
+    #[serde(with = "quantity")]
+    pub required_total: U256,
+    /// The seed of a random plan.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+}
+
+fn overflow() -> Error {
+    Error::invalid_argument("amounts", "total overflows 256 bits")
+}
+
+fn sum(amounts: &[U256]) -> Result<U256, Error> {
+    amounts
+        .iter()
+        .try_fold(U256::zero(), |total, amount| total.checked_add(*amount))
+        .ok_or_else(overflow)
+}
+
+/// Splits `amount` in proportion to `weights` by largest remainder.
+fn apportion(amount: U256, weights: &[U256]) -> Vec<U256> {
+    let total_weight = U512::from(weights.iter().fold(U256::zero(), |total, weight| total + weight));
+    let (mut shares, remainders): (Vec<U256>, Vec<U512>) = weights
+        .iter()
+        .map(|weight| {
+            let (share, remainder) = amount.full_mul(*weight).div_mod(total_weight);
+            (U256::try_from(share).expect("a share never exceeds the amount"), remainder)
+        })
+        .unzip();
+
+    let leftover = (amount - shares.iter().fold(U256::zero(), |total, share| total + share)).as_usize();
+    let mut order: Vec<usize> = (0..weights.len()).collect();
+    order.sort_by_key(|&i| Reverse(remainders[i]));
+    for &i in &order[..leftover] {
+        shares[i] += U256::one();
+    }
+    shares
+}
+
+/// Splits `total` by `weights` with every share in `min..=max`. Children that
+/// hit `max` drop out and the excess is shared again among the rest.
+fn share(total: U256, weights: &[U256], min: U256, max: U256) -> Result<Vec<U256>, Error> {
+    let count = U256::from(weights.len());
+    let floor = min.checked_mul(count).ok_or_else(overflow)?;
+    if total < floor {
+        return Err(Error::invalid_argument(
+            "total",
+            format!("{} wei cannot give {} children at least {} wei each", total, count, min),
+        ));
+    }
+    if max.checked_mul(count).is_some_and(|ceiling| total > ceiling) {
+        return Err(Error::invalid_argument(
+            "total",
+            format!("{} wei cannot be split among {} children at most {} wei each", total, count, max),
+        ));
+    }
+
+    let mut amounts = vec![min; weights.len()];
+    let mut remaining = total - floor;
+    let mut open: Vec<usize> = (0..weights.len()).filter(|&i| !weights[i].is_zero() && max > min).collect();
+    while !remaining.is_zero() {
+        if open.is_empty() {
+            return Err(Error::invalid_argument(
+                "weights",
+                format!("{} wei would go to children with zero weight", remaining),
+            ));
+        }
+        let grants = apportion(remaining, &open.iter().map(|&i| weights[i]).collect::<Vec<_>>());
+        let mut still_open = Vec::with_capacity(open.len());
+        for (&i, grant) in open.iter().zip(grants) {
+            let room = max - amounts[i];
+            let grant = grant.min(room);
+            amounts[i] += grant;
+            remaining -= grant;
+            if grant < room {
+                still_open.push(i);
+            }
+        }
+        open = still_open;
+    }
+    Ok(amounts)
+}
+
+fn check_length(argument: &str, length: usize, count: usize) -> Result<(), Error> {
+    if length != count {
+        return Err(Error::invalid_argument(argument, format!("{} given for {} children", length, count)));
+    }
+    Ok(())
+}
+
+impl AmountPlan {
+    /// The amounts for `count` children.
+    pub fn plan(&self, count: usize) -> Result<SplitPlan, Error> {
+        if count == 0 {
+            return Err(Error::invalid_argument("count", "at least one child is required"));
+        }
+        let min = self.min_amount.unwrap_or_else(U256::one);
+        let max = self.max_amount.unwrap_or(U256::MAX);
+        if min > max {
+            return Err(Error::invalid_argument("min_amount", "must not exceed max_amount"));
+        }
+        let gas_reserved = self
+            .gas_reserve
+            .unwrap_or_default()
+            .checked_mul(U256::from(count))
+            .ok_or_else(overflow)?;
+        let available = |total: U256| {
+            total.checked_sub(gas_reserved).ok_or_else(|| {
+                Error::invalid_argument("total", format!("{} wei does not cover {} wei of gas reserves", total, gas_reserved))
+            })
+        };
+
+        let mut seed = None;
+        let amounts = match &self.strategy {
+            AmountStrategy::Equal { total } => share(available(*total)?, &vec![U256::one(); count], min, max)?,
+            AmountStrategy::Weighted { total, weights } => {
+                check_length("weights", weights.len(), count)?;
+                let weights: Vec<U256> = weights.iter().map(|&w| U256::from(w)).collect();
+                share(available(*total)?, &weights, min, max)?
+            }
+            AmountStrategy::Random { total, seed: requested } => {
+                let chosen = requested.unwrap_or_else(|| rand::thread_rng().next_u64());
+                seed = Some(chosen);
+                let mut rng = ChaCha20Rng::seed_from_u64(chosen);
+                let weights: Vec<U256> = (0..count).map(|_| U256::from(rng.gen_range(1..=u32::MAX))).collect();
+                share(available(*total)?, &weights, min, max)?
+            }
+            AmountStrategy::Fixed { amount } => vec![*amount; count],
+            AmountStrategy::PerChild { amounts } => {
+                check_length("amounts", amounts.len(), count)?;
+                amounts.clone()
+            }
+        };
+        if let Some((i, amount)) = amounts.iter().enumerate().find(|(_, amount)| **amount < min || **amount > max) {
+            return Err(Error::invalid_argument(
+                "amounts",
+                format!("child {} would get {} wei, outside {}..={}", i, amount, min, max),
+            ));
+        }
+
+        let total_amount = sum(&amounts)?;
+        Ok(SplitPlan {
+            amounts,
+            total_amount,
+            gas_reserved,
+            required_total: total_amount.checked_add(gas_reserved).ok_or_else(overflow)?,
+            seed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wei(amounts: &[u64]) -> Vec<U256> {
+        amounts.iter().map(|&amount| U256::from(amount)).collect()
+    }
+
+    fn plan(strategy: AmountStrategy, count: usize) -> Result<SplitPlan, Error> {
+        AmountPlan::from(strategy).plan(count)
+    }
+
+    fn equal(total: u64) -> AmountStrategy {
+        AmountStrategy::Equal { total: U256::from(total) }
+    }
+
+    fn weighted(total: u64, weights: &[u64]) -> AmountStrategy {
+        AmountStrategy::Weighted { total: U256::from(total), weights: weights.to_vec() }
+    }
+
+    #[test]
+    fn equal_splits_add_up_to_the_wei() {
+        let split = plan(equal(10), 3).unwrap();
+        assert_eq!(split.amounts, wei(&[4, 3, 3]));
+        assert_eq!(split.total_amount, U256::from(10));
+        assert_eq!(split.required_total, U256::from(10));
+
+        let total = U256::exp10(18) + 7;
+        let split = plan(AmountStrategy::Equal { total }, 7).unwrap();
+        assert_eq!(split.amounts.iter().fold(U256::zero(), |sum, amount| sum + amount), total);
+    }
+
+    #[test]
+    fn remainder_goes_to_the_largest_fraction_then_the_lowest_index() {
+        // 10/3 = 3.33 and 20/3 = 6.67: the second child has the larger fraction.
+        let unfloored = AmountPlan { min_amount: Some(U256::zero()), ..AmountPlan::from(weighted(10, &[1, 2])) };
+        assert_eq!(unfloored.plan(2).unwrap().amounts, wei(&[3, 7]));
+        // With the default 1 wei floor, 8 wei are shared: 2.67 and 5.33.
+        assert_eq!(plan(weighted(10, &[1, 2]), 2).unwrap().amounts, wei(&[4, 6]));
+        // Equal fractions: the first children get the spare wei.
+        assert_eq!(plan(weighted(11, &[1, 1, 1]), 3).unwrap().amounts, wei(&[4, 4, 3]));
+        assert_eq!(plan(weighted(11, &[5, 3, 3]), 3).unwrap().amounts, wei(&[5, 3, 3]));
+    }
+
+    #[test]
+    fn bounds_cap_and_redistribute() {
+        let bounded = AmountPlan {
+            min_amount: Some(U256::from(10)),
+            max_amount: Some(U256::from(40)),
+            ..AmountPlan::from(weighted(100, &[1, 1, 8]))
+        };
+        assert_eq!(bounded.plan(3).unwrap().amounts, wei(&[30, 30, 40]));
+
+        let too_little = AmountPlan { strategy: equal(29), ..bounded.clone() };
+        assert!(too_little.plan(3).is_err());
+        let too_much = AmountPlan { strategy: equal(121), ..bounded.clone() };
+        assert!(too_much.plan(3).is_err());
+        let inverted = AmountPlan { min_amount: Some(U256::from(41)), ..bounded.clone() };
+        assert!(inverted.plan(3).is_err());
+
+        let fixed = AmountPlan { strategy: AmountStrategy::Fixed { amount: U256::from(50) }, ..bounded };
+        assert!(fixed.plan(3).is_err());
+    }
+
+    #[test]
+    fn every_child_gets_at_least_one_wei_by_default() {
+        assert!(plan(equal(2), 3).is_err());
+        // A zero weight still gets the minimum, and nothing more.
+        assert_eq!(plan(weighted(10, &[0, 1]), 2).unwrap().amounts, wei(&[1, 9]));
+        assert!(plan(weighted(10, &[0, 0]), 2).is_err());
+    }
+
+    #[test]
+    fn gas_reserve_comes_out_of_the_total() {
+        let reserved = AmountPlan { gas_reserve: Some(U256::from(100)), ..AmountPlan::from(equal(1_000)) };
+        let split = reserved.plan(3).unwrap();
+        assert_eq!(split.amounts, wei(&[234, 233, 233]));
+        assert_eq!(split.total_amount, U256::from(700));
+        assert_eq!(split.gas_reserved, U256::from(300));
+        assert_eq!(split.required_total, U256::from(1_000));
+
+        let fixed = AmountPlan {
+            gas_reserve: Some(U256::from(100)),
+            ..AmountPlan::from(AmountStrategy::Fixed { amount: U256::from(50) })
+        };
+        assert_eq!(fixed.plan(3).unwrap().required_total, U256::from(450));
+
+        let short = AmountPlan { gas_reserve: Some(U256::from(400)), ..AmountPlan::from(equal(1_000)) };
+        assert!(short.plan(3).is_err());
+    }
+
+    #[test]
+    fn random_splits_are_reproducible_from_the_seed() {
+        let total = U256::exp10(18);
+        let seeded = |seed| plan(AmountStrategy::Random { total, seed: Some(seed) }, 10).unwrap();
+        let split = seeded(42);
+        assert_eq!(split.seed, Some(42));
+        assert_eq!(split.amounts, seeded(42).amounts);
+        assert_ne!(split.amounts, seeded(43).amounts);
+        assert_eq!(split.total_amount, total);
+
+        let drawn = plan(AmountStrategy::Random { total, seed: None }, 10).unwrap();
+        assert_eq!(drawn.amounts, seeded(drawn.seed.unwrap()).amounts);
+    }
+
+    #[test]
+    fn explicit_amounts_are_checked() {
+        let per_child = AmountStrategy::PerChild { amounts: wei(&[5, 6, 7]) };
+        let split = plan(per_child.clone(), 3).unwrap();
+        assert_eq!(split.amounts, wei(&[5, 6, 7]));
+        assert_eq!(split.total_amount, U256::from(18));
+        assert!(plan(per_child, 2).is_err());
+        assert!(plan(AmountStrategy::PerChild { amounts: wei(&[5, 0]) }, 2).is_err());
+        assert!(plan(weighted(10, &[1, 2, 3]), 2).is_err());
+        assert!(plan(equal(10), 0).is_err());
+    }
+
+    #[test]
+    fn deserializes_the_flattened_form() {
+        let plan: AmountPlan = serde_json::from_str(r#"{ "strategy": "equal", "total": "0x3e8", "gas_reserve": "100" }"#).unwrap();
+        assert_eq!(plan.strategy, equal(1_000));
+        assert_eq!(plan.gas_reserve, Some(U256::from(100)));
+        assert_eq!(plan.min_amount, None);
+    }
+}
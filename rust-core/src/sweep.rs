@@ -0,0 +1,272 @@
+//! Sweeping split children back into one address, to retire or rotate a
+//! split set.
+//!
+//! Each funded child signs a transfer of its balance minus the maximum fee.
+//! A legacy gas price empties the child exactly; under EIP-1559 the child
+//! keeps whatever part of `max_fee_per_gas` the block did not charge.
+
+use crate::address;
+use crate::distribution::{self, FeePolicy};
+use crate::error::Error;
+use crate::hd_wallet::HdWallet;
+use crate::transaction::{quantity, SignedTransaction};
+use crate::utils;
+use crate::WalletInfo;
+use primitive_types::U256;
+use serde::{Serialize, Deserialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SweepParams {
+    pub chain_id: u64,
+    pub destination: String,
+    pub fees: FeePolicy,
+    #[serde(default = "distribution::default_gas_limit")]
+    pub gas_limit: u64,
+}
+
+/// A child's balance and next nonce, as fetched from the chain.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ChildBalance {
+    pub address: String,
+    #[serde(with = "quantity")]
+    pub balance: U256,
+    #[serde(default)]
+    pub nonce: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// The balance does not exceed the fee; children without a balance entry count as empty.
+    BelowFee,
+    /// The child is the destination itself.
+    Destination,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SweepEntry {
+    pub from: String,
+    pub nonce: u64,
+    #[serde(with = "quantity")]
+    pub value: U256,
+    #[serde(with = "quantity")]
+    pub max_fee: U256,
+    pub hash: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SkippedChild {
+    pub address: String,
+    #[serde(with = "quantity")]
+    pub balance: U256,
+    pub reason: SkipReason,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SweepSummary {
+    pub chain_id: u64,
+    pub destination: String,
+    pub swept_count: usize,
+    pub skipped_count: usize,
+    /// Balance of every child, swept or not.
+    #[serde(with = "quantity")]
+    pub total_balance: U256,
+    /// What reaches `destination`.
+    #[serde(with = "quantity")]
+    pub total_recovered: U256,
+    #[serde(with = "quantity")]
+    pub max_total_fee: U256,
+    pub created_at_ms: u64,
+}
+
+/// One signed transaction per swept child, each from its own account, so
+/// they can be broadcast in any order.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SweepBatch {
+    pub summary: SweepSummary,
+    pub entries: Vec<SweepEntry>,
+    pub skipped: Vec<SkippedChild>,
+    pub transactions: Vec<SignedTransaction>,
+}
+
+fn overflow() -> Error {
+    Error::invalid_argument("balances", "total overflows 256 bits")
+}
+
+/// Signs "send max" transfers from `children` to `params.destination`,
+/// matching `balances` to children by address.
+pub fn sign_sweep(children: &[WalletInfo], balances: &[ChildBalance], params: &SweepParams) -> Result<SweepBatch, Error> {
+    let destination = address::parse_address(&params.destination)?;
+    let fee = params.fees.max_fee(params.gas_limit)?;
+
+    let mut entries = Vec::new();
+    let mut skipped = Vec::new();
+    let mut transactions = Vec::new();
+    let mut total_balance = U256::zero();
+    let mut total_recovered = U256::zero();
+    for child in children {
+        let (balance, nonce) = balances
+            .iter()
+            .find(|entry| entry.address.eq_ignore_ascii_case(&child.address))
+            .map_or((U256::zero(), 0), |entry| (entry.balance, entry.nonce));
+        total_balance = total_balance.checked_add(balance).ok_or_else(overflow)?;
+
+        let reason = if address::parse_address(&child.address)? == destination {
+            Some(SkipReason::Destination)
+        } else if balance <= fee {
+            Some(SkipReason::BelowFee)
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            skipped.push(SkippedChild { address: child.address.clone(), balance, reason });
+            continue;
+        }
+
+        let value = balance - fee;
+        let signed = params
+            .fees
+            .transfer(params.chain_id, nonce, &params.destination, value, params.gas_limit)
+            .sign(&child.private_key)?;
+        total_recovered += value;
+        entries.push(SweepEntry {
+            from: child.address.clone(),
+            nonce,
+            value,
+            max_fee: fee,
+            hash: signed.hash.clone(),
+        });
+        transactions.push(signed);
+    }
+
+    Ok(SweepBatch {
+        summary: SweepSummary {
+            chain_id: params.chain_id,
+            destination: params.destination.clone(),
+            swept_count: entries.len(),
+            skipped_count: skipped.len(),
+            total_balance,
+            total_recovered,
+            max_total_fee: fee.checked_mul(U256::from(entries.len())).ok_or_else(overflow)?,
+            created_at_ms: utils::now_millis(),
+        },
+        entries,
+        skipped,
+        transactions,
+    })
+}
+
+/// Sweeps children `start..start + count` of `wallet`.
+pub fn sign_split_sweep(
+    wallet: &HdWallet,
+    start: u32,
+    count: u32,
+    balances: &[ChildBalance],
+    params: &SweepParams,
+) -> Result<SweepBatch, Error> {
+    sign_sweep(&wallet.children_range(start, count)?, balances, params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HARDHAT: &str = "test test test test test test test test test test test junk";
+
+    fn gwei(amount: u64) -> U256 {
+        U256::from(amount) * U256::exp10(9)
+    }
+
+    fn balance(child: &WalletInfo, balance: U256, nonce: u64) -> ChildBalance {
+        ChildBalance { address: child.address.to_lowercase(), balance, nonce }
+    }
+
+    /// Children 1 to 4: a funded one, one holding exactly the fee, one with no
+    /// balance entry, and the destination.
+    fn sweep(fees: FeePolicy) -> (Vec<WalletInfo>, SweepBatch, U256) {
+        let wallet = HdWallet::from_mnemonic(HARDHAT, None).unwrap();
+        let children = wallet.children_range(1, 4).unwrap();
+        let params = SweepParams {
+            chain_id: 1,
+            destination: children[3].address.clone(),
+            fees,
+            gas_limit: 21_000,
+        };
+        let fee = fees.max_fee(21_000).unwrap();
+        let balances = [
+            balance(&children[0], U256::exp10(18), 3),
+            balance(&children[1], fee, 0),
+            balance(&children[3], U256::exp10(18), 0),
+        ];
+        let batch = sign_split_sweep(&wallet, 1, 4, &balances, &params).unwrap();
+        (children, batch, fee)
+    }
+
+    #[test]
+    fn sends_the_balance_minus_the_max_fee() {
+        let fees = FeePolicy::Legacy { gas_price: gwei(10) };
+        let (children, batch, fee) = sweep(fees);
+        assert_eq!(fee, gwei(210_000));
+
+        assert_eq!(batch.entries.len(), 1);
+        let entry = &batch.entries[0];
+        assert_eq!(entry.from, children[0].address);
+        assert_eq!(entry.nonce, 3);
+        assert_eq!(entry.value, U256::exp10(18) - fee);
+        assert_eq!(entry.max_fee, fee);
+
+        let signed = &batch.transactions[0];
+        assert_eq!(signed.hash, entry.hash);
+        assert_eq!(signed.from, children[0].address);
+        let rebuilt = fees
+            .transfer(1, 3, &children[3].address, entry.value, 21_000)
+            .sign(&children[0].private_key)
+            .unwrap();
+        assert_eq!(rebuilt.raw, signed.raw);
+
+        let summary = &batch.summary;
+        assert_eq!((summary.swept_count, summary.skipped_count), (1, 3));
+        assert_eq!(summary.total_balance, U256::exp10(18) * 2 + fee);
+        assert_eq!(summary.total_recovered, U256::exp10(18) - fee);
+        assert_eq!(summary.max_total_fee, fee);
+    }
+
+    #[test]
+    fn skips_dust_missing_balances_and_the_destination() {
+        let (children, batch, fee) = sweep(FeePolicy::Legacy { gas_price: gwei(10) });
+        let skipped: Vec<_> = batch.skipped.iter().map(|child| (child.address.as_str(), child.balance, child.reason)).collect();
+        assert_eq!(
+            skipped,
+            [
+                (children[1].address.as_str(), fee, SkipReason::BelowFee),
+                (children[2].address.as_str(), U256::zero(), SkipReason::BelowFee),
+                (children[3].address.as_str(), U256::exp10(18), SkipReason::Destination),
+            ]
+        );
+    }
+
+    #[test]
+    fn eip1559_reserves_the_max_fee_per_gas() {
+        let fees = FeePolicy::Eip1559 { max_fee_per_gas: gwei(30), max_priority_fee_per_gas: gwei(2) };
+        let (_, batch, fee) = sweep(fees);
+        assert_eq!(fee, gwei(30) * 21_000);
+        assert_eq!(batch.entries[0].value, U256::exp10(18) - fee);
+        assert!(batch.transactions[0].raw.starts_with("0x02"));
+
+        let (_, legacy, _) = sweep(FeePolicy::Legacy { gas_price: gwei(30) });
+        assert_eq!(legacy.entries[0].value, batch.entries[0].value);
+        assert!(!legacy.transactions[0].raw.starts_with("0x0"));
+    }
+
+    #[test]
+    fn rejects_a_bad_destination() {
+        let wallet = HdWallet::from_mnemonic(HARDHAT, None).unwrap();
+        let params = SweepParams {
+            chain_id: 1,
+            destination: "0x1234".to_string(),
+            fees: FeePolicy::Legacy { gas_price: gwei(1) },
+            gas_limit: 21_000,
+        };
+        assert!(sign_split_sweep(&wallet, 1, 2, &[], &params).is_err());
+    }
+}
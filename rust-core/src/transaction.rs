@@ -0,0 +1,487 @@
+//! Building and signing Ethereum transactions, so keys never have to leave
+//! the core to send funds.
+//!
+//! Supports legacy transactions with EIP-155 replay protection, EIP-2930
+//! access-list transactions and EIP-1559 (type 2) fee-market transactions.
+
+use crate::address;
+use crate::error::Error;
+use crate::secret::PrivateKey;
+use crate::signing::{self, Signature};
+use crate::utils;
+use primitive_types::U256;
+use rlp::RlpStream;
+use serde::{Serialize, Deserialize};
+
+pub const ETHEREUM_MAINNET_CHAIN_ID: u64 = 1;
+pub const BASE_SEPOLIA_CHAIN_ID: u64 = 84532;
+pub const LISK_SEPOLIA_CHAIN_ID: u64 = 4202;
+
+/// Gas for a plain ETH transfer to an account without code.
+pub const TRANSFER_GAS_LIMIT: u64 = 21_000;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TxType {
+    /// Type 0, signed with EIP-155.
+    Legacy,
+    /// Type 1.
+    Eip2930,
+    /// Type 2.
+    #[default]
+    Eip1559,
+}
+
+impl TxType {
+    fn envelope(self) -> Option<u8> {
+        match self {
+            TxType::Legacy => None,
+            TxType::Eip2930 => Some(0x01),
+            TxType::Eip1559 => Some(0x02),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AccessListItem {
+    pub address: String,
+    #[serde(default)]
+    pub storage_keys: Vec<String>,
+}
+
+/// An unsigned transaction. Quantities are wei and (de)serialize as decimal
+/// strings; `0x` hex strings and plain numbers are accepted too.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Transaction {
+    #[serde(rename = "type", default)]
+    pub tx_type: TxType,
+    pub chain_id: u64,
+    pub nonce: u64,
+    /// `None` deploys a contract.
+    #[serde(default)]
+    pub to: Option<String>,
+    #[serde(default, with = "quantity")]
+    pub value: U256,
+    /// Calldata as hex.
+    #[serde(default)]
+    pub data: String,
+    pub gas_limit: u64,
+    /// Required for legacy and EIP-2930 transactions.
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "quantity::option")]
+    pub gas_price: Option<U256>,
+    /// Required for EIP-1559 transactions.
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "quantity::option")]
+    pub max_fee_per_gas: Option<U256>,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "quantity::option")]
+    pub max_priority_fee_per_gas: Option<U256>,
+    /// Ignored by legacy transactions.
+    #[serde(default)]
+    pub access_list: Vec<AccessListItem>,
+}
+
+/// A signed transaction ready for `eth_sendRawTransaction`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SignedTransaction {
+    /// The raw signed bytes, `0x`-prefixed.
+    pub raw: String,
+    /// keccak256 of `raw`; the hash block explorers show.
+    pub hash: String,
+    pub from: String,
+    pub nonce: u64,
+}
+
+/// Serde for 256-bit wei amounts.
+pub mod quantity {
+    use crate::error::Error;
+    use primitive_types::U256;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    /// Parses a decimal or `0x`-prefixed hex quantity.
+    pub fn parse(quantity: &str) -> Result<U256, Error> {
+        let quantity = quantity.trim();
+        let parsed = match quantity.strip_prefix("0x").or_else(|| quantity.strip_prefix("0X")) {
+            Some(hex) => U256::from_str_radix(hex, 16).map_err(|e| e.to_string()),
+            None => U256::from_dec_str(quantity).map_err(|e| format!("{:?}", e)),
+        };
+        parsed.map_err(|reason| Error::invalid_argument("quantity", format!("{} ({})", quantity, reason)))
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Number(u64),
+        Text(String),
+    }
+
+    impl Raw {
+        fn into_u256(self) -> Result<U256, Error> {
+            match self {
+                Raw::Number(n) => Ok(U256::from(n)),
+                Raw::Text(text) => parse(&text),
+            }
+        }
+    }
+
+    pub fn serialize<S: Serializer>(value: &U256, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&value.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<U256, D::Error> {
+        Raw::deserialize(deserializer)?.into_u256().map_err(serde::de::Error::custom)
+    }
+
+    pub mod option {
+        use super::Raw;
+        use primitive_types::U256;
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(value: &Option<U256>, serializer: S) -> Result<S::Ok, S::Error> {
+            match value {
+                Some(value) => super::serialize(value, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<U256>, D::Error> {
+            Option::<Raw>::deserialize(deserializer)?
+                .map(Raw::into_u256)
+                .transpose()
+                .map_err(serde::de::Error::custom)
+        }
+    }
+
+    pub mod vec {
+        use super::Raw;
+        use primitive_types::U256;
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(values: &[U256], serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(values.iter().map(U256::to_string))
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<U256>, D::Error> {
+            Vec::<Raw>::deserialize(deserializer)?
+                .into_iter()
+                .map(Raw::into_u256)
+                .collect::<Result<_, _>>()
+                .map_err(serde::de::Error::custom)
+        }
+    }
+}
+
+fn decode_hex(argument: &str, value: &str) -> Result<Vec<u8>, Error> {
+    let value = value.trim();
+    hex::decode(value.strip_prefix("0x").unwrap_or(value)).map_err(|e| Error::invalid_argument(argument, e))
+}
+
+fn required(value: Option<U256>, field: &str, tx_type: TxType) -> Result<U256, Error> {
+    value.ok_or_else(|| Error::invalid_argument(field, format!("required for {:?} transactions", tx_type)))
+}
+
+impl Transaction {
+    /// A plain ETH transfer with the given EIP-1559 fees.
+    pub fn transfer(chain_id: u64, nonce: u64, to: &str, value: U256, max_fee_per_gas: U256, max_priority_fee_per_gas: U256) -> Self {
+        Transaction {
+            tx_type: TxType::Eip1559,
+            chain_id,
+            nonce,
+            to: Some(to.to_string()),
+            value,
+            data: String::new(),
+            gas_limit: TRANSFER_GAS_LIMIT,
+            gas_price: None,
+            max_fee_per_gas: Some(max_fee_per_gas),
+            max_priority_fee_per_gas: Some(max_priority_fee_per_gas),
+            access_list: Vec::new(),
+        }
+    }
+
+    /// The most this transaction can cost in fees: `gas_limit` times the gas
+    /// price, or times `max_fee_per_gas` for EIP-1559.
+    pub fn max_fee(&self) -> Result<U256, Error> {
+        let price = match self.tx_type {
+            TxType::Legacy | TxType::Eip2930 => required(self.gas_price, "gas_price", self.tx_type)?,
+            TxType::Eip1559 => required(self.max_fee_per_gas, "max_fee_per_gas", self.tx_type)?,
+        };
+        price
+            .checked_mul(U256::from(self.gas_limit))
+            .ok_or_else(|| Error::invalid_argument("gas_limit", "fee overflows 256 bits"))
+    }
+
+    /// Appends the fields every type shares, in their type-specific order,
+    /// leaving the signature to the caller.
+    fn append_fields(&self, stream: &mut RlpStream) -> Result<(), Error> {
+        if self.chain_id == 0 {
+            return Err(Error::invalid_argument("chain_id", "must not be 0"));
+        }
+        let to = match &self.to {
+            Some(to) => address::parse_address(to)?.to_vec(),
+            None => Vec::new(),
+        };
+        let data = decode_hex("data", &self.data)?;
+
+        match self.tx_type {
+            TxType::Legacy => {
+                stream.append(&self.nonce);
+                stream.append(&required(self.gas_price, "gas_price", self.tx_type)?);
+            }
+            TxType::Eip2930 => {
+                stream.append(&self.chain_id);
+                stream.append(&self.nonce);
+                stream.append(&required(self.gas_price, "gas_price", self.tx_type)?);
+            }
+            TxType::Eip1559 => {
+                let max_fee = required(self.max_fee_per_gas, "max_fee_per_gas", self.tx_type)?;
+                let priority_fee = required(self.max_priority_fee_per_gas, "max_priority_fee_per_gas", self.tx_type)?;
+                if priority_fee > max_fee {
+                    return Err(Error::invalid_argument(
+                        "max_priority_fee_per_gas",
+                        "must not exceed max_fee_per_gas",
+                    ));
+                }
+                stream.append(&self.chain_id);
+                stream.append(&self.nonce);
+                stream.append(&priority_fee);
+                stream.append(&max_fee);
+            }
+        }
+        stream.append(&self.gas_limit);
+        stream.append(&to);
+        stream.append(&self.value);
+        stream.append(&data);
+
+        if self.tx_type != TxType::Legacy {
+            stream.begin_list(self.access_list.len());
+            for item in &self.access_list {
+                stream.begin_list(2);
+                stream.append(&address::parse_address(&item.address)?.to_vec());
+                stream.begin_list(item.storage_keys.len());
+                for key in &item.storage_keys {
+                    let key = decode_hex("storage key", key)?;
+                    if key.len() != 32 {
+                        return Err(Error::invalid_argument("storage key", "must be 32 bytes"));
+                    }
+                    stream.append(&key);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn field_count(&self) -> usize {
+        match self.tx_type {
+            TxType::Legacy => 6,
+            TxType::Eip2930 => 8,
+            TxType::Eip1559 => 9,
+        }
+    }
+
+    /// Prefixes typed payloads with their EIP-2718 type byte.
+    fn envelope(&self, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(payload.len() + 1);
+        bytes.extend(self.tx_type.envelope());
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    /// The hash the sender signs.
+    pub fn signing_hash(&self) -> Result<[u8; 32], Error> {
+        let mut stream = RlpStream::new();
+        if self.tx_type == TxType::Legacy {
+            // EIP-155: chain_id, 0, 0 stand in for v, r, s.
+            stream.begin_list(9);
+            self.append_fields(&mut stream)?;
+            stream.append(&self.chain_id);
+            stream.append(&0u8);
+            stream.append(&0u8);
+        } else {
+            stream.begin_list(self.field_count());
+            self.append_fields(&mut stream)?;
+        }
+        Ok(utils::keccak256(&self.envelope(&stream.out())))
+    }
+
+    /// The signed transaction bytes for `signature` over [`Transaction::signing_hash`].
+    pub fn encode_signed(&self, signature: &Signature) -> Result<Vec<u8>, Error> {
+        let mut stream = RlpStream::new();
+        stream.begin_list(self.field_count() + 3);
+        self.append_fields(&mut stream)?;
+        if self.tx_type == TxType::Legacy {
+            let v = self
+                .chain_id
+                .checked_mul(2)
+                .and_then(|v| v.checked_add(35 + u64::from(signature.recovery_id)))
+                .ok_or_else(|| Error::invalid_argument("chain_id", "too large for EIP-155"))?;
+            stream.append(&v);
+        } else {
+            stream.append(&signature.recovery_id);
+        }
+        stream.append(&U256::from_big_endian(&signature.r));
+        stream.append(&U256::from_big_endian(&signature.s));
+        Ok(self.envelope(&stream.out()))
+    }
+
+    pub fn sign(&self, key: &PrivateKey) -> Result<SignedTransaction, Error> {
+        let signature = signing::sign_hash(key, &self.signing_hash()?);
+        let raw = self.encode_signed(&signature)?;
+        let public_key = secp256k1::PublicKey::from_secret_key(&secp256k1::Secp256k1::signing_only(), &key.to_secret_key());
+
+        Ok(SignedTransaction {
+            hash: format!("0x{}", hex::encode(utils::keccak256(&raw))),
+            raw: format!("0x{}", hex::encode(&raw)),
+            from: utils::public_key_to_address(&public_key),
+            nonce: self.nonce,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The key and recipient of the EIP-155 example.
+    const KEY: &str = "4646464646464646464646464646464646464646464646464646464646464646";
+    const FROM: &str = "0x9d8A62f656a8d1615C1294fd71e9CFb3E4855A4F";
+    const TO: &str = "0x3535353535353535353535353535353535353535";
+
+    fn key() -> PrivateKey {
+        PrivateKey::from_hex(KEY).unwrap()
+    }
+
+    fn gwei(amount: u64) -> U256 {
+        U256::from(amount) * U256::exp10(9)
+    }
+
+    fn transaction(tx_type: TxType) -> Transaction {
+        Transaction {
+            tx_type,
+            chain_id: ETHEREUM_MAINNET_CHAIN_ID,
+            nonce: 9,
+            to: Some(TO.to_string()),
+            value: U256::exp10(18),
+            data: String::new(),
+            gas_limit: TRANSFER_GAS_LIMIT,
+            gas_price: None,
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            access_list: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn eip155_example() {
+        let transaction = Transaction { gas_price: Some(gwei(20)), ..transaction(TxType::Legacy) };
+        assert_eq!(
+            hex::encode(transaction.signing_hash().unwrap()),
+            "daf5a779ae972f972197303d7b574746c7ef83eadac0f2791ad23db92e4c8e53"
+        );
+        let signed = transaction.sign(&key()).unwrap();
+        assert_eq!(
+            signed.raw,
+            "0xf86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83"
+        );
+        assert_eq!(signed.from, FROM);
+        assert_eq!(signed.nonce, 9);
+    }
+
+    // The expected bytes and hashes of the typed transactions come from an
+    // independent RLP, keccak and RFC 6979 implementation.
+    #[test]
+    fn eip2930_access_list_transaction() {
+        let transaction = Transaction {
+            nonce: 0,
+            value: U256::one(),
+            gas_limit: 30_000,
+            gas_price: Some(gwei(20)),
+            access_list: vec![AccessListItem {
+                address: "0x0000000000000000000000000000000000000001".to_string(),
+                storage_keys: vec![format!("0x{}", "00".repeat(32))],
+            }],
+            ..transaction(TxType::Eip2930)
+        };
+        assert_eq!(
+            hex::encode(transaction.signing_hash().unwrap()),
+            "3830054459d7ab5c4f5c2c508cb26b7dff44b1c624e929fef3be0c3440bb5b3e"
+        );
+        let signed = transaction.sign(&key()).unwrap();
+        assert_eq!(
+            signed.raw,
+            "0x01f89f01808504a817c8008275309435353535353535353535353535353535353535350180f838f7940000000000000000000000000000000000000001e1a0000000000000000000000000000000000000000000000000000000000000000080a0a20e7baa6d4f30763e20a159972d6c6f0b75016519109fc1fd4809618ff51028a030a246f93edaeec86a5a6f81c108c5e85a29247ea0a33ac0ba2e837b5c543141"
+        );
+        assert_eq!(signed.hash, "0x4b937c8497add2bbe2e673cf09ca4632ad1538d9abaae7940b474fd615a28276");
+    }
+
+    #[test]
+    fn eip1559_transfer() {
+        let transaction = Transaction::transfer(ETHEREUM_MAINNET_CHAIN_ID, 9, TO, U256::exp10(18), gwei(30), gwei(1));
+        assert_eq!(
+            hex::encode(transaction.signing_hash().unwrap()),
+            "61b515f42ee083d21625f1465de93314f0df919cee4e9689347e3e6387f4eeea"
+        );
+        let signed = transaction.sign(&key()).unwrap();
+        assert_eq!(
+            signed.raw,
+            "0x02f8730109843b9aca008506fc23ac00825208943535353535353535353535353535353535353535880de0b6b3a764000080c001a0ad4241a480b4069450d9bd1823a3afb8bd5a697e2135056f13fc85778785a013a0782c2fc38f01d18de96e4f5caeb077484ef6d844f2ce873a475515552460c075"
+        );
+        assert_eq!(signed.hash, "0x8a3ff34f002402f5d26a11e21f0efcd1810e189a4ba6555cf013579c8ac06727");
+        assert_eq!(transaction.max_fee().unwrap(), gwei(30) * 21_000);
+    }
+
+    fn raw_bytes(signed: &SignedTransaction) -> Vec<u8> {
+        hex::decode(&signed.raw[2..]).unwrap()
+    }
+
+    #[test]
+    fn eip155_on_the_split_testnets() {
+        let vectors = [
+            (
+                BASE_SEPOLIA_CHAIN_ID,
+                "37d8fa5decc62dc5d96973d3c2fcca64da808c6b05f9c126f03509fc1366aef0",
+                "0xf86f098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a7640000808302948ca0ffefbd3fd4d316563b2247c5e864587ddb23d2a42ab90d60726db1100fe45f50a0185581ec2a8ffbc5fc0d21d6cb9734da804fa7b29671d44439697b4b7eefc982",
+            ),
+            (
+                LISK_SEPOLIA_CHAIN_ID,
+                "e5bdcb090631c04b0e844d85bed0983324dff7413444c793291f46ef273852ab",
+                "0xf86e098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a7640000808220f8a08bb5ebf7ebde4819985214774dc6918737eb4a387d189140866cacdce52693bca033b332fec50a13de676836a9bebdc2a97dfbae69e857d188a7cf66d8e65e68ad",
+            ),
+        ];
+        for (chain_id, hash, raw) in vectors {
+            let transaction = Transaction { chain_id, gas_price: Some(gwei(20)), ..transaction(TxType::Legacy) };
+            assert_eq!(hex::encode(transaction.signing_hash().unwrap()), hash);
+            let signed = transaction.sign(&key()).unwrap();
+            assert_eq!(signed.raw, raw);
+            assert_eq!(signed.from, FROM);
+
+            let v: u64 = rlp::Rlp::new(&raw_bytes(&signed)).val_at(6).unwrap();
+            assert!(v == chain_id * 2 + 35 || v == chain_id * 2 + 36, "v = {} on chain {}", v, chain_id);
+        }
+    }
+
+    #[test]
+    fn eip1559_encodes_the_chain_id() {
+        let transaction = Transaction::transfer(BASE_SEPOLIA_CHAIN_ID, 9, TO, U256::exp10(18), gwei(30), gwei(1));
+        let signed = transaction.sign(&key()).unwrap();
+        assert_eq!(
+            signed.raw,
+            "0x02f87683014a3409843b9aca008506fc23ac00825208943535353535353535353535353535353535353535880de0b6b3a764000080c080a0bc770c1d9815568b5a6c2c14937c8ecdcd351f6068d1653e143f43ed765633c0a07ce2a3341b1c98171f05e47f51e729b6d3dc564dd9866dc4f2c5b659cf50e520"
+        );
+        assert_eq!(signed.hash, "0x37586f9682c6dd44a01f32fdb2580c131db03f35e7fbcd4b934150c83baaf158");
+
+        let raw = raw_bytes(&signed);
+        assert_eq!(raw[0], 0x02);
+        let chain_id: u64 = rlp::Rlp::new(&raw[1..]).val_at(0).unwrap();
+        assert_eq!(chain_id, BASE_SEPOLIA_CHAIN_ID);
+    }
+
+    #[test]
+    fn rejects_incomplete_fees() {
+        assert!(transaction(TxType::Legacy).signing_hash().is_err());
+        assert!(transaction(TxType::Eip2930).signing_hash().is_err());
+        assert!(transaction(TxType::Eip1559).signing_hash().is_err());
+
+        let inverted = Transaction::transfer(ETHEREUM_MAINNET_CHAIN_ID, 0, TO, U256::one(), gwei(1), gwei(2));
+        assert!(inverted.signing_hash().is_err());
+        let no_chain = Transaction::transfer(0, 0, TO, U256::one(), gwei(2), gwei(1));
+        assert!(no_chain.signing_hash().is_err());
+    }
+}
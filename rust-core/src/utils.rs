@@ -1,7 +1,9 @@
+use crate::address;
 use secp256k1::PublicKey;
 use sha2::{Sha256, Digest};
-use sha3::{Keccak256, Digest as KeccakDigest};
+use sha3::Keccak256;
 
+/// EIP-55 checksummed address of `public_key`.
 pub fn public_key_to_address(public_key: &PublicKey) -> String {
     let public_key_bytes = public_key.serialize_uncompressed();
     
@@ -9,13 +11,12 @@ pub fn public_key_to_address(public_key: &PublicKey) -> String {
     let public_key_hash = &public_key_bytes[1..];
     
     // Keccak256 hash
-    let mut hasher = Keccak256::new();
-    hasher.update(public_key_hash);
-    let result = hasher.finalize();
+    let result = keccak256(public_key_hash);
     
-    // Take the last 20 bytes and convert to hex
-    let address_bytes = &result[12..];
-    format!("0x{}", hex::encode(address_bytes))
+    // Take the last 20 bytes and checksum them
+    let mut address_bytes = [0u8; 20];
+    address_bytes.copy_from_slice(&result[12..]);
+    address::to_checksum_address(&address_bytes, None)
 }
 
 pub fn keccak256(data: &[u8]) -> [u8; 32] {
@@ -30,4 +31,27 @@ pub fn sha256(data: &[u8]) -> [u8; 32] {
     hasher.update(data);
     let result = hasher.finalize();
     result.into()
-} 
\ No newline at end of file
+}
+
+/// Milliseconds since the Unix epoch. `std::time` is unavailable in the
+/// browser, so WASM builds ask JS instead.
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+pub fn now_millis() -> u64 {
+    js_sys::Date::now() as u64
+}
+
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+pub fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// RIPEMD160(SHA256(data)), as used for BIP32 key fingerprints.
+pub fn hash160(data: &[u8]) -> [u8; 20] {
+    let mut hasher = ripemd::Ripemd160::new();
+    hasher.update(sha256(data));
+    let result = hasher.finalize();
+    result.into()
+}
@@ -0,0 +1,320 @@
+use crate::bip39;
+use crate::error::Error;
+use crate::secret::SecretPhrase;
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use rand::RngCore;
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+use zeroize::Zeroizing;
+
+// Layout, integers big-endian:
+//
+//   magic "DWSV" | version u8 | kdf u8 | memory_kib u32 | iterations u32 |
+//   parallelism u32 | salt [16] | nonce [24] | ciphertext || tag [16]
+//
+// Everything before the ciphertext is authenticated as associated data, so
+// tampering with the KDF parameters fails the same way a wrong password does.
+const MAGIC: &[u8; 4] = b"DWSV";
+const VERSION: u8 = 1;
+const KDF_ARGON2ID: u8 = 1;
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 24;
+const HEADER_LENGTH: usize = 4 + 1 + 1 + 12 + SALT_LENGTH + NONCE_LENGTH;
+const TAG_LENGTH: usize = 16;
+
+/// Upper bounds accepted when opening, so a crafted vault cannot make the
+/// device allocate or spin without limit. 256 MiB is four times the default
+/// and still within what a wasm32 or mobile host will hand out.
+const MAX_MEMORY_KIB: u32 = 256 * 1024;
+const MAX_ITERATIONS: u32 = 64;
+const MAX_PARALLELISM: u32 = 16;
+
+/// Argon2id cost parameters. Missing fields deserialize from [`VaultParams::DEFAULT`].
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct VaultParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl VaultParams {
+    /// RFC 9106's 64 MiB, t = 3 profile, with one lane since WASM runs on a
+    /// single thread anyway.
+    pub const DEFAULT: VaultParams = VaultParams { memory_kib: 64 * 1024, iterations: 3, parallelism: 1 };
+    /// OWASP's floor for Argon2id: 19 MiB, t = 2. New vaults may not go lower.
+    pub const MINIMUM: VaultParams = VaultParams { memory_kib: 19 * 1024, iterations: 2, parallelism: 1 };
+
+    fn check_minimum(&self) -> Result<(), Error> {
+        if self.memory_kib < Self::MINIMUM.memory_kib || self.iterations < Self::MINIMUM.iterations {
+            return Err(Error::invalid_argument(
+                "vault params",
+                format!(
+                    "at least {} KiB and {} iterations are required",
+                    Self::MINIMUM.memory_kib,
+                    Self::MINIMUM.iterations
+                ),
+            ));
+        }
+        self.check_maximum().map_err(|e| Error::invalid_argument("vault params", e))
+    }
+
+    fn check_maximum(&self) -> Result<(), String> {
+        if self.memory_kib > MAX_MEMORY_KIB || self.iterations > MAX_ITERATIONS || self.parallelism > MAX_PARALLELISM {
+            return Err(format!(
+                "at most {} KiB, {} iterations and {} lanes are supported",
+                MAX_MEMORY_KIB, MAX_ITERATIONS, MAX_PARALLELISM
+            ));
+        }
+        Ok(())
+    }
+
+    fn argon2(&self) -> Result<Argon2<'static>, String> {
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, Some(32)).map_err(|e| e.to_string())?;
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+    }
+}
+
+impl Default for VaultParams {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// What a vault holds. Everything here is encrypted, metadata included.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct VaultContents {
+    #[serde(with = "crate::secret::exposed")]
+    pub mnemonic: SecretPhrase,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub passphrase: Option<Zeroizing<String>>,
+    /// Free-form app data, e.g. a wallet name or the split settings.
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+}
+
+/// The unencrypted part of a vault, readable without the password.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VaultHeader {
+    pub version: u8,
+    pub params: VaultParams,
+}
+
+impl VaultHeader {
+    /// Whether the vault was sealed with weaker parameters than `params`;
+    /// if so the app should [`rekey`] it with the same password.
+    pub fn needs_upgrade(&self, params: &VaultParams) -> bool {
+        self.params.memory_kib < params.memory_kib || self.params.iterations < params.iterations
+    }
+}
+
+fn invalid(reason: impl std::fmt::Display) -> Error {
+    Error::InvalidVault { reason: reason.to_string() }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(bytes[offset..offset + 4].try_into().expect("4 bytes"))
+}
+
+/// Parses and bounds-checks the header without touching the ciphertext.
+pub fn inspect(vault: &[u8]) -> Result<VaultHeader, Error> {
+    if vault.len() < HEADER_LENGTH + TAG_LENGTH || &vault[..4] != MAGIC {
+        return Err(invalid("not a dwss vault"));
+    }
+    if vault[4] != VERSION {
+        return Err(invalid(format!("unsupported version {}", vault[4])));
+    }
+    if vault[5] != KDF_ARGON2ID {
+        return Err(invalid(format!("unsupported kdf {}", vault[5])));
+    }
+    let params = VaultParams {
+        memory_kib: read_u32(vault, 6),
+        iterations: read_u32(vault, 10),
+        parallelism: read_u32(vault, 14),
+    };
+    params.check_maximum().map_err(invalid)?;
+    Ok(VaultHeader { version: VERSION, params })
+}
+
+fn derive_key(params: &VaultParams, password: &str, salt: &[u8]) -> Result<Zeroizing<[u8; 32]>, String> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    params
+        .argon2()?
+        .hash_password_into(password.as_bytes(), salt, &mut key[..])
+        .map_err(|e| e.to_string())?;
+    Ok(key)
+}
+
+/// Encrypts `contents` under `password`. The mnemonic is checked and stored in
+/// its normalized form.
+pub fn seal(contents: &VaultContents, password: &str, params: VaultParams) -> Result<Vec<u8>, Error> {
+    params.check_minimum()?;
+    let contents = VaultContents {
+        mnemonic: bip39::format_phrase(&bip39::parse_mnemonic(contents.mnemonic.expose())?),
+        ..contents.clone()
+    };
+
+    let mut rng = rand::thread_rng();
+    let mut salt = [0u8; SALT_LENGTH];
+    let mut nonce = [0u8; NONCE_LENGTH];
+    rng.fill_bytes(&mut salt);
+    rng.fill_bytes(&mut nonce);
+
+    let mut vault = Vec::with_capacity(HEADER_LENGTH + 256);
+    vault.extend_from_slice(MAGIC);
+    vault.push(VERSION);
+    vault.push(KDF_ARGON2ID);
+    vault.extend_from_slice(&params.memory_kib.to_be_bytes());
+    vault.extend_from_slice(&params.iterations.to_be_bytes());
+    vault.extend_from_slice(&params.parallelism.to_be_bytes());
+    vault.extend_from_slice(&salt);
+    vault.extend_from_slice(&nonce);
+
+    let key = derive_key(&params, password, &salt).map_err(|e| Error::invalid_argument("vault params", e))?;
+    let plaintext = Zeroizing::new(
+        serde_json::to_vec(&contents).map_err(|e| Error::SerializationFailed { reason: e.to_string() })?,
+    );
+    let ciphertext = XChaCha20Poly1305::new(key.as_ref().into())
+        .encrypt(XNonce::from_slice(&nonce), Payload { msg: &plaintext, aad: &vault })
+        .map_err(|_| Error::SerializationFailed { reason: "encryption failed".to_string() })?;
+    vault.extend_from_slice(&ciphertext);
+    Ok(vault)
+}
+
+/// Decrypts a vault. A wrong password and a tampered vault both fail
+/// authentication and are reported as `InvalidPassword`.
+pub fn open(vault: &[u8], password: &str) -> Result<VaultContents, Error> {
+    let header = inspect(vault)?;
+    let salt = &vault[18..18 + SALT_LENGTH];
+    let nonce = &vault[18 + SALT_LENGTH..HEADER_LENGTH];
+
+    let key = derive_key(&header.params, password, salt).map_err(invalid)?;
+    let plaintext = Zeroizing::new(
+        XChaCha20Poly1305::new(key.as_ref().into())
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload { msg: &vault[HEADER_LENGTH..], aad: &vault[..HEADER_LENGTH] },
+            )
+            .map_err(|_| Error::InvalidPassword)?,
+    );
+    serde_json::from_slice(&plaintext).map_err(|e| invalid(format!("unreadable contents: {}", e)))
+}
+
+/// Changes the password, re-encrypting with a fresh salt and nonce. `params`
+/// of `None` keeps the vault's current parameters.
+pub fn rekey(vault: &[u8], old_password: &str, new_password: &str, params: Option<VaultParams>) -> Result<Vec<u8>, Error> {
+    let params = match params {
+        Some(params) => params,
+        None => inspect(vault)?.params,
+    };
+    seal(&open(vault, old_password)?, new_password, params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HARDHAT: &str = "test test test test test test test test test test test junk";
+    const PASSWORD: &str = "correct horse battery staple";
+
+    fn contents(mnemonic: &str) -> VaultContents {
+        VaultContents {
+            mnemonic: SecretPhrase::new(mnemonic.to_string()),
+            passphrase: Some(Zeroizing::new("TREZOR".to_string())),
+            metadata: BTreeMap::from([("name".to_string(), "savings".to_string())]),
+        }
+    }
+
+    fn sealed() -> Vec<u8> {
+        seal(&contents(HARDHAT), PASSWORD, VaultParams::MINIMUM).unwrap()
+    }
+
+    #[test]
+    fn round_trips_normalized_contents() {
+        let vault = seal(&contents(&HARDHAT.to_uppercase()), PASSWORD, VaultParams::MINIMUM).unwrap();
+        assert_eq!(inspect(&vault).unwrap(), VaultHeader { version: VERSION, params: VaultParams::MINIMUM });
+
+        let opened = open(&vault, PASSWORD).unwrap();
+        assert_eq!(opened.mnemonic.expose(), HARDHAT);
+        assert_eq!(opened.passphrase.as_deref().map(String::as_str), Some("TREZOR"));
+        assert_eq!(opened.metadata, contents(HARDHAT).metadata);
+    }
+
+    #[test]
+    fn wrong_password_fails_authentication() {
+        assert!(matches!(open(&sealed(), "wrong password"), Err(Error::InvalidPassword)));
+    }
+
+    #[test]
+    fn tampered_header_fails_authentication() {
+        // iterations, salt and nonce: each is authenticated or changes the key.
+        for offset in [13, 18, 18 + SALT_LENGTH] {
+            let mut vault = sealed();
+            vault[offset] ^= 1;
+            assert!(matches!(open(&vault, PASSWORD), Err(Error::InvalidPassword)), "offset {}", offset);
+        }
+    }
+
+    #[test]
+    fn tampered_ciphertext_fails_authentication() {
+        let mut vault = sealed();
+        vault[HEADER_LENGTH] ^= 1;
+        assert!(matches!(open(&vault, PASSWORD), Err(Error::InvalidPassword)));
+
+        let mut vault = sealed();
+        *vault.last_mut().unwrap() ^= 1;
+        assert!(matches!(open(&vault, PASSWORD), Err(Error::InvalidPassword)));
+
+        let vault = sealed();
+        assert!(matches!(open(&vault[..vault.len() - 1], PASSWORD), Err(Error::InvalidPassword)));
+    }
+
+    #[test]
+    fn malformed_headers_are_rejected_before_the_kdf() {
+        let mut vault = sealed();
+        vault[0] = b'X';
+        assert!(matches!(inspect(&vault), Err(Error::InvalidVault { .. })));
+
+        let mut vault = sealed();
+        vault[4] = VERSION + 1;
+        assert!(matches!(open(&vault, PASSWORD), Err(Error::InvalidVault { .. })));
+
+        let mut vault = sealed();
+        vault[6..10].copy_from_slice(&(MAX_MEMORY_KIB + 1).to_be_bytes());
+        assert!(matches!(open(&vault, PASSWORD), Err(Error::InvalidVault { .. })));
+        vault[6..10].copy_from_slice(&(1u32 << 20).to_be_bytes());
+        assert!(matches!(open(&vault, PASSWORD), Err(Error::InvalidVault { .. })));
+
+        assert!(matches!(inspect(&sealed()[..HEADER_LENGTH]), Err(Error::InvalidVault { .. })));
+    }
+
+    #[test]
+    fn rekey_changes_the_password_and_params() {
+        let vault = sealed();
+        let upgraded = VaultParams { memory_kib: VaultParams::MINIMUM.memory_kib * 2, ..VaultParams::MINIMUM };
+        assert!(inspect(&vault).unwrap().needs_upgrade(&upgraded));
+
+        let rekeyed = rekey(&vault, PASSWORD, "new password", Some(upgraded)).unwrap();
+        assert_eq!(inspect(&rekeyed).unwrap().params, upgraded);
+        assert!(!inspect(&rekeyed).unwrap().needs_upgrade(&upgraded));
+        assert!(matches!(open(&rekeyed, PASSWORD), Err(Error::InvalidPassword)));
+        assert_eq!(open(&rekeyed, "new password").unwrap().mnemonic.expose(), HARDHAT);
+
+        let same_params = rekey(&vault, PASSWORD, "new password", None).unwrap();
+        assert_eq!(inspect(&same_params).unwrap().params, VaultParams::MINIMUM);
+        assert_ne!(same_params[18..HEADER_LENGTH], vault[18..HEADER_LENGTH]);
+
+        assert!(matches!(rekey(&vault, "wrong password", "new password", None), Err(Error::InvalidPassword)));
+    }
+
+    #[test]
+    fn seal_enforces_the_minimum_params() {
+        let weak = VaultParams { iterations: 1, ..VaultParams::MINIMUM };
+        assert!(matches!(seal(&contents(HARDHAT), PASSWORD, weak), Err(Error::InvalidArgument { .. })));
+        let oversized = VaultParams { memory_kib: MAX_MEMORY_KIB + 1, ..VaultParams::DEFAULT };
+        assert!(matches!(seal(&contents(HARDHAT), PASSWORD, oversized), Err(Error::InvalidArgument { .. })));
+        assert!(seal(&contents("test test test"), PASSWORD, VaultParams::MINIMUM).is_err());
+    }
+}
@@ -0,0 +1,1184 @@
+use crate::{
+    address, bip39, bip85, compat, derivation_path, distribution, entropy, extended_key, hd_wallet, keyring, keystore, recovery, secret,
+    slip39, split_plan, sweep, transaction, vault, watch_only, Error, WalletInfo, DEFAULT_SPLIT_CHILD_COUNT,
+    FIRST_SPLIT_CHILD,
+};
+use gloo_timers::callback::Timeout;
+use std::cell::{RefCell, RefMut};
+use std::rc::Rc;
+use std::time::Duration;
+use wasm_bindgen::prelude::*;
+use zeroize::Zeroizing;
+
+#[wasm_bindgen]
+pub fn generate_mnemonic(word_count: Option<u32>, language: Option<String>) -> Result<String, JsValue> {
+    let language = match language {
+        Some(code) => bip39::language_from_code(&code)
+            .map_err(JsValue::from)?,
+        None => ::bip39::Language::English,
+    };
+    
+    bip39::generate_mnemonic(language, word_count.map_or(bip39::DEFAULT_WORD_COUNT, |count| count as usize))
+        .map(|phrase| phrase.expose().to_string())
+        .map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+pub fn generate_mnemonic_from_entropy(
+    source: &str,
+    input: &str,
+    word_count: Option<u32>,
+    language: Option<String>,
+    mix_system_entropy: bool,
+) -> Result<JsValue, JsValue> {
+    let source = entropy::EntropySource::from_name(source)
+        .map_err(JsValue::from)?;
+    let language = match language {
+        Some(code) => bip39::language_from_code(&code)
+            .map_err(JsValue::from)?,
+        None => ::bip39::Language::English,
+    };
+    
+    let result = entropy::mnemonic_from_user_entropy(source, input, language, word_count.map_or(bip39::DEFAULT_WORD_COUNT, |count| count as usize), mix_system_entropy)
+        .map_err(JsValue::from)?;
+    
+    serde_wasm_bindgen::to_value(&result)
+        .map_err(|e| Error::from(e).into())
+}
+
+#[wasm_bindgen]
+pub fn detect_mnemonic_language(mnemonic: &str) -> Result<String, JsValue> {
+    bip39::detect_language(mnemonic)
+        .map(|language| bip39::language_code(language).to_string())
+        .map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+pub fn supported_mnemonic_languages() -> Result<JsValue, JsValue> {
+    serde_wasm_bindgen::to_value(&bip39::supported_language_codes())
+        .map_err(|e| Error::from(e).into())
+}
+
+/// Hands private keys to JS; prefer `KeyringSession`, which keeps them in WASM.
+#[wasm_bindgen]
+pub fn derive_parent_wallet(mnemonic: &str, passphrase: Option<String>) -> Result<JsValue, JsValue> {
+    let wallet = hd_wallet::derive_parent_wallet(mnemonic, passphrase.as_deref())
+        .map_err(JsValue::from)?;
+    
+    serde_wasm_bindgen::to_value(&wallet)
+        .map_err(|e| Error::from(e).into())
+}
+
+/// Hands private keys to JS; prefer `KeyringSession`, which keeps them in WASM.
+#[wasm_bindgen]
+pub fn derive_child_wallets(mnemonic: &str, count: u32, passphrase: Option<String>) -> Result<JsValue, JsValue> {
+    let wallets = hd_wallet::derive_child_wallets(mnemonic, passphrase.as_deref(), count)
+        .map_err(JsValue::from)?;
+    
+    serde_wasm_bindgen::to_value(&wallets)
+        .map_err(|e| Error::from(e).into())
+}
+
+#[wasm_bindgen]
+pub fn derive_child_wallets_range(mnemonic: &str, start: u32, count: u32, passphrase: Option<String>) -> Result<JsValue, JsValue> {
+    let wallets = hd_wallet::derive_child_wallets_range(mnemonic, passphrase.as_deref(), start, count)
+        .map_err(JsValue::from)?;
+    
+    serde_wasm_bindgen::to_value(&wallets)
+        .map_err(|e| Error::from(e).into())
+}
+
+#[wasm_bindgen]
+pub fn derive_child_wallets_at(mnemonic: &str, indexes: Vec<u32>, passphrase: Option<String>) -> Result<JsValue, JsValue> {
+    let wallets = hd_wallet::derive_child_wallets_at(mnemonic, passphrase.as_deref(), &indexes)
+        .map_err(JsValue::from)?;
+    
+    serde_wasm_bindgen::to_value(&wallets)
+        .map_err(|e| Error::from(e).into())
+}
+
+#[wasm_bindgen]
+pub fn derive_wallet_at_path(mnemonic: &str, path: &str, passphrase: Option<String>) -> Result<JsValue, JsValue> {
+    let path: derivation_path::DerivationPath = path.parse()
+        .map_err(JsValue::from)?;
+    
+    let wallet = hd_wallet::derive_wallet_at_path(mnemonic, passphrase.as_deref(), &path)
+        .map_err(JsValue::from)?;
+    
+    serde_wasm_bindgen::to_value(&wallet)
+        .map_err(|e| Error::from(e).into())
+}
+
+#[wasm_bindgen]
+pub fn derive_child_wallets_with_template(
+    mnemonic: &str,
+    template: &str,
+    count: u32,
+    account: Option<u32>,
+    change: Option<u32>,
+    passphrase: Option<String>,
+    start: Option<u32>,
+) -> Result<JsValue, JsValue> {
+    let template: derivation_path::PathTemplate = template.parse()
+        .map_err(JsValue::from)?;
+    let values = derivation_path::PathValues {
+        account: account.unwrap_or(0),
+        change: change.unwrap_or(0),
+        index: 0,
+    };
+    
+    let wallets = hd_wallet::derive_child_wallets_with_template(mnemonic, passphrase.as_deref(), &template, values, start.unwrap_or(0), count)
+        .map_err(JsValue::from)?;
+    
+    serde_wasm_bindgen::to_value(&wallets)
+        .map_err(|e| Error::from(e).into())
+}
+
+#[wasm_bindgen]
+pub fn derive_child_wallets_with_template_at(
+    mnemonic: &str,
+    template: &str,
+    indexes: Vec<u32>,
+    account: Option<u32>,
+    change: Option<u32>,
+    passphrase: Option<String>,
+) -> Result<JsValue, JsValue> {
+    let template: derivation_path::PathTemplate = template.parse()
+        .map_err(JsValue::from)?;
+    let values = derivation_path::PathValues {
+        account: account.unwrap_or(0),
+        change: change.unwrap_or(0),
+        index: 0,
+    };
+    
+    let wallets = hd_wallet::derive_child_wallets_with_template_at(mnemonic, passphrase.as_deref(), &template, values, &indexes)
+        .map_err(JsValue::from)?;
+    
+    serde_wasm_bindgen::to_value(&wallets)
+        .map_err(|e| Error::from(e).into())
+}
+
+/// Hands private keys to JS; prefer `KeyringSession`, which keeps them in WASM.
+/// `start_index` defaults to 1, since child 0 is the parent, so the default
+/// children are the ones `sign_split_distribution` funds.
+#[wasm_bindgen]
+pub fn create_split_operation(
+    mnemonic: &str,
+    passphrase: Option<String>,
+    parent_path: Option<String>,
+    child_template: Option<String>,
+    child_count: Option<u32>,
+    start_index: Option<u32>,
+) -> Result<JsValue, JsValue> {
+    let parent_path: derivation_path::DerivationPath = parent_path.as_deref()
+        .unwrap_or(derivation_path::DEFAULT_PARENT_PATH)
+        .parse()
+        .map_err(JsValue::from)?;
+    let child_template: derivation_path::PathTemplate = child_template.as_deref()
+        .unwrap_or(derivation_path::DEFAULT_CHILD_TEMPLATE)
+        .parse()
+        .map_err(JsValue::from)?;
+    
+    let result = hd_wallet::HdWallet::from_mnemonic(mnemonic, passphrase.as_deref())
+        .and_then(|wallet| wallet.split(
+            &parent_path,
+            &child_template,
+            start_index.unwrap_or(FIRST_SPLIT_CHILD),
+            child_count.unwrap_or(DEFAULT_SPLIT_CHILD_COUNT),
+        ))
+        .map_err(JsValue::from)?;
+    
+    serde_wasm_bindgen::to_value(&result)
+        .map_err(|e| Error::from(e).into())
+}
+
+/// Keeps the master and `m/44'/60'/0'/0` keys in WASM memory so children can
+/// be derived in bulk without re-running PBKDF2 for every call.
+#[wasm_bindgen]
+pub struct HdWalletHandle {
+    wallet: hd_wallet::HdWallet,
+}
+
+#[wasm_bindgen]
+impl HdWalletHandle {
+    #[wasm_bindgen(constructor)]
+    pub fn new(mnemonic: &str, passphrase: Option<String>) -> Result<HdWalletHandle, JsValue> {
+        let wallet = hd_wallet::HdWallet::from_mnemonic(mnemonic, passphrase.as_deref())
+            .map_err(JsValue::from)?;
+        
+        Ok(HdWalletHandle { wallet })
+    }
+
+    /// Opens the wallet that uses the recovered SLIP-39 master secret as its
+    /// BIP32 seed, as Trezor does; `slip39_split_seed` shares work the same
+    /// way. Shares from `slip39_split` hold BIP39 entropy instead; see
+    /// `slip39_combine_mnemonic`.
+    pub fn from_slip39(shares: Vec<String>, passphrase: Option<String>) -> Result<HdWalletHandle, JsValue> {
+        let seed = slip39::combine_shares(&shares, passphrase.as_deref().unwrap_or(""))
+            .map_err(JsValue::from)?;
+        let wallet = hd_wallet::HdWallet::from_seed(&seed)
+            .map_err(JsValue::from)?;
+        
+        Ok(HdWalletHandle { wallet })
+    }
+
+    /// Opens a wallet from a root `xprv` (depth 0), an account `xprv` at
+    /// `m/44'/60'/0'` or an external chain `xprv` at `m/44'/60'/0'/0`; any
+    /// other depth is rejected.
+    pub fn from_xprv(xprv: &str) -> Result<HdWalletHandle, JsValue> {
+        let key: extended_key::ExtendedKey = xprv.parse()
+            .map_err(JsValue::from)?;
+        let wallet = hd_wallet::HdWallet::from_extended_key(key)
+            .map_err(JsValue::from)?;
+        
+        Ok(HdWalletHandle { wallet })
+    }
+
+    pub fn account_xpub(&self) -> String {
+        self.wallet.account_key().to_xpub()
+    }
+
+    pub fn account_xprv(&self) -> Result<String, JsValue> {
+        self.wallet.account_key().to_xprv()
+            .map_err(JsValue::from)
+    }
+
+    pub fn account_key_info(&self) -> Result<JsValue, JsValue> {
+        serde_wasm_bindgen::to_value(&self.wallet.account_key().info())
+            .map_err(|e| Error::from(e).into())
+    }
+
+    pub fn master_fingerprint(&self) -> Result<String, JsValue> {
+        self.wallet.master_fingerprint()
+            .map(hex::encode)
+            .map_err(JsValue::from)
+    }
+
+    /// `xpub` (or `xprv` when `private`) at any path from the master key.
+    pub fn export_extended_key(&self, path: &str, private: bool) -> Result<String, JsValue> {
+        let path: derivation_path::DerivationPath = path.parse()
+            .map_err(JsValue::from)?;
+        let key = self.wallet.extended_key(&path)
+            .map_err(JsValue::from)?;
+        
+        if private {
+            key.to_xprv().map_err(JsValue::from)
+        } else {
+            Ok(key.to_xpub())
+        }
+    }
+
+    pub fn parent(&self) -> Result<JsValue, JsValue> {
+        let wallet = self.wallet.parent()
+            .map_err(JsValue::from)?;
+        
+        serde_wasm_bindgen::to_value(&wallet)
+            .map_err(|e| Error::from(e).into())
+    }
+
+    pub fn child(&self, index: u32) -> Result<JsValue, JsValue> {
+        let wallet = self.wallet.child(index)
+            .map_err(JsValue::from)?;
+        
+        serde_wasm_bindgen::to_value(&wallet)
+            .map_err(|e| Error::from(e).into())
+    }
+
+    pub fn children(&self, count: u32) -> Result<JsValue, JsValue> {
+        let wallets = self.wallet.children(count)
+            .map_err(JsValue::from)?;
+        
+        serde_wasm_bindgen::to_value(&wallets)
+            .map_err(|e| Error::from(e).into())
+    }
+
+    pub fn children_range(&self, start: u32, count: u32) -> Result<JsValue, JsValue> {
+        let wallets = self.wallet.children_range(start, count)
+            .map_err(JsValue::from)?;
+        
+        serde_wasm_bindgen::to_value(&wallets)
+            .map_err(|e| Error::from(e).into())
+    }
+
+    pub fn children_at(&self, indexes: Vec<u32>) -> Result<JsValue, JsValue> {
+        let wallets = self.wallet.children_at(&indexes)
+            .map_err(JsValue::from)?;
+        
+        serde_wasm_bindgen::to_value(&wallets)
+            .map_err(|e| Error::from(e).into())
+    }
+
+    pub fn child_addresses(&self, start: u32, count: u32) -> Result<Vec<String>, JsValue> {
+        let wallets = self.wallet.children_range(start, count)
+            .map_err(JsValue::from)?;
+        
+        Ok(wallets.into_iter().map(|w| w.address).collect())
+    }
+
+    /// V3 keystore JSON for the key at `path`, parent or child; see
+    /// `KeyringSession.export_keystore` for `kdf`.
+    pub fn export_keystore(&self, path: &str, password: &str, kdf: Option<String>) -> Result<String, JsValue> {
+        let path: derivation_path::DerivationPath = path.parse()
+            .map_err(JsValue::from)?;
+        let kdf = keystore_kdf(kdf).map_err(JsValue::from)?;
+        let wallet = self.wallet.derive_path(&path)
+            .map_err(JsValue::from)?;
+        
+        keystore::encrypt_key(&wallet.private_key, password, kdf)
+            .and_then(|keystore| keystore.to_json())
+            .map_err(JsValue::from)
+    }
+
+    /// Signs a transaction with child `index`; see `KeyringSession.sign_transaction`.
+    pub fn sign_transaction(&self, index: u32, transaction: JsValue) -> Result<JsValue, JsValue> {
+        let transaction = transaction_from_js(transaction).map_err(JsValue::from)?;
+        let signed = self.wallet.child(index)
+            .and_then(|wallet| transaction.sign(&wallet.private_key))
+            .map_err(JsValue::from)?;
+        
+        serde_wasm_bindgen::to_value(&signed)
+            .map_err(|e| Error::from(e).into())
+    }
+
+    /// Parent-signed funding transfers to children `start..start + count`;
+    /// see `KeyringSession.sign_split_distribution`.
+    pub fn sign_split_distribution(&self, start: Option<u32>, count: u32, params: JsValue) -> Result<JsValue, JsValue> {
+        let params = distribution_params_from_js(params).map_err(JsValue::from)?;
+        let batch = distribution::sign_split_distribution(&self.wallet, start.unwrap_or(FIRST_SPLIT_CHILD), count, &params)
+            .map_err(JsValue::from)?;
+        
+        serde_wasm_bindgen::to_value(&batch)
+            .map_err(|e| Error::from(e).into())
+    }
+
+    /// Sweeps children `start..start + count`; see `KeyringSession.sign_split_sweep`.
+    pub fn sign_split_sweep(&self, start: Option<u32>, count: u32, balances: JsValue, params: JsValue) -> Result<JsValue, JsValue> {
+        let (balances, params) = sweep_from_js(balances, params).map_err(JsValue::from)?;
+        let batch = sweep::sign_split_sweep(&self.wallet, start.unwrap_or(FIRST_SPLIT_CHILD), count, &balances, &params)
+            .map_err(JsValue::from)?;
+        
+        serde_wasm_bindgen::to_value(&batch)
+            .map_err(|e| Error::from(e).into())
+    }
+
+    /// Child addresses under the default and ethers-legacy layouts. Without
+    /// `start`, the default layout starts at child 1, since child 0 is the
+    /// parent, and ethers-legacy at child 0, which the TS fallback funded.
+    pub fn child_recovery_report(&self, start: Option<u32>, count: Option<u32>) -> Result<JsValue, JsValue> {
+        let report = compat::recovery_report(&self.wallet, start, count.unwrap_or(DEFAULT_SPLIT_CHILD_COUNT))
+            .map_err(JsValue::from)?;
+        
+        serde_wasm_bindgen::to_value(&report)
+            .map_err(|e| Error::from(e).into())
+    }
+
+    /// Which named layout derives `address`, or `null` if none does in range.
+    /// `start` defaults per layout, as for `child_recovery_report`.
+    pub fn detect_child_scheme(&self, address: &str, start: Option<u32>, count: Option<u32>) -> Result<JsValue, JsValue> {
+        let found = compat::detect_child_scheme(&self.wallet, address, start, count.unwrap_or(DEFAULT_SPLIT_CHILD_COUNT))
+            .map_err(JsValue::from)?;
+        
+        serde_wasm_bindgen::to_value(&found)
+            .map_err(|e| Error::from(e).into())
+    }
+
+    /// BIP-85 child mnemonics `start..start + count`, each a full seed of its
+    /// own that this wallet can always re-derive.
+    pub fn bip85_mnemonics(&self, word_count: Option<u32>, language: Option<String>, start: u32, count: u32) -> Result<Vec<String>, JsValue> {
+        let bip85 = self.wallet.bip85().map_err(JsValue::from)?;
+        bip85_mnemonics(&bip85, word_count, language, start, count)
+            .map_err(JsValue::from)
+    }
+
+    pub fn bip85_hex(&self, length: usize, index: u32) -> Result<String, JsValue> {
+        self.wallet.bip85()
+            .and_then(|bip85| bip85.hex(length, index))
+            .map(|hex| hex.to_string())
+            .map_err(JsValue::from)
+    }
+
+    pub fn bip85_wif(&self, index: u32) -> Result<String, JsValue> {
+        self.wallet.bip85()
+            .and_then(|bip85| bip85.wif(index))
+            .map(|wif| wif.to_string())
+            .map_err(JsValue::from)
+    }
+
+    /// `encoding` is `"base64"` (default) or `"base85"`.
+    pub fn bip85_password(&self, length: usize, index: u32, encoding: Option<String>) -> Result<String, JsValue> {
+        let bip85 = self.wallet.bip85().map_err(JsValue::from)?;
+        
+        match encoding.as_deref().unwrap_or("base64") {
+            "base64" => bip85.password_base64(length, index),
+            "base85" => bip85.password_base85(length, index),
+            other => Err(Error::invalid_argument("encoding", format!("unknown encoding {}", other))),
+        }
+        .map(|password| password.to_string())
+        .map_err(JsValue::from)
+    }
+
+    pub fn derive_path(&self, path: &str) -> Result<JsValue, JsValue> {
+        let path: derivation_path::DerivationPath = path.parse()
+            .map_err(JsValue::from)?;
+        let wallet = self.wallet.derive_path(&path)
+            .map_err(JsValue::from)?;
+        
+        serde_wasm_bindgen::to_value(&wallet)
+            .map_err(|e| Error::from(e).into())
+    }
+}
+
+/// A wallet whose keys stay in WASM memory. JS gets addresses, public keys
+/// and signatures, never private keys. The session locks itself, wiping the
+/// keys, after `auto_lock_minutes` without a call (default 5, `0` disables):
+/// a timer armed on every call locks it even if JS never calls again.
+#[wasm_bindgen]
+pub struct KeyringSession {
+    keyring: Rc<RefCell<keyring::Keyring>>,
+    /// Pending auto-lock; dropping it cancels the timer.
+    lock_timer: Option<Timeout>,
+}
+
+fn auto_lock_timeout(minutes: Option<u32>) -> Option<Duration> {
+    match minutes {
+        None => Some(keyring::DEFAULT_IDLE_TIMEOUT),
+        Some(0) => None,
+        Some(minutes) => Some(Duration::from_secs(u64::from(minutes) * 60)),
+    }
+}
+
+impl KeyringSession {
+    fn start(keyring: keyring::Keyring) -> KeyringSession {
+        let mut session = KeyringSession { keyring: Rc::new(RefCell::new(keyring)), lock_timer: None };
+        session.arm_lock_timer();
+        session
+    }
+
+    /// The keyring, for a call that counts as activity.
+    fn keyring(&mut self) -> RefMut<'_, keyring::Keyring> {
+        self.arm_lock_timer();
+        self.keyring.borrow_mut()
+    }
+
+    /// Restarts the auto-lock timer, or cancels it when the keyring is locked
+    /// or auto-lock is off.
+    fn arm_lock_timer(&mut self) {
+        self.lock_timer = None;
+        let timeout = {
+            let mut keyring = self.keyring.borrow_mut();
+            match keyring.idle_timeout() {
+                Some(timeout) if !keyring.check_idle() => timeout,
+                _ => return,
+            }
+        };
+        // setTimeout takes a signed 32-bit delay (about 24.8 days).
+        let millis = u32::try_from(timeout.as_millis()).unwrap_or(u32::MAX).min(i32::MAX as u32);
+        let keyring = Rc::downgrade(&self.keyring);
+        self.lock_timer = Some(Timeout::new(millis, move || {
+            if let Some(keyring) = keyring.upgrade() {
+                keyring.borrow_mut().lock();
+            }
+        }));
+    }
+}
+
+#[wasm_bindgen]
+impl KeyringSession {
+    #[wasm_bindgen(constructor)]
+    pub fn new(mnemonic: &str, passphrase: Option<String>, auto_lock_minutes: Option<u32>) -> Result<KeyringSession, JsValue> {
+        let keyring = keyring::Keyring::unlock(mnemonic, passphrase.as_deref(), auto_lock_timeout(auto_lock_minutes))
+            .map_err(JsValue::from)?;
+        
+        Ok(KeyringSession::start(keyring))
+    }
+
+    /// Unlocks from SLIP-39 shares whose master secret is the BIP32 seed, as
+    /// with `HdWalletHandle.from_slip39`; the recovered seed stays in WASM.
+    pub fn from_slip39(shares: Vec<String>, passphrase: Option<String>, auto_lock_minutes: Option<u32>) -> Result<KeyringSession, JsValue> {
+        let keyring = slip39::combine_shares(&shares, passphrase.as_deref().unwrap_or(""))
+            .and_then(|seed| keyring::Keyring::unlock_seed(&seed, auto_lock_timeout(auto_lock_minutes)))
+            .map_err(JsValue::from)?;
+        
+        Ok(KeyringSession::start(keyring))
+    }
+
+    /// Unlocks from shares made by `slip39_split`; the recovered mnemonic
+    /// stays in WASM. `language` is the original wordlist code (default `"en"`).
+    pub fn from_slip39_mnemonic(
+        shares: Vec<String>,
+        slip39_passphrase: Option<String>,
+        bip39_passphrase: Option<String>,
+        language: Option<String>,
+        auto_lock_minutes: Option<u32>,
+    ) -> Result<KeyringSession, JsValue> {
+        let language = match language {
+            Some(code) => bip39::language_from_code(&code)
+                .map_err(JsValue::from)?,
+            None => ::bip39::Language::English,
+        };
+        let keyring = slip39::combine_mnemonic(&shares, slip39_passphrase.as_deref().unwrap_or(""), language)
+            .and_then(|mnemonic| {
+                keyring::Keyring::unlock(mnemonic.expose(), bip39_passphrase.as_deref(), auto_lock_timeout(auto_lock_minutes))
+            })
+            .map_err(JsValue::from)?;
+        
+        Ok(KeyringSession::start(keyring))
+    }
+
+    /// Unlocks from a vault made by `seal_vault`; the mnemonic stays in WASM.
+    pub fn from_vault(vault: &str, password: &str, auto_lock_minutes: Option<u32>) -> Result<KeyringSession, JsValue> {
+        let vault = vault_bytes(vault).map_err(JsValue::from)?;
+        let keyring = keyring::Keyring::unlock_vault(&vault, password, auto_lock_timeout(auto_lock_minutes))
+            .map_err(JsValue::from)?;
+        
+        Ok(KeyringSession::start(keyring))
+    }
+
+    pub fn lock(&mut self) {
+        self.keyring.borrow_mut().lock();
+        self.lock_timer = None;
+    }
+
+    /// Reports whether the session is locked. Does not count as activity.
+    pub fn is_locked(&mut self) -> bool {
+        let locked = self.keyring.borrow_mut().check_idle();
+        if locked {
+            self.lock_timer = None;
+        }
+        locked
+    }
+
+    pub fn set_auto_lock_minutes(&mut self, minutes: Option<u32>) {
+        self.keyring.borrow_mut().set_idle_timeout(auto_lock_timeout(minutes));
+        self.arm_lock_timer();
+    }
+
+    pub fn account_xpub(&mut self) -> Result<String, JsValue> {
+        self.keyring().account_xpub().map_err(JsValue::from)
+    }
+
+    pub fn child(&mut self, index: u32) -> Result<JsValue, JsValue> {
+        let wallet = self.keyring().child(index)
+            .map_err(JsValue::from)?;
+        
+        serde_wasm_bindgen::to_value(&wallet)
+            .map_err(|e| Error::from(e).into())
+    }
+
+    pub fn children_range(&mut self, start: u32, count: u32) -> Result<JsValue, JsValue> {
+        let wallets = self.keyring().children_range(start, count)
+            .map_err(JsValue::from)?;
+        
+        serde_wasm_bindgen::to_value(&wallets)
+            .map_err(|e| Error::from(e).into())
+    }
+
+    /// Signs a 32-byte digest; `v` in the result is `27 + recovery id`.
+    pub fn sign_hash(&mut self, index: u32, hash: &[u8]) -> Result<JsValue, JsValue> {
+        let hash: [u8; 32] = hash.try_into()
+            .map_err(|_| JsValue::from(Error::invalid_argument("hash", format!("expected 32 bytes, got {}", hash.len()))))?;
+        let signature = self.keyring().sign_hash(index, &hash)
+            .map_err(JsValue::from)?;
+        
+        serde_wasm_bindgen::to_value(&signature.to_info())
+            .map_err(|e| Error::from(e).into())
+    }
+
+    /// V3 keystore JSON for child `index`. `kdf` is `"scrypt-light"` (default,
+    /// 4 MiB), `"scrypt"` or `"pbkdf2"`. `"scrypt"` is geth's default and
+    /// allocates 256 MiB, more than many mobile WASM heaps allow.
+    pub fn export_keystore(&mut self, index: u32, password: &str, kdf: Option<String>) -> Result<String, JsValue> {
+        let kdf = keystore_kdf(kdf).map_err(JsValue::from)?;
+        
+        self.keyring().export_keystore(index, password, kdf)
+            .and_then(|keystore| keystore.to_json())
+            .map_err(JsValue::from)
+    }
+
+    /// Signs a transaction object (see `Transaction` in rust-core) with child
+    /// `index`; returns `{ raw, hash, from, nonce }`.
+    pub fn sign_transaction(&mut self, index: u32, transaction: JsValue) -> Result<JsValue, JsValue> {
+        let transaction = transaction_from_js(transaction).map_err(JsValue::from)?;
+        let signed = self.keyring().sign_transaction(index, &transaction)
+            .map_err(JsValue::from)?;
+        
+        serde_wasm_bindgen::to_value(&signed)
+            .map_err(|e| Error::from(e).into())
+    }
+
+    /// Signs one transfer from the parent to each child `start..start + count`,
+    /// with nonces from `params.start_nonce` up, for broadcasting later without
+    /// the WalletSplitter contract. `start` defaults to 1, since child 0 is the
+    /// parent. `params` is `{ chain_id, start_nonce, fees, amounts,
+    /// gas_limit? }`; returns `{ manifest, transactions }`.
+    pub fn sign_split_distribution(&mut self, start: Option<u32>, count: u32, params: JsValue) -> Result<JsValue, JsValue> {
+        let params = distribution_params_from_js(params).map_err(JsValue::from)?;
+        let batch = self.keyring().sign_split_distribution(start.unwrap_or(FIRST_SPLIT_CHILD), count, &params)
+            .map_err(JsValue::from)?;
+        
+        serde_wasm_bindgen::to_value(&batch)
+            .map_err(|e| Error::from(e).into())
+    }
+
+    /// Signs a "send max minus fee" transfer from every funded child in
+    /// `start..start + count` to `params.destination`; `start` defaults to 1,
+    /// since child 0 is the parent. `balances` is `[{ address, balance,
+    /// nonce? }]`; children missing from it or holding no more than the fee
+    /// are skipped. `params` is `{ chain_id, destination, fees, gas_limit? }`;
+    /// returns `{ summary, entries, skipped, transactions }`.
+    pub fn sign_split_sweep(&mut self, start: Option<u32>, count: u32, balances: JsValue, params: JsValue) -> Result<JsValue, JsValue> {
+        let (balances, params) = sweep_from_js(balances, params).map_err(JsValue::from)?;
+        let batch = self.keyring().sign_split_sweep(start.unwrap_or(FIRST_SPLIT_CHILD), count, &balances, &params)
+            .map_err(JsValue::from)?;
+        
+        serde_wasm_bindgen::to_value(&batch)
+            .map_err(|e| Error::from(e).into())
+    }
+
+    /// `personal_sign` (EIP-191) over `message`.
+    pub fn sign_message(&mut self, index: u32, message: &[u8]) -> Result<JsValue, JsValue> {
+        let signature = self.keyring().sign_message(index, message)
+            .map_err(JsValue::from)?;
+        
+        serde_wasm_bindgen::to_value(&signature.to_info())
+            .map_err(|e| Error::from(e).into())
+    }
+}
+
+fn bip85_mnemonics(
+    bip85: &bip85::Bip85,
+    word_count: Option<u32>,
+    language: Option<String>,
+    start: u32,
+    count: u32,
+) -> Result<Vec<String>, Error> {
+    let language = match language {
+        Some(code) => bip39::language_from_code(&code)?,
+        None => ::bip39::Language::English,
+    };
+    let end = start
+        .checked_add(count)
+        .ok_or_else(|| Error::invalid_argument("child range", format!("{}+{} overflows", start, count)))?;
+    
+    (start..end)
+        .map(|index| bip85.mnemonic(language, word_count.map_or(bip39::DEFAULT_WORD_COUNT, |count| count as usize), index))
+        .map(|phrase| phrase.map(|phrase| phrase.expose().to_string()))
+        .collect()
+}
+
+/// Independent child mnemonics for handing out as separate wallets; see
+/// `HdWalletHandle.bip85_mnemonics`.
+#[wasm_bindgen]
+pub fn derive_bip85_mnemonics(
+    mnemonic: &str,
+    passphrase: Option<String>,
+    word_count: Option<u32>,
+    language: Option<String>,
+    start: u32,
+    count: u32,
+) -> Result<Vec<String>, JsValue> {
+    let bip85 = bip85::Bip85::from_mnemonic(mnemonic, passphrase.as_deref())
+        .map_err(JsValue::from)?;
+    
+    bip85_mnemonics(&bip85, word_count, language, start, count)
+        .map_err(JsValue::from)
+}
+
+fn transaction_from_js(transaction: JsValue) -> Result<transaction::Transaction, Error> {
+    serde_wasm_bindgen::from_value(transaction).map_err(|e| Error::invalid_argument("transaction", e))
+}
+
+/// The hash a transaction's sender signs, e.g. for an external signer.
+#[wasm_bindgen]
+pub fn transaction_signing_hash(transaction: JsValue) -> Result<String, JsValue> {
+    transaction_from_js(transaction)
+        .and_then(|transaction| transaction.signing_hash())
+        .map(|hash| format!("0x{}", hex::encode(hash)))
+        .map_err(JsValue::from)
+}
+
+/// Splits wei between `count` children in exact 256-bit arithmetic, replacing
+/// `parseEther` math in JS. `plan` is `{ strategy, ...fields, min_amount?,
+/// max_amount?, gas_reserve? }` with amounts as decimal strings, e.g.
+/// `{ strategy: "equal", total: "1000000000000000000" }`.
+#[wasm_bindgen]
+pub fn plan_split_amounts(count: u32, plan: JsValue) -> Result<JsValue, JsValue> {
+    let plan: split_plan::AmountPlan = serde_wasm_bindgen::from_value(plan)
+        .map_err(|e| Error::invalid_argument("plan", e))?;
+    let plan = plan.plan(count as usize)
+        .map_err(JsValue::from)?;
+    
+    serde_wasm_bindgen::to_value(&plan)
+        .map_err(|e| Error::from(e).into())
+}
+
+fn distribution_params_from_js(params: JsValue) -> Result<distribution::DistributionParams, Error> {
+    serde_wasm_bindgen::from_value(params).map_err(|e| Error::invalid_argument("params", e))
+}
+
+/// Funding transfers from a standalone hex private key to arbitrary
+/// addresses; see `KeyringSession.sign_split_distribution`.
+#[wasm_bindgen]
+pub fn sign_distribution(private_key: &str, recipients: Vec<String>, params: JsValue) -> Result<JsValue, JsValue> {
+    let private_key = secret::PrivateKey::from_hex(private_key)
+        .map_err(JsValue::from)?;
+    let params = distribution_params_from_js(params).map_err(JsValue::from)?;
+    let recipients: Vec<distribution::Recipient> = recipients.into_iter()
+        .map(|address| distribution::Recipient { address, child_index: None })
+        .collect();
+    let batch = distribution::sign_distribution(&private_key, &recipients, &params)
+        .map_err(JsValue::from)?;
+    
+    serde_wasm_bindgen::to_value(&batch)
+        .map_err(|e| Error::from(e).into())
+}
+
+fn sweep_from_js(balances: JsValue, params: JsValue) -> Result<(Vec<sweep::ChildBalance>, sweep::SweepParams), Error> {
+    let balances = serde_wasm_bindgen::from_value(balances).map_err(|e| Error::invalid_argument("balances", e))?;
+    let params = serde_wasm_bindgen::from_value(params).map_err(|e| Error::invalid_argument("params", e))?;
+    Ok((balances, params))
+}
+
+/// Sweeps `children` as returned by `derive_child_wallets`; see
+/// `KeyringSession.sign_split_sweep`.
+#[wasm_bindgen]
+pub fn sign_sweep(children: JsValue, balances: JsValue, params: JsValue) -> Result<JsValue, JsValue> {
+    let children: Vec<WalletInfo> = serde_wasm_bindgen::from_value(children)
+        .map_err(|e| Error::invalid_argument("children", e))?;
+    let (balances, params) = sweep_from_js(balances, params).map_err(JsValue::from)?;
+    let batch = sweep::sign_sweep(&children, &balances, &params)
+        .map_err(JsValue::from)?;
+    
+    serde_wasm_bindgen::to_value(&batch)
+        .map_err(|e| Error::from(e).into())
+}
+
+/// Light scrypt unless asked otherwise: the standard parameters need 256 MiB.
+fn keystore_kdf(name: Option<String>) -> Result<keystore::Kdf, Error> {
+    name.as_deref().map_or(Ok(keystore::Kdf::SCRYPT_LIGHT), keystore::Kdf::from_name)
+}
+
+/// Encrypts a hex private key, e.g. `WalletInfo.private_key`, as V3 keystore
+/// JSON; see `KeyringSession.export_keystore` for `kdf`.
+#[wasm_bindgen]
+pub fn encrypt_keystore(private_key: &str, password: &str, kdf: Option<String>) -> Result<String, JsValue> {
+    let private_key = secret::PrivateKey::from_hex(private_key)
+        .map_err(JsValue::from)?;
+    let kdf = keystore_kdf(kdf).map_err(JsValue::from)?;
+    
+    keystore::encrypt_key(&private_key, password, kdf)
+        .and_then(|keystore| keystore.to_json())
+        .map_err(JsValue::from)
+}
+
+/// Decrypts V3 keystore JSON from Geth, MetaMask or `encrypt_keystore`.
+#[wasm_bindgen]
+pub fn decrypt_keystore(keystore_json: &str, password: &str) -> Result<JsValue, JsValue> {
+    let wallet = keystore_json.parse::<keystore::Keystore>()
+        .and_then(|keystore| keystore::decrypt_key(&keystore, password))
+        .map(WalletInfo::from_private_key)
+        .map_err(JsValue::from)?;
+    
+    serde_wasm_bindgen::to_value(&wallet)
+        .map_err(|e| Error::from(e).into())
+}
+
+fn vault_bytes(vault: &str) -> Result<Vec<u8>, Error> {
+    hex::decode(vault.trim()).map_err(|e| Error::InvalidVault { reason: format!("not hex: {}", e) })
+}
+
+/// Reads an optional JS object argument; `undefined` and `null` give the default.
+fn optional_from_js<T: serde::de::DeserializeOwned + Default>(argument: &str, value: JsValue) -> Result<T, Error> {
+    if value.is_undefined() || value.is_null() {
+        return Ok(T::default());
+    }
+    serde_wasm_bindgen::from_value(value).map_err(|e| Error::invalid_argument(argument, e))
+}
+
+/// Encrypts a mnemonic, its passphrase and string `metadata` under `password`
+/// with Argon2id and XChaCha20-Poly1305. Returns the vault as hex, ready for
+/// SecureStore. `params` is `{ memory_kib, iterations, parallelism }`; omitted
+/// fields default to 64 MiB, 3 and 1.
+#[wasm_bindgen]
+pub fn seal_vault(
+    mnemonic: &str,
+    passphrase: Option<String>,
+    password: &str,
+    metadata: JsValue,
+    params: JsValue,
+) -> Result<String, JsValue> {
+    let contents = vault::VaultContents {
+        mnemonic: secret::SecretPhrase::new(mnemonic.to_string()),
+        passphrase: passphrase.map(Zeroizing::new),
+        metadata: optional_from_js("metadata", metadata).map_err(JsValue::from)?,
+    };
+    let params = optional_from_js("params", params).map_err(JsValue::from)?;
+    
+    vault::seal(&contents, password, params)
+        .map(hex::encode)
+        .map_err(JsValue::from)
+}
+
+/// Hands the mnemonic to JS, e.g. for a backup screen; to sign, prefer
+/// `KeyringSession.from_vault`.
+#[wasm_bindgen]
+pub fn open_vault(vault: &str, password: &str) -> Result<JsValue, JsValue> {
+    let contents = vault_bytes(vault)
+        .and_then(|vault| vault::open(&vault, password))
+        .map_err(JsValue::from)?;
+    
+    serde_wasm_bindgen::to_value(&contents)
+        .map_err(|e| Error::from(e).into())
+}
+
+/// Re-encrypts under `new_password`. Omit `params` to keep the current ones,
+/// or pass stronger ones to upgrade an old vault.
+#[wasm_bindgen]
+pub fn rekey_vault(vault: &str, old_password: &str, new_password: &str, params: JsValue) -> Result<String, JsValue> {
+    let params = optional_from_js("params", params).map_err(JsValue::from)?;
+    
+    vault_bytes(vault)
+        .and_then(|vault| vault::rekey(&vault, old_password, new_password, params))
+        .map(hex::encode)
+        .map_err(JsValue::from)
+}
+
+/// Version and KDF parameters of a vault; needs no password.
+#[wasm_bindgen]
+pub fn inspect_vault(vault: &str) -> Result<JsValue, JsValue> {
+    let header = vault_bytes(vault)
+        .and_then(|vault| vault::inspect(&vault))
+        .map_err(JsValue::from)?;
+    
+    serde_wasm_bindgen::to_value(&header)
+        .map_err(|e| Error::from(e).into())
+}
+
+fn slip39_groups(groups: JsValue) -> Result<Vec<slip39::GroupSpec>, Error> {
+    serde_wasm_bindgen::from_value(groups).map_err(|e| Error::invalid_argument("groups", e))
+}
+
+fn slip39_shares_to_js(shares: &[Vec<secret::SecretPhrase>]) -> Result<JsValue, JsValue> {
+    let shares: Vec<Vec<&str>> = shares.iter()
+        .map(|group| group.iter().map(|share| share.expose()).collect())
+        .collect();
+    serde_wasm_bindgen::to_value(&shares)
+        .map_err(|e| Error::from(e).into())
+}
+
+/// Splits the BIP39 entropy of `mnemonic` into SLIP-39 shares, one array per
+/// group. `groups` is `[{ threshold, count }, ...]`; `slip39_passphrase`
+/// encrypts the entropy before splitting. Only `slip39_combine_mnemonic` and
+/// `KeyringSession.from_slip39_mnemonic` turn the shares back into this
+/// wallet; the BIP39 passphrase is not part of them. Other SLIP-39 tools
+/// recover the raw entropy, and restoring the shares on a Trezor uses that
+/// entropy as the seed, which gives a different wallet with other addresses.
+#[wasm_bindgen]
+pub fn slip39_split(
+    mnemonic: &str,
+    slip39_passphrase: Option<String>,
+    group_threshold: u8,
+    groups: JsValue,
+    extendable: Option<bool>,
+) -> Result<JsValue, JsValue> {
+    let groups = slip39_groups(groups).map_err(JsValue::from)?;
+    let shares = slip39::split_mnemonic(
+        mnemonic,
+        slip39_passphrase.as_deref().unwrap_or(""),
+        group_threshold,
+        &groups,
+        slip39::DEFAULT_ITERATION_EXPONENT,
+        extendable.unwrap_or(true),
+    )
+    .map_err(JsValue::from)?;
+
+    slip39_shares_to_js(&shares)
+}
+
+/// Like `slip39_split`, but splits the 64-byte BIP39 seed, passphrase
+/// included. Not portable: Trezor devices only take 128- and 256-bit
+/// secrets, so only `from_slip39` here or Trezor's `shamir-mnemonic` library
+/// can use these shares.
+#[wasm_bindgen]
+pub fn slip39_split_seed(
+    mnemonic: &str,
+    bip39_passphrase: Option<String>,
+    slip39_passphrase: Option<String>,
+    group_threshold: u8,
+    groups: JsValue,
+    extendable: Option<bool>,
+) -> Result<JsValue, JsValue> {
+    let groups = slip39_groups(groups).map_err(JsValue::from)?;
+    let seed = bip39::mnemonic_to_seed(mnemonic, bip39_passphrase.as_deref())
+        .map_err(JsValue::from)?;
+    let shares = slip39::split_master_secret(
+        seed.as_bytes(),
+        slip39_passphrase.as_deref().unwrap_or(""),
+        group_threshold,
+        &groups,
+        slip39::DEFAULT_ITERATION_EXPONENT,
+        extendable.unwrap_or(true),
+    )
+    .map_err(JsValue::from)?;
+    
+    slip39_shares_to_js(&shares)
+}
+
+/// Recovers the mnemonic from shares made by `slip39_split`. `language` is
+/// the wordlist code of the original phrase (default `"en"`).
+#[wasm_bindgen]
+pub fn slip39_combine_mnemonic(shares: Vec<String>, slip39_passphrase: Option<String>, language: Option<String>) -> Result<String, JsValue> {
+    let language = match language {
+        Some(code) => bip39::language_from_code(&code)
+            .map_err(JsValue::from)?,
+        None => ::bip39::Language::English,
+    };
+    
+    slip39::combine_mnemonic(&shares, slip39_passphrase.as_deref().unwrap_or(""), language)
+        .map(|phrase| phrase.expose().to_string())
+        .map_err(JsValue::from)
+}
+
+/// Group and member metadata of one share, for showing recovery progress.
+#[wasm_bindgen]
+pub fn slip39_share_info(share: &str) -> Result<JsValue, JsValue> {
+    let share: slip39::Share = share.parse()
+        .map_err(JsValue::from)?;
+    
+    serde_wasm_bindgen::to_value(&share)
+        .map_err(|e| Error::from(e).into())
+}
+
+/// Lists children under both the default and the ethers-legacy layout, so
+/// funds split by the TS fallback can be found. `start` defaults per layout,
+/// as for `HdWalletHandle.child_recovery_report`.
+#[wasm_bindgen]
+pub fn child_recovery_report(mnemonic: &str, passphrase: Option<String>, start: Option<u32>, count: Option<u32>) -> Result<JsValue, JsValue> {
+    let report = hd_wallet::HdWallet::from_mnemonic(mnemonic, passphrase.as_deref())
+        .and_then(|wallet| compat::recovery_report(&wallet, start, count.unwrap_or(DEFAULT_SPLIT_CHILD_COUNT)))
+        .map_err(JsValue::from)?;
+    
+    serde_wasm_bindgen::to_value(&report)
+        .map_err(|e| Error::from(e).into())
+}
+
+/// SeedXOR parts of `mnemonic`; each is a valid mnemonic of the same length.
+#[wasm_bindgen]
+pub fn seed_xor_split(mnemonic: &str, parts: usize) -> Result<Vec<String>, JsValue> {
+    bip39::seed_xor_split(mnemonic, parts)
+        .map(|parts| parts.iter().map(|part| part.expose().to_string()).collect())
+        .map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+pub fn seed_xor_combine(parts: Vec<String>) -> Result<String, JsValue> {
+    bip39::seed_xor_combine(&parts)
+        .map(|phrase| phrase.expose().to_string())
+        .map_err(JsValue::from)
+}
+
+/// The public half of `create_split_operation`; `start_index` defaults to 1.
+#[wasm_bindgen]
+pub fn derive_watch_only_split(xpub: &str, child_count: Option<u32>, start_index: Option<u32>) -> Result<JsValue, JsValue> {
+    let wallet = watch_only::WatchOnlyWallet::from_xpub(xpub)
+        .map_err(JsValue::from)?;
+    let split = wallet.split(start_index.unwrap_or(FIRST_SPLIT_CHILD), child_count.unwrap_or(DEFAULT_SPLIT_CHILD_COUNT))
+        .map_err(JsValue::from)?;
+    
+    serde_wasm_bindgen::to_value(&split)
+        .map_err(|e| Error::from(e).into())
+}
+
+#[wasm_bindgen]
+pub fn derive_watch_only_children(xpub: &str, start: u32, count: u32) -> Result<JsValue, JsValue> {
+    let wallet = watch_only::WatchOnlyWallet::from_xpub(xpub)
+        .map_err(JsValue::from)?;
+    let children = wallet.children_range(start, count)
+        .map_err(JsValue::from)?;
+    
+    serde_wasm_bindgen::to_value(&children)
+        .map_err(|e| Error::from(e).into())
+}
+
+#[wasm_bindgen]
+pub fn verify_child(xpub: &str, index: u32, address: &str) -> Result<bool, JsValue> {
+    watch_only::WatchOnlyWallet::from_xpub(xpub)
+        .and_then(|wallet| wallet.verify_child(index, address))
+        .map_err(JsValue::from)
+}
+
+/// Re-checksums `address` with EIP-55, or EIP-1191 when `chain_id` is given.
+#[wasm_bindgen]
+pub fn to_checksum_address(address: &str, chain_id: Option<u64>) -> Result<String, JsValue> {
+    address::parse_address(address)
+        .map(|bytes| address::to_checksum_address(&bytes, chain_id))
+        .map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+pub fn validate_address(address: &str, chain_id: Option<u64>) -> Result<JsValue, JsValue> {
+    let validation = address::validate_address(address, chain_id);
+    
+    serde_wasm_bindgen::to_value(&validation)
+        .map_err(|e| Error::from(e).into())
+}
+
+#[wasm_bindgen]
+pub fn inspect_extended_key(extended_key: &str) -> Result<JsValue, JsValue> {
+    let key: extended_key::ExtendedKey = extended_key.parse()
+        .map_err(JsValue::from)?;
+    
+    serde_wasm_bindgen::to_value(&key.info())
+        .map_err(|e| Error::from(e).into())
+}
+
+#[wasm_bindgen]
+pub fn validate_derivation_path(path: &str) -> bool {
+    path.parse::<derivation_path::DerivationPath>().is_ok()
+}
+
+#[wasm_bindgen]
+pub fn path_template_preset(name: &str) -> Result<String, JsValue> {
+    derivation_path::template_preset(name)
+        .map(str::to_string)
+        .ok_or_else(|| Error::invalid_argument("path template preset", format!("unknown preset {}", name)).into())
+}
+
+#[wasm_bindgen]
+pub fn validate_path_template(template: &str) -> bool {
+    template.parse::<derivation_path::PathTemplate>().is_ok()
+}
+
+#[wasm_bindgen]
+pub fn validate_mnemonic(mnemonic: &str) -> bool {
+    bip39::validate_mnemonic(mnemonic)
+}
+
+#[wasm_bindgen]
+pub fn validate_mnemonic_detailed(mnemonic: &str) -> Result<JsValue, JsValue> {
+    let validation = bip39::validate_mnemonic_detailed(mnemonic);
+    
+    serde_wasm_bindgen::to_value(&validation)
+        .map_err(|e| Error::from(e).into())
+}
+
+#[wasm_bindgen]
+pub struct MnemonicRecovery {
+    search: recovery::RecoverySearch,
+}
+
+#[wasm_bindgen]
+impl MnemonicRecovery {
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        mnemonic: &str,
+        suspect_positions: Vec<u32>,
+        target_address: Option<String>,
+        passphrase: Option<String>,
+        max_candidates: Option<u32>,
+    ) -> Result<MnemonicRecovery, JsValue> {
+        let suspect_positions: Vec<usize> = suspect_positions.into_iter().map(|p| p as usize).collect();
+        let search = recovery::RecoverySearch::new(
+            mnemonic,
+            &suspect_positions,
+            target_address.as_deref(),
+            passphrase.as_deref(),
+            max_candidates.map(|m| m as usize),
+        ).map_err(JsValue::from)?;
+        
+        Ok(MnemonicRecovery { search })
+    }
+
+    /// Runs the next `budget` combinations; call repeatedly until `done`.
+    pub fn step(&mut self, budget: u32) -> Result<JsValue, JsValue> {
+        let progress = self.search.step(budget as u64)
+            .map_err(JsValue::from)?;
+        
+        serde_wasm_bindgen::to_value(&progress)
+            .map_err(|e| Error::from(e).into())
+    }
+
+    pub fn cancel(&mut self) {
+        self.search.cancel();
+    }
+
+    pub fn result(&self) -> Result<JsValue, JsValue> {
+        serde_wasm_bindgen::to_value(&self.search.result())
+            .map_err(|e| Error::from(e).into())
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use gloo_timers::future::TimeoutFuture;
+    use primitive_types::U256;
+    use serde::Serialize;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    const HARDHAT: &str = "test test test test test test test test test test test junk";
+
+    /// A session whose timer fires after `millis`. The keyring's own idle
+    /// check is then turned off, so only the timer can lock it.
+    fn session_locking_after(millis: u64) -> KeyringSession {
+        let mut session = KeyringSession::new(HARDHAT, None, None).unwrap();
+        session.keyring.borrow_mut().set_idle_timeout(Some(Duration::from_millis(millis)));
+        session.arm_lock_timer();
+        session.keyring.borrow_mut().set_idle_timeout(None);
+        session
+    }
+
+    #[wasm_bindgen_test]
+    async fn timer_locks_without_a_call() {
+        let mut session = session_locking_after(50);
+        assert!(!session.is_locked());
+
+        TimeoutFuture::new(150).await;
+        assert!(session.is_locked());
+        assert!(session.child(1).is_err());
+        assert!(session.sign_message(1, b"hello").is_err());
+    }
+
+    #[wasm_bindgen_test]
+    async fn disabling_auto_lock_cancels_the_timer() {
+        let mut session = session_locking_after(50);
+        session.set_auto_lock_minutes(Some(0));
+
+        TimeoutFuture::new(150).await;
+        assert!(!session.is_locked());
+        assert!(session.child(1).is_ok());
+    }
+
+    #[wasm_bindgen_test]
+    fn default_split_lists_the_distributed_children() {
+        let split: crate::SplitResult =
+            serde_wasm_bindgen::from_value(create_split_operation(HARDHAT, None, None, None, None, None).unwrap()).unwrap();
+        let params = distribution::DistributionParams {
+            chain_id: 1,
+            start_nonce: 0,
+            fees: distribution::FeePolicy::Legacy { gas_price: U256::exp10(9) },
+            amounts: split_plan::AmountStrategy::Fixed { amount: U256::exp10(15) }.into(),
+            gas_limit: transaction::TRANSFER_GAS_LIMIT,
+        };
+        let params = params.serialize(&serde_wasm_bindgen::Serializer::json_compatible()).unwrap();
+        let handle = HdWalletHandle::new(HARDHAT, None).unwrap();
+        let batch: distribution::DistributionBatch =
+            serde_wasm_bindgen::from_value(handle.sign_split_distribution(None, DEFAULT_SPLIT_CHILD_COUNT, params).unwrap()).unwrap();
+
+        assert_eq!(split.child_start_index, FIRST_SPLIT_CHILD);
+        let listed: Vec<&str> = split.child_wallets.iter().map(|child| child.address.as_str()).collect();
+        let funded: Vec<&str> = batch.manifest.entries.iter().map(|entry| entry.to.as_str()).collect();
+        assert_eq!(listed, funded);
+        assert!(!listed.contains(&split.parent_wallet.address.as_str()));
+    }
+
+    #[wasm_bindgen_test]
+    fn calls_fail_after_lock() {
+        let mut session = KeyringSession::new(HARDHAT, None, None).unwrap();
+        session.lock();
+        assert!(session.is_locked());
+        assert!(session.account_xpub().is_err());
+        assert!(session.child(1).is_err());
+        assert!(session.sign_hash(1, &[0; 32]).is_err());
+    }
+}
@@ -0,0 +1,167 @@
+use crate::address::{self, AddressStatus};
+use crate::error::Error;
+use crate::extended_key::ExtendedKey;
+use crate::hd_wallet;
+use crate::utils;
+use hdwallet::{ExtendedPubKey, KeyIndex};
+use serde::{Serialize, Deserialize};
+
+/// Depth of `m/44'/60'/0'`, as exported by MetaMask and Ledger Live.
+pub(crate) const ACCOUNT_DEPTH: u8 = 3;
+/// Depth of `m/44'/60'/0'/0`, the node split children hang off.
+pub(crate) const EXTERNAL_CHAIN_DEPTH: u8 = 4;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PublicWalletInfo {
+    pub index: u32,
+    pub address: String,
+    pub public_key: String,
+}
+
+/// The public half of a `SplitResult`: addresses and keys, nothing secret.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WatchOnlySplit {
+    pub account_xpub: String,
+    pub parent_wallet: PublicWalletInfo,
+    pub child_wallets: Vec<PublicWalletInfo>,
+    pub child_start_index: u32,
+}
+
+/// Public derivation below an `xpub`, without any private key material.
+pub struct WatchOnlyWallet {
+    chain: ExtendedPubKey,
+    xpub: String,
+}
+
+impl WatchOnlyWallet {
+    /// Accepts an `xpub` at `m/44'/60'/0'/0`, or at `m/44'/60'/0'` in which
+    /// case the external chain `/0` is derived first. An `xprv` is rejected
+    /// rather than quietly carried into a "watch-only" result.
+    pub fn from_xpub(xpub: &str) -> Result<Self, Error> {
+        let key: ExtendedKey = xpub.parse()?;
+        if key.is_private() {
+            return Err(Error::invalid_argument("xpub", "expected an xpub, got a private extended key"));
+        }
+        let chain = match key.depth {
+            EXTERNAL_CHAIN_DEPTH => key.to_public(),
+            ACCOUNT_DEPTH => key.to_public().derive_public_key(KeyIndex::Normal(0))
+                .map_err(|e| Error::derivation_failed("external chain", e))?,
+            depth => return Err(Error::InvalidExtendedKey {
+                reason: format!("expected an xpub at m/44'/60'/0' or m/44'/60'/0'/0, got depth {}", depth),
+            }),
+        };
+
+        Ok(WatchOnlyWallet { chain, xpub: key.to_xpub() })
+    }
+
+    pub fn child(&self, index: u32) -> Result<PublicWalletInfo, Error> {
+        let key = self.chain.derive_public_key(KeyIndex::Normal(index))
+            .map_err(|e| Error::derivation_failed(format!("child {}", index), e))?;
+
+        Ok(PublicWalletInfo {
+            index,
+            address: utils::public_key_to_address(&key.public_key),
+            public_key: hex::encode(key.public_key.serialize()),
+        })
+    }
+
+    pub fn children_range(&self, start: u32, count: u32) -> Result<Vec<PublicWalletInfo>, Error> {
+        let end = hd_wallet::index_range_end(start, count)?;
+        (start..end).map(|i| self.child(i)).collect()
+    }
+
+    pub fn split(&self, start: u32, count: u32) -> Result<WatchOnlySplit, Error> {
+        Ok(WatchOnlySplit {
+            account_xpub: self.xpub.clone(),
+            parent_wallet: self.child(0)?,
+            child_wallets: self.children_range(start, count)?,
+            child_start_index: start,
+        })
+    }
+
+    /// True when `address` is the child at `index`. Lowercase, uppercase and
+    /// EIP-55 checksummed addresses are accepted; a malformed address or a
+    /// mixed-case one with a bad checksum is an error, not a mismatch.
+    pub fn verify_child(&self, index: u32, address: &str) -> Result<bool, Error> {
+        let bytes = address::parse_address(address)?;
+        if address::validate_address(address, None).status == AddressStatus::BadChecksum {
+            return Err(Error::InvalidAddress {
+                address: address.to_string(),
+                reason: "bad EIP-55 checksum".to_string(),
+            });
+        }
+        Ok(address::parse_address(&self.child(index)?.address)? == bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hd_wallet::HdWallet;
+
+    const HARDHAT: &str = "test test test test test test test test test test test junk";
+
+    fn hardhat() -> (HdWallet, WatchOnlyWallet) {
+        let wallet = HdWallet::from_mnemonic(HARDHAT, None).unwrap();
+        let watch_only = WatchOnlyWallet::from_xpub(&wallet.account_key().to_xpub()).unwrap();
+        (wallet, watch_only)
+    }
+
+    #[test]
+    fn verify_child_agrees_with_private_derivation() {
+        let (wallet, watch_only) = hardhat();
+        for child in watch_only.children_range(0, 20).unwrap() {
+            let private = wallet.child(child.index).unwrap();
+            assert_eq!(child.address, private.address);
+            assert_eq!(child.public_key, private.public_key);
+            assert!(watch_only.verify_child(child.index, &private.address.to_lowercase()).unwrap());
+            assert!(!watch_only.verify_child(child.index + 1, &private.address).unwrap());
+        }
+        assert!(watch_only.verify_child(1, "0x70997970C51812dc3A010C7d01b50e0d17dc79C8").unwrap());
+    }
+
+    #[test]
+    fn verify_child_rejects_bad_addresses() {
+        let (_, watch_only) = hardhat();
+        assert!(watch_only.verify_child(1, "0x70997970C51812DC3A010C7D01B50E0D17DC79C8").unwrap());
+        for bad in [
+            "0x70997970c51812dc3A010C7d01b50e0d17dc79C8",
+            "70997970C51812dc3A010C7d01b50e0d17dc79C8",
+            "0x70997970C51812dc3A010C7d01b50e0d17dc79",
+            "0x70997970C51812dc3A010C7d01b50e0d17dc79Cg",
+            "",
+        ] {
+            assert!(matches!(watch_only.verify_child(1, bad), Err(Error::InvalidAddress { .. })), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn account_xpub_derives_the_external_chain() {
+        let (wallet, watch_only) = hardhat();
+        let account = wallet.extended_key(&"m/44'/60'/0'".parse().unwrap()).unwrap();
+        let from_account = WatchOnlyWallet::from_xpub(&account.to_xpub()).unwrap();
+        assert_eq!(from_account.child(7).unwrap().address, watch_only.child(7).unwrap().address);
+    }
+
+    #[test]
+    fn rejects_an_xprv() {
+        let (wallet, watch_only) = hardhat();
+        let xprv = wallet.account_key().to_xprv().unwrap();
+        assert!(matches!(
+            WatchOnlyWallet::from_xpub(&xprv),
+            Err(Error::InvalidArgument { ref argument, .. }) if argument == "xpub"
+        ));
+        assert_eq!(watch_only.split(1, 2).unwrap().account_xpub, wallet.account_key().to_xpub());
+    }
+
+    #[test]
+    fn range_is_checked_before_deriving() {
+        let (_, watch_only) = hardhat();
+        assert_eq!(watch_only.children_range((1 << 31) - 2, 2).unwrap().len(), 2);
+        assert!(matches!(
+            watch_only.children_range((1 << 31) - 2, 3),
+            Err(Error::InvalidArgument { ref argument, .. }) if argument == "child range"
+        ));
+        assert!(watch_only.children_range(u32::MAX, 2).is_err());
+    }
+}